@@ -10,11 +10,11 @@
 // See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
 
 use anyhow::Context as _;
-use serde::Serialize;
 use serde_json::Value as JsonValue;
 use uuid::Uuid;
 
-use snowplow_tracker::{HasSchema, Platform, Schema, SchemaVersion, TrackedEvent, Tracker};
+use snowplow_tracker::events::ScreenView;
+use snowplow_tracker::{Platform, TrackedEvent, Tracker};
 
 #[derive(clap::Parser)]
 struct Args {
@@ -25,23 +25,6 @@ struct Args {
     payload: JsonValue,
 }
 
-// An example unstructured event we might want to track
-#[derive(Debug, Serialize)]
-struct WebPage {
-    name: String,
-    id: String,
-}
-
-impl HasSchema for WebPage {
-    fn schema(&self) -> Schema {
-        Schema::new(
-            "com.snowplowanalytics.snowplow",
-            "screen_view",
-            SchemaVersion::new(1, 0, 0),
-        )
-    }
-}
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let tracker = Tracker::build(
@@ -58,12 +41,10 @@ async fn main() -> anyhow::Result<()> {
 
     tracker
         .track(TrackedEvent {
-            payload: WebPage {
-                name: "test".to_owned(),
-                id: "something else".to_owned(),
-            },
+            payload: ScreenView::new("test", Uuid::new_v4()),
             id: Some(event_id),
             timestamp: None,
+            contexts: Vec::new(),
         })
         .await
         .context("Failed to send snowplow event")?;
@@ -0,0 +1,719 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Parsing for enriched events, as produced by the Snowplow pipeline. This is
+the Rust counterpart of the Snowplow Analytics SDKs: an [`EnrichedEvent`] is
+parsed from a single line of the 131-field enriched TSV format, with its
+self-describing fields decoded into the crate's [`Envelope`] types, and can be
+transformed into the same flattened ("shredded") JSON that the official SDKs
+produce.
+
+```
+use snowplow_tracker::analytics::EnrichedEvent;
+
+# fn example(line: &str) -> Result<(), Box<dyn std::error::Error>> {
+let event: EnrichedEvent = line.parse()?;
+
+if let Some(unstruct_event) = &event.unstruct_event {
+    println!("{}: {}", unstruct_event.schema, unstruct_event.data);
+}
+
+println!("{}", event.to_shredded_json());
+# Ok(())
+# }
+```
+*/
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::payload::{
+    Contexts, Envelope, Platform, PlatformParseError, Schema, SelfDescribingJson, UnstructWrapper,
+};
+
+/// The number of tab-separated fields in an enriched event
+pub const FIELD_COUNT: usize = FIELD_NAMES.len();
+
+/// A timestamp in an enriched event, which the pipeline writes as
+/// `yyyy-MM-dd HH:mm:ss.SSS` in UTC. It's kept in that form;
+/// [`to_iso8601`][Self::to_iso8601] converts it to the form used in shredded
+/// JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EnrichedTimestamp(String);
+
+impl EnrichedTimestamp {
+    /// The timestamp, exactly as it appeared in the enriched event
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The timestamp in ISO 8601 form, like `2022-10-17T09:41:05.123Z`
+    pub fn to_iso8601(&self) -> String {
+        format!("{}Z", self.0.replacen(' ', "T", 1))
+    }
+}
+
+impl Display for EnrichedTimestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for EnrichedTimestamp {
+    type Err = String;
+
+    fn from_str(timestamp: &str) -> Result<Self, Self::Err> {
+        let bytes = timestamp.as_bytes();
+        let is_timestamp = bytes.len() >= 19
+            && bytes[10] == b' '
+            && bytes[4] == b'-'
+            && bytes[7] == b'-'
+            && bytes[13] == b':'
+            && bytes[16] == b':';
+
+        if is_timestamp {
+            Ok(Self(timestamp.to_owned()))
+        } else {
+            Err(format!(
+                "expected a timestamp like \"yyyy-MM-dd HH:mm:ss.SSS\", got {timestamp:?}"
+            ))
+        }
+    }
+}
+
+/// An error parsing an [`EnrichedEvent`]
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum EnrichedEventParseError {
+    /// The line didn't have the right number of fields
+    #[error("expected {FIELD_COUNT} tab-separated fields, but found {0}")]
+    FieldCount(usize),
+
+    /// One of the fields couldn't be parsed
+    #[error("invalid value for field {field}: {reason}")]
+    InvalidField {
+        /// The name of the field
+        field: &'static str,
+
+        /// What was wrong with it
+        reason: String,
+    },
+}
+
+/// A type that can appear as a field of an [`EnrichedEvent`]. This handles
+/// both parsing the field from the TSV and adding it to the shredded JSON.
+trait EnrichedField: Sized {
+    fn parse(raw: &str) -> Result<Self, String>;
+
+    fn shred(&self, key: &'static str, json: &mut JsonMap<String, JsonValue>);
+}
+
+impl EnrichedField for String {
+    fn parse(raw: &str) -> Result<Self, String> {
+        Ok(raw.to_owned())
+    }
+
+    fn shred(&self, key: &'static str, json: &mut JsonMap<String, JsonValue>) {
+        json.insert(key.to_owned(), self.as_str().into());
+    }
+}
+
+impl EnrichedField for i64 {
+    fn parse(raw: &str) -> Result<Self, String> {
+        raw.parse().map_err(|err| format!("{err}: {raw:?}"))
+    }
+
+    fn shred(&self, key: &'static str, json: &mut JsonMap<String, JsonValue>) {
+        json.insert(key.to_owned(), (*self).into());
+    }
+}
+
+impl EnrichedField for f64 {
+    fn parse(raw: &str) -> Result<Self, String> {
+        raw.parse().map_err(|err| format!("{err}: {raw:?}"))
+    }
+
+    fn shred(&self, key: &'static str, json: &mut JsonMap<String, JsonValue>) {
+        json.insert(key.to_owned(), (*self).into());
+    }
+}
+
+impl EnrichedField for bool {
+    fn parse(raw: &str) -> Result<Self, String> {
+        match raw {
+            "1" | "true" => Ok(true),
+            "0" | "false" => Ok(false),
+            _ => Err(format!("expected a boolean, got {raw:?}")),
+        }
+    }
+
+    fn shred(&self, key: &'static str, json: &mut JsonMap<String, JsonValue>) {
+        json.insert(key.to_owned(), (*self).into());
+    }
+}
+
+impl EnrichedField for Uuid {
+    fn parse(raw: &str) -> Result<Self, String> {
+        raw.parse().map_err(|err| format!("{err}: {raw:?}"))
+    }
+
+    fn shred(&self, key: &'static str, json: &mut JsonMap<String, JsonValue>) {
+        json.insert(key.to_owned(), self.to_string().into());
+    }
+}
+
+impl EnrichedField for Platform {
+    fn parse(raw: &str) -> Result<Self, String> {
+        raw.parse()
+            .map_err(|err: PlatformParseError| err.to_string())
+    }
+
+    fn shred(&self, key: &'static str, json: &mut JsonMap<String, JsonValue>) {
+        json.insert(
+            key.to_owned(),
+            serde_json::to_value(self).expect("platforms always serialize successfully"),
+        );
+    }
+}
+
+impl EnrichedField for EnrichedTimestamp {
+    fn parse(raw: &str) -> Result<Self, String> {
+        raw.parse()
+    }
+
+    fn shred(&self, key: &'static str, json: &mut JsonMap<String, JsonValue>) {
+        json.insert(key.to_owned(), self.to_iso8601().into());
+    }
+}
+
+/// The `unstruct_event` field, which is shredded into a single
+/// `unstruct_event_{vendor}_{name}_{major}` key
+impl EnrichedField for SelfDescribingJson {
+    fn parse(raw: &str) -> Result<Self, String> {
+        let Envelope(UnstructWrapper(Envelope(event))) =
+            serde_json::from_str(raw).map_err(|err: serde_json::Error| err.to_string())?;
+
+        Ok(event)
+    }
+
+    fn shred(&self, _key: &'static str, json: &mut JsonMap<String, JsonValue>) {
+        json.insert(
+            shredded_key("unstruct_event", &self.schema),
+            self.data.clone(),
+        );
+    }
+}
+
+/// The `contexts` and `derived_contexts` fields, which are shredded into a
+/// `contexts_{vendor}_{name}_{major}` key per schema, each of which is an
+/// array of all the entities with that schema.
+impl EnrichedField for Contexts {
+    fn parse(raw: &str) -> Result<Self, String> {
+        let Envelope(contexts) =
+            serde_json::from_str(raw).map_err(|err: serde_json::Error| err.to_string())?;
+
+        Ok(contexts)
+    }
+
+    fn shred(&self, _key: &'static str, json: &mut JsonMap<String, JsonValue>) {
+        for Envelope(entity) in &self.0 {
+            let entities = json
+                .entry(shredded_key("contexts", &entity.schema))
+                .or_insert_with(|| JsonValue::Array(Vec::new()));
+
+            if let JsonValue::Array(entities) = entities {
+                entities.push(entity.data.clone());
+            }
+        }
+    }
+}
+
+/// Compute the shredded JSON key for a schema, like
+/// `contexts_com_acme_my_entity_1` for `iglu:com.acme/myEntity/jsonschema/1-0-0`.
+/// This matches the naming used by the Snowplow Analytics SDKs.
+fn shredded_key(prefix: &str, schema: &Schema) -> String {
+    let mut key = format!("{prefix}_");
+
+    key.extend(schema.vendor.chars().map(|c| match c {
+        '.' | '-' => '_',
+        c => c.to_ascii_lowercase(),
+    }));
+    key.push('_');
+
+    let mut previous = None;
+    for c in schema.name.chars() {
+        if c.is_ascii_uppercase()
+            && previous.is_some_and(|p: char| !p.is_ascii_uppercase() && p != '_')
+        {
+            key.push('_');
+        }
+        key.push(match c {
+            '-' => '_',
+            c => c.to_ascii_lowercase(),
+        });
+        previous = Some(c);
+    }
+
+    key.push('_');
+    key.push_str(&schema.version.major.to_string());
+    key
+}
+
+macro_rules! enriched_event {
+    ($($field:ident: $type:ty,)*) => {
+        /**
+        A single enriched event, as output by the Snowplow pipeline. Every
+        field is optional, and is `None` when it was empty in the TSV. The
+        fields are in the same order as the TSV columns.
+
+        See the [Snowplow canonical event model](https://docs.snowplow.io/docs/understanding-your-pipeline/canonical-event/)
+        for details of what each field contains.
+        */
+        #[derive(Debug, Clone, Default, PartialEq)]
+        pub struct EnrichedEvent {
+            $(
+                #[doc = concat!("The `", stringify!($field), "` field")]
+                pub $field: Option<$type>,
+            )*
+        }
+
+        /// The names of the fields of an enriched event, in TSV order
+        pub const FIELD_NAMES: [&str; [$(stringify!($field)),*].len()] =
+            [$(stringify!($field)),*];
+
+        impl EnrichedEvent {
+            fn from_fields(fields: &[&str]) -> Result<Self, EnrichedEventParseError> {
+                let mut fields = fields.iter();
+
+                Ok(Self {
+                    $(
+                        $field: parse_field(
+                            stringify!($field),
+                            fields.next().expect("field count was checked"),
+                        )?,
+                    )*
+                })
+            }
+
+            fn shred_fields(&self, json: &mut JsonMap<String, JsonValue>) {
+                $(
+                    if let Some(value) = &self.$field {
+                        value.shred(stringify!($field), json);
+                    }
+                )*
+            }
+        }
+    };
+}
+
+fn parse_field<T: EnrichedField>(
+    field: &'static str,
+    raw: &str,
+) -> Result<Option<T>, EnrichedEventParseError> {
+    if raw.is_empty() {
+        return Ok(None);
+    }
+
+    T::parse(raw)
+        .map(Some)
+        .map_err(|reason| EnrichedEventParseError::InvalidField { field, reason })
+}
+
+enriched_event! {
+    // Application
+    app_id: String,
+    platform: Platform,
+
+    // Date & time
+    etl_tstamp: EnrichedTimestamp,
+    collector_tstamp: EnrichedTimestamp,
+    dvce_created_tstamp: EnrichedTimestamp,
+
+    // Event
+    event: String,
+    event_id: Uuid,
+    txn_id: i64,
+
+    // Namespacing and versioning
+    name_tracker: String,
+    v_tracker: String,
+    v_collector: String,
+    v_etl: String,
+
+    // User and visit
+    user_id: String,
+    user_ipaddress: String,
+    user_fingerprint: String,
+    domain_userid: String,
+    domain_sessionidx: i64,
+    network_userid: String,
+
+    // Location
+    geo_country: String,
+    geo_region: String,
+    geo_city: String,
+    geo_zipcode: String,
+    geo_latitude: f64,
+    geo_longitude: f64,
+    geo_region_name: String,
+
+    // IP address lookups
+    ip_isp: String,
+    ip_organization: String,
+    ip_domain: String,
+    ip_netspeed: String,
+
+    // Page
+    page_url: String,
+    page_title: String,
+    page_referrer: String,
+
+    // Page URL components
+    page_urlscheme: String,
+    page_urlhost: String,
+    page_urlport: i64,
+    page_urlpath: String,
+    page_urlquery: String,
+    page_urlfragment: String,
+
+    // Referrer URL components
+    refr_urlscheme: String,
+    refr_urlhost: String,
+    refr_urlport: i64,
+    refr_urlpath: String,
+    refr_urlquery: String,
+    refr_urlfragment: String,
+
+    // Referrer details
+    refr_medium: String,
+    refr_source: String,
+    refr_term: String,
+
+    // Marketing
+    mkt_medium: String,
+    mkt_source: String,
+    mkt_term: String,
+    mkt_content: String,
+    mkt_campaign: String,
+
+    // Custom contexts
+    contexts: Contexts,
+
+    // Structured event
+    se_category: String,
+    se_action: String,
+    se_label: String,
+    se_property: String,
+    se_value: f64,
+
+    // Unstructured event
+    unstruct_event: SelfDescribingJson,
+
+    // Ecommerce transaction
+    tr_orderid: String,
+    tr_affiliation: String,
+    tr_total: f64,
+    tr_tax: f64,
+    tr_shipping: f64,
+    tr_city: String,
+    tr_state: String,
+    tr_country: String,
+
+    // Ecommerce transaction item
+    ti_orderid: String,
+    ti_sku: String,
+    ti_name: String,
+    ti_category: String,
+    ti_price: f64,
+    ti_quantity: i64,
+
+    // Page ping
+    pp_xoffset_min: i64,
+    pp_xoffset_max: i64,
+    pp_yoffset_min: i64,
+    pp_yoffset_max: i64,
+
+    // Useragent
+    useragent: String,
+
+    // Browser
+    br_name: String,
+    br_family: String,
+    br_version: String,
+    br_type: String,
+    br_renderengine: String,
+    br_lang: String,
+    br_features_pdf: bool,
+    br_features_flash: bool,
+    br_features_java: bool,
+    br_features_director: bool,
+    br_features_quicktime: bool,
+    br_features_realplayer: bool,
+    br_features_windowsmedia: bool,
+    br_features_gears: bool,
+    br_features_silverlight: bool,
+    br_cookies: bool,
+    br_colordepth: String,
+    br_viewwidth: i64,
+    br_viewheight: i64,
+
+    // Operating System
+    os_name: String,
+    os_family: String,
+    os_manufacturer: String,
+    os_timezone: String,
+
+    // Device and Hardware
+    dvce_type: String,
+    dvce_ismobile: bool,
+    dvce_screenwidth: i64,
+    dvce_screenheight: i64,
+
+    // Document
+    doc_charset: String,
+    doc_width: i64,
+    doc_height: i64,
+
+    // Currency
+    tr_currency: String,
+    tr_total_base: f64,
+    tr_tax_base: f64,
+    tr_shipping_base: f64,
+    ti_currency: String,
+    ti_price_base: f64,
+    base_currency: String,
+
+    // Geolocation
+    geo_timezone: String,
+
+    // Click ID
+    mkt_clickid: String,
+    mkt_network: String,
+
+    // ETL tags
+    etl_tags: String,
+
+    // Time event was sent
+    dvce_sent_tstamp: EnrichedTimestamp,
+
+    // Referer
+    refr_domain_userid: String,
+    refr_dvce_tstamp: EnrichedTimestamp,
+
+    // Derived contexts
+    derived_contexts: Contexts,
+
+    // Session ID
+    domain_sessionid: String,
+
+    // Derived timestamp
+    derived_tstamp: EnrichedTimestamp,
+
+    // Event schema
+    event_vendor: String,
+    event_name: String,
+    event_format: String,
+    event_version: String,
+
+    // Event fingerprint
+    event_fingerprint: String,
+
+    // True timestamp
+    true_tstamp: EnrichedTimestamp,
+}
+
+impl EnrichedEvent {
+    /// Parse an enriched event from a single line of enriched TSV. A
+    /// trailing newline is ignored.
+    pub fn parse(line: &str) -> Result<Self, EnrichedEventParseError> {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let fields: Vec<&str> = line.split('\t').collect();
+
+        if fields.len() != FIELD_COUNT {
+            return Err(EnrichedEventParseError::FieldCount(fields.len()));
+        }
+
+        Self::from_fields(&fields)
+    }
+
+    /// Transform this event into the flattened JSON produced by the Snowplow
+    /// Analytics SDKs. Empty fields are omitted, timestamps are converted to
+    /// ISO 8601, and a `geo_location` field is added if both the latitude and
+    /// longitude are present. The self-describing fields are shredded:
+    ///
+    /// - The `unstruct_event` becomes a single
+    ///   `unstruct_event_{vendor}_{name}_{major}` field with the event's data.
+    /// - Each schema in `contexts` and `derived_contexts` becomes a
+    ///   `contexts_{vendor}_{name}_{major}` field, with an array of the data of
+    ///   every entity with that schema.
+    pub fn to_shredded_json(&self) -> JsonValue {
+        let mut json = JsonMap::new();
+        self.shred_fields(&mut json);
+
+        if let (Some(latitude), Some(longitude)) = (self.geo_latitude, self.geo_longitude) {
+            json.insert(
+                "geo_location".to_owned(),
+                format!("{latitude},{longitude}").into(),
+            );
+        }
+
+        JsonValue::Object(json)
+    }
+}
+
+impl FromStr for EnrichedEvent {
+    type Err = EnrichedEventParseError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        Self::parse(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{shredded_key, EnrichedEvent, EnrichedEventParseError, FIELD_COUNT, FIELD_NAMES};
+    use crate::{Platform, Schema, SchemaVersion};
+
+    fn tsv(values: &[(&str, &str)]) -> String {
+        FIELD_NAMES
+            .iter()
+            .map(|name| {
+                values
+                    .iter()
+                    .find(|(field, _)| field == name)
+                    .map_or("", |&(_, value)| value)
+            })
+            .collect::<Vec<_>>()
+            .join("\t")
+    }
+
+    #[test]
+    fn test_shredded_key() {
+        let schema = Schema::new("com.acme-corp", "myEntity", SchemaVersion::new(2, 1, 0));
+        assert_eq!(
+            shredded_key("contexts", &schema),
+            "contexts_com_acme_corp_my_entity_2"
+        );
+
+        let schema = Schema::new("org.w3", "PerformanceTiming", SchemaVersion::new(1, 0, 0));
+        assert_eq!(
+            shredded_key("contexts", &schema),
+            "contexts_org_w3_performance_timing_1"
+        );
+    }
+
+    #[test]
+    fn test_parse_and_shred() {
+        assert_eq!(FIELD_COUNT, 131);
+
+        let line = tsv(&[
+            ("app_id", "angry-birds"),
+            ("platform", "web"),
+            ("collector_tstamp", "2013-11-26 00:02:05"),
+            ("event", "unstruct"),
+            ("event_id", "c6ef3124-b53a-4b13-a233-0088f79dcbcb"),
+            ("domain_sessionidx", "3"),
+            ("geo_latitude", "37.443604"),
+            ("geo_longitude", "-122.4124"),
+            ("br_features_pdf", "1"),
+            ("dvce_ismobile", "0"),
+            (
+                "contexts",
+                r#"{"schema":"iglu:com.snowplowanalytics.snowplow/contexts/jsonschema/1-0-0","data":[{"schema":"iglu:org.schema/WebPage/jsonschema/1-0-0","data":{"genre":"blog"}},{"schema":"iglu:org.schema/WebPage/jsonschema/1-0-0","data":{"genre":"news"}}]}"#,
+            ),
+            (
+                "unstruct_event",
+                r#"{"schema":"iglu:com.snowplowanalytics.snowplow/unstruct_event/jsonschema/1-0-0","data":{"schema":"iglu:com.snowplowanalytics.snowplow/link_click/jsonschema/1-0-1","data":{"targetUrl":"http://www.example.com"}}}"#,
+            ),
+            (
+                "derived_contexts",
+                r#"{"schema":"iglu:com.snowplowanalytics.snowplow/contexts/jsonschema/1-0-1","data":[{"schema":"iglu:com.snowplowanalytics.snowplow/ua_parser_context/jsonschema/1-0-0","data":{"useragentFamily":"IE"}}]}"#,
+            ),
+        ]) + "\n";
+
+        let event: EnrichedEvent = line.parse().unwrap();
+        assert_eq!(event.app_id.as_deref(), Some("angry-birds"));
+        assert_eq!(event.platform, Some(Platform::Web));
+        assert_eq!(event.domain_sessionidx, Some(3));
+        assert_eq!(event.br_features_pdf, Some(true));
+        assert_eq!(event.page_url, None);
+        assert_eq!(event.contexts.as_ref().unwrap().0.len(), 2);
+        assert_eq!(
+            event.unstruct_event.as_ref().unwrap().schema,
+            Schema::new(
+                "com.snowplowanalytics.snowplow",
+                "link_click",
+                SchemaVersion::new(1, 0, 1)
+            )
+        );
+
+        assert_eq!(
+            event.to_shredded_json(),
+            json!({
+                "app_id": "angry-birds",
+                "platform": "web",
+                "collector_tstamp": "2013-11-26T00:02:05Z",
+                "event": "unstruct",
+                "event_id": "c6ef3124-b53a-4b13-a233-0088f79dcbcb",
+                "domain_sessionidx": 3,
+                "geo_latitude": 37.443604,
+                "geo_longitude": -122.4124,
+                "geo_location": "37.443604,-122.4124",
+                "br_features_pdf": true,
+                "dvce_ismobile": false,
+                "contexts_org_schema_web_page_1": [{"genre": "blog"}, {"genre": "news"}],
+                "unstruct_event_com_snowplowanalytics_snowplow_link_click_1": {
+                    "targetUrl": "http://www.example.com",
+                },
+                "contexts_com_snowplowanalytics_snowplow_ua_parser_context_1": [
+                    {"useragentFamily": "IE"},
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "a\tb\tc".parse::<EnrichedEvent>(),
+            Err(EnrichedEventParseError::FieldCount(3))
+        );
+
+        let error = tsv(&[("txn_id", "lots")])
+            .parse::<EnrichedEvent>()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            EnrichedEventParseError::InvalidField {
+                field: "txn_id",
+                ..
+            }
+        ));
+
+        let error = tsv(&[("contexts", "{}")])
+            .parse::<EnrichedEvent>()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            EnrichedEventParseError::InvalidField {
+                field: "contexts",
+                ..
+            }
+        ));
+    }
+}
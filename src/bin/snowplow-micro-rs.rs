@@ -0,0 +1,38 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+//! A standalone mock Snowplow collector. Usage: `snowplow-micro-rs [ADDRESS]`,
+//! where `ADDRESS` defaults to `127.0.0.1:9090`. See
+//! `snowplow_tracker::micro` for details.
+
+use std::net::SocketAddr;
+
+use snowplow_tracker::micro::Micro;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let address: SocketAddr = match std::env::args().nth(1) {
+        Some(address) => address
+            .parse()
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?,
+        None => ([127, 0, 0, 1], 9090).into(),
+    };
+
+    let micro = Micro::start(address)?;
+    println!("Listening for events on {}", micro.collector_url());
+    println!(
+        "Inspect them at {}",
+        micro.url().join("micro/all").expect("valid path")
+    );
+
+    // Run until the process is killed
+    std::future::pending().await
+}
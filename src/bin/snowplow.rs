@@ -0,0 +1,622 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+//! The `snowplow` command line tool, for sending, validating and replaying
+//! Snowplow events. Run `snowplow --help` for usage.
+//!
+//! Event files (for `send-file` and `validate`) are newline-delimited JSON,
+//! with one self-describing event per line, optionally with a list of
+//! self-describing context entities:
+//!
+//! ```json
+//! {"schema": "iglu:com.example/click/jsonschema/1-0-0", "data": {"target": "button"}, "contexts": []}
+//! ```
+//!
+//! Persisted queues (for `replay`) are newline-delimited JSON, with one
+//! already-built Snowplow event per line, in the same form as the elements of
+//! a `payload_data` batch. Replayed events keep their original event IDs,
+//! timestamps and tracker fields.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::Context as _;
+use clap::{Args, Parser, Subcommand};
+use jsonschema::JSONSchema;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+use snowplow_tracker::emitter::SendOptions;
+use snowplow_tracker::payload::{Envelope, SnowplowEvent};
+use snowplow_tracker::{Platform, Schema, SelfDescribingJson, TrackedEvent, Tracker};
+
+#[derive(Parser)]
+#[clap(about = "Send, validate and replay Snowplow events")]
+struct Cli {
+    #[clap(flatten)]
+    tracker: TrackerArgs,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+/// Flags shared by all of the subcommands
+#[derive(Args)]
+struct TrackerArgs {
+    /// The collector, as a bare host (which uses https) or a full URL. URLs
+    /// without a path get the tp2 path.
+    #[clap(long, global = true, default_value = "http://localhost:9090")]
+    collector: String,
+
+    /// The tracker namespace
+    #[clap(long, global = true, default_value = "snowplow-cli")]
+    namespace: String,
+
+    /// The application ID
+    #[clap(long, global = true, default_value = "snowplow-cli")]
+    app_id: String,
+
+    /// The platform, like `pc` or `srv`
+    #[clap(long, global = true, default_value = "pc")]
+    platform: Platform,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send a single self-describing event
+    Send {
+        /// The schema of the event, like `iglu:com.example/click/jsonschema/1-0-0`
+        #[clap(short, long)]
+        schema: Schema,
+
+        /// The event data, as JSON
+        #[clap(short, long, value_parser = parse_json)]
+        payload: JsonValue,
+
+        /// A context entity to attach, as self-describing JSON. May be given
+        /// more than once.
+        #[clap(long = "context", value_parser = parse_context)]
+        contexts: Vec<SelfDescribingJson>,
+    },
+
+    /// Send every event in a newline-delimited JSON file
+    SendFile {
+        /// The file to send, or `-` for stdin
+        path: PathBuf,
+
+        /// The maximum number of events to send in a single request
+        #[clap(long, default_value_t = 100)]
+        batch_size: usize,
+    },
+
+    /// Validate every event in a newline-delimited JSON file against the
+    /// schemas in a local Iglu repository, without sending anything
+    Validate {
+        /// The file to validate, or `-` for stdin
+        path: PathBuf,
+
+        /// The root of the Iglu repository, containing a `schemas` directory
+        #[clap(long)]
+        iglu: PathBuf,
+    },
+
+    /// Resend the events in a persisted queue
+    Replay {
+        /// The persisted queue to replay, or `-` for stdin
+        path: PathBuf,
+
+        /// The maximum number of events to send in a single request
+        #[clap(long, default_value_t = 100)]
+        batch_size: usize,
+    },
+}
+
+/// A single line of an event file
+#[derive(Deserialize)]
+struct FileEvent {
+    schema: Schema,
+    data: JsonValue,
+    #[serde(default)]
+    contexts: Vec<Envelope<SelfDescribingJson>>,
+}
+
+impl FileEvent {
+    fn into_tracked_event(self) -> TrackedEvent<SelfDescribingJson> {
+        TrackedEvent {
+            payload: SelfDescribingJson::new(self.schema, self.data),
+            id: Some(Uuid::new_v4()),
+            timestamp: None,
+            contexts: self
+                .contexts
+                .into_iter()
+                .map(|Envelope(context)| context)
+                .collect(),
+        }
+    }
+}
+
+fn parse_json(json: &str) -> Result<JsonValue, String> {
+    serde_json::from_str(json).map_err(|err| err.to_string())
+}
+
+fn parse_context(context: &str) -> Result<SelfDescribingJson, String> {
+    serde_json::from_str(context)
+        .map(|Envelope(context)| context)
+        .map_err(|err| err.to_string())
+}
+
+/// Read the non-empty lines of a file (or stdin, for `-`), along with their
+/// line numbers
+fn read_lines(path: &Path) -> anyhow::Result<Vec<(usize, String)>> {
+    let reader: Box<dyn BufRead> = if path == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Box::new(BufReader::new(file))
+    };
+
+    let mut lines = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| format!("Failed to read {}", path.display()))?;
+        if !line.trim().is_empty() {
+            lines.push((index + 1, line));
+        }
+    }
+
+    Ok(lines)
+}
+
+fn read_events(path: &Path) -> anyhow::Result<Vec<FileEvent>> {
+    read_lines(path)?
+        .into_iter()
+        .map(|(number, line)| {
+            serde_json::from_str(&line)
+                .with_context(|| format!("Invalid event on line {number} of {}", path.display()))
+        })
+        .collect()
+}
+
+/// The JSON schemas in a local Iglu repository, which are loaded as they're
+/// needed
+struct IgluRepository {
+    root: PathBuf,
+    schemas: HashMap<Schema, JSONSchema>,
+}
+
+impl IgluRepository {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            schemas: HashMap::new(),
+        }
+    }
+
+    /// Validate some self-describing data, returning a list of everything
+    /// that's wrong with it
+    fn validate(&mut self, schema: &Schema, data: &JsonValue) -> anyhow::Result<Vec<String>> {
+        if !self.schemas.contains_key(schema) {
+            let path = self
+                .root
+                .join("schemas")
+                .join(schema.vendor.as_ref())
+                .join(schema.name.as_ref())
+                .join("jsonschema")
+                .join(schema.version.to_string());
+
+            let file = File::open(&path)
+                .with_context(|| format!("Failed to open schema {schema} at {}", path.display()))?;
+            let json: JsonValue = serde_json::from_reader(BufReader::new(file))
+                .with_context(|| format!("Schema {schema} isn't valid JSON"))?;
+            let compiled = JSONSchema::compile(&json)
+                .map_err(|err| anyhow::anyhow!("Schema {schema} is invalid: {err}"))?;
+
+            self.schemas.insert(schema.clone(), compiled);
+        }
+
+        let errors = match self.schemas[schema].validate(data) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|error| format!("{schema}: {error} (at {})", error.instance_path))
+                .collect(),
+        };
+
+        Ok(errors)
+    }
+}
+
+fn tracker(args: TrackerArgs) -> anyhow::Result<Tracker> {
+    Tracker::builder()
+        .collector(args.collector)
+        .namespace(args.namespace)
+        .app_id(args.app_id)
+        .platform(args.platform)
+        .build()
+        .context("Invalid tracker settings")
+}
+
+/// Send a single event, returning its event ID
+async fn send(
+    tracker: &Tracker,
+    schema: Schema,
+    payload: JsonValue,
+    contexts: Vec<SelfDescribingJson>,
+) -> anyhow::Result<Uuid> {
+    let event_id = Uuid::new_v4();
+    let event = TrackedEvent {
+        payload: SelfDescribingJson::new(schema, payload),
+        id: Some(event_id),
+        timestamp: None,
+        contexts,
+    };
+
+    tracker
+        .track(event)
+        .await
+        .context("Failed to send snowplow event")?;
+
+    Ok(event_id)
+}
+
+/// Send every event in an event file, returning the number sent
+async fn send_file(tracker: &Tracker, path: &Path, batch_size: usize) -> anyhow::Result<usize> {
+    let mut events = read_events(path)?
+        .into_iter()
+        .map(FileEvent::into_tracked_event);
+    let mut sent = 0;
+
+    loop {
+        let batch: Vec<_> = events.by_ref().take(batch_size.max(1)).collect();
+        if batch.is_empty() {
+            break;
+        }
+
+        let count = batch.len();
+        tracker
+            .track_batch(batch)
+            .await
+            .with_context(|| format!("Failed to send events after sending {sent}"))?;
+        sent += count;
+    }
+
+    Ok(sent)
+}
+
+/// The result of validating an event file
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Validation {
+    /// The number of valid events
+    valid: usize,
+
+    /// The invalid events, by their position in the file (counting from 1),
+    /// with everything that's wrong with each of them
+    invalid: Vec<(usize, Vec<String>)>,
+}
+
+/// Validate every event in an event file, and its contexts, against the
+/// schemas in a local Iglu repository
+fn validate(path: &Path, iglu: PathBuf) -> anyhow::Result<Validation> {
+    let mut repository = IgluRepository::new(iglu);
+    let mut validation = Validation::default();
+
+    for (index, event) in read_events(path)?.iter().enumerate() {
+        let mut errors = repository.validate(&event.schema, &event.data)?;
+        for Envelope(context) in &event.contexts {
+            errors.extend(repository.validate(&context.schema, &context.data)?);
+        }
+
+        if errors.is_empty() {
+            validation.valid += 1;
+        } else {
+            validation.invalid.push((index + 1, errors));
+        }
+    }
+
+    Ok(validation)
+}
+
+/// Resend the events in a persisted queue, returning the number sent
+async fn replay(tracker: &Tracker, path: &Path, batch_size: usize) -> anyhow::Result<usize> {
+    let events: Vec<SnowplowEvent<'static, SelfDescribingJson>> = read_lines(path)?
+        .into_iter()
+        .map(|(number, line)| {
+            serde_json::from_str(&line)
+                .with_context(|| format!("Invalid event on line {number} of {}", path.display()))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    let mut sent = 0;
+
+    for batch in events.chunks(batch_size.max(1)) {
+        tracker
+            .emitter()
+            .send_events(batch.iter().cloned(), SendOptions::default())
+            .await
+            .with_context(|| format!("Failed to replay events after sending {sent}"))?;
+        sent += batch.len();
+    }
+
+    Ok(sent)
+}
+
+/// Run a command, writing its report to `out`
+async fn run(cli: Cli, out: &mut impl Write) -> anyhow::Result<ExitCode> {
+    match cli.command {
+        Command::Send {
+            schema,
+            payload,
+            contexts,
+        } => {
+            let event_id = send(&tracker(cli.tracker)?, schema, payload, contexts).await?;
+            writeln!(out, "{event_id}")?;
+        }
+
+        Command::SendFile { path, batch_size } => {
+            let sent = send_file(&tracker(cli.tracker)?, &path, batch_size).await?;
+            writeln!(out, "Sent {sent} events")?;
+        }
+
+        Command::Validate { path, iglu } => {
+            let validation = validate(&path, iglu)?;
+            for (index, errors) in &validation.invalid {
+                writeln!(out, "Event {index} is invalid:")?;
+                for error in errors {
+                    writeln!(out, "  {error}")?;
+                }
+            }
+
+            let invalid = validation.invalid.len();
+            writeln!(out, "{} valid, {invalid} invalid", validation.valid)?;
+            if invalid > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+
+        Command::Replay { path, batch_size } => {
+            let sent = replay(&tracker(cli.tracker)?, &path, batch_size).await?;
+            writeln!(out, "Replayed {sent} events")?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<ExitCode> {
+    run(Cli::parse(), &mut io::stdout()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::process::ExitCode;
+
+    use clap::Parser as _;
+    #[cfg(feature = "micro")]
+    use serde_json::json;
+    #[cfg(feature = "micro")]
+    use snowplow_tracker::{micro::Micro, payload::Envelope};
+
+    use super::{run, tracker, validate, Cli};
+
+    fn fixture(name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", "cli", name]
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_collector() {
+        for (collector, expected) in [
+            (
+                "localhost:9090",
+                "https://localhost:9090/com.snowplowanalytics.snowplow/tp2",
+            ),
+            (
+                "http://localhost:9090",
+                "http://localhost:9090/com.snowplowanalytics.snowplow/tp2",
+            ),
+            (
+                "http://localhost:9090/custom",
+                "http://localhost:9090/custom",
+            ),
+        ] {
+            let cli = Cli::try_parse_from(["snowplow", "send-file", "-", "--collector", collector])
+                .unwrap();
+
+            let endpoints = tracker(cli.tracker).unwrap().emitter().endpoints();
+            assert_eq!(endpoints[0].0.as_str(), expected);
+        }
+
+        let cli = Cli::try_parse_from(["snowplow", "send-file", "-", "--collector", "ftp://host"])
+            .unwrap();
+        assert!(tracker(cli.tracker).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let validation = validate(&fixture("events.ndjson"), fixture("iglu")).unwrap();
+
+        assert_eq!(validation.valid, 1);
+        let invalid: Vec<_> = validation.invalid.iter().map(|(index, _)| *index).collect();
+        assert_eq!(invalid, [2, 3]);
+
+        let (_, errors) = &validation.invalid[0];
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("iglu:com.example/click/jsonschema/1-0-0: "),
+            "{errors:?}"
+        );
+        assert!(errors[0].ends_with("(at /target)"), "{errors:?}");
+
+        // Contexts are validated against their own schemas
+        let (_, errors) = &validation.invalid[1];
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("iglu:com.example/page/jsonschema/1-0-0: "),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn test_validate_missing_schema() {
+        let error = validate(&fixture("events.ndjson"), fixture("missing")).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Failed to open schema iglu:com.example/click/jsonschema/1-0-0"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_command() {
+        let cli = Cli::try_parse_from([
+            "snowplow".as_ref(),
+            "validate".as_ref(),
+            fixture("events.ndjson").as_os_str(),
+            "--iglu".as_ref(),
+            fixture("iglu").as_os_str(),
+        ])
+        .unwrap();
+
+        let mut out = Vec::new();
+        let code = run(cli, &mut out).await.unwrap();
+        assert_eq!(code, ExitCode::FAILURE);
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("Event 2 is invalid:\n  iglu:"), "{out}");
+        assert!(out.ends_with("1 valid, 2 invalid\n"), "{out}");
+    }
+
+    #[cfg(feature = "micro")]
+    #[tokio::test]
+    async fn test_send_command() {
+        let micro = Micro::start(([127, 0, 0, 1], 0).into()).unwrap();
+        let cli = Cli::try_parse_from([
+            "snowplow",
+            "send",
+            "--schema",
+            "iglu:com.example/click/jsonschema/1-0-0",
+            "--payload",
+            r#"{"target": "button"}"#,
+            "--context",
+            r#"{"schema": "iglu:com.example/page/jsonschema/1-0-0", "data": {"id": 5}}"#,
+            "--app-id",
+            "cli-test",
+            "--collector",
+            micro.url().as_str(),
+        ])
+        .unwrap();
+
+        let mut out = Vec::new();
+        let code = run(cli, &mut out).await.unwrap();
+        assert_eq!(code, ExitCode::SUCCESS);
+
+        // The event ID is printed
+        let out = String::from_utf8(out).unwrap();
+        let events = micro.good();
+        assert_eq!(events.len(), 1);
+        let event = &events[0].event;
+        assert_eq!(out, format!("{}\n", event.event_id.as_deref().unwrap()));
+        assert_eq!(event.app_id.as_deref(), Some("cli-test"));
+        assert_eq!(
+            event.unstruct_event.as_ref().unwrap().0.data["target"],
+            "button"
+        );
+
+        assert!(event.contexts.iter().any(|Envelope(context)| {
+            context.schema.to_string() == "iglu:com.example/page/jsonschema/1-0-0"
+                && context.data["id"] == 5
+        }));
+    }
+
+    #[cfg(feature = "micro")]
+    #[tokio::test]
+    async fn test_send_file_command() {
+        let micro = Micro::start(([127, 0, 0, 1], 0).into()).unwrap();
+        let cli = Cli::try_parse_from([
+            "snowplow".as_ref(),
+            "send-file".as_ref(),
+            fixture("events.ndjson").as_os_str(),
+            "--batch-size".as_ref(),
+            "2".as_ref(),
+            "--collector".as_ref(),
+            micro.url().as_str().as_ref(),
+        ])
+        .unwrap();
+
+        let mut out = Vec::new();
+        let code = run(cli, &mut out).await.unwrap();
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(String::from_utf8(out).unwrap(), "Sent 3 events\n");
+
+        // Events are sent as-is, without validation, in batches
+        let summary = micro.summary();
+        assert_eq!((summary.good, summary.bad), (3, 0));
+        let targets: Vec<_> = micro
+            .good()
+            .iter()
+            .map(|event| event.event.unstruct_event.as_ref().unwrap().0.data["target"].clone())
+            .collect();
+        assert_eq!(targets, [json!("button"), json!(5), json!("link")]);
+    }
+
+    #[cfg(feature = "micro")]
+    #[tokio::test]
+    async fn test_replay_command() {
+        let micro = Micro::start(([127, 0, 0, 1], 0).into()).unwrap();
+        let cli = Cli::try_parse_from([
+            "snowplow".as_ref(),
+            "replay".as_ref(),
+            fixture("queue.ndjson").as_os_str(),
+            "--batch-size".as_ref(),
+            "2".as_ref(),
+            "--collector".as_ref(),
+            micro.collector_url().as_str().as_ref(),
+        ])
+        .unwrap();
+
+        let mut out = Vec::new();
+        let code = run(cli, &mut out).await.unwrap();
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(String::from_utf8(out).unwrap(), "Replayed 3 events\n");
+
+        // Replayed events keep their original IDs and tracker fields
+        let events = micro.good();
+        let ids: Vec<_> = events
+            .iter()
+            .map(|event| event.event.event_id.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "8c2a1b52-a2ff-4f2d-9c4d-6f5c8e3b1a7e",
+                "1d7f3a4e-5b6c-4d8e-9f0a-1b2c3d4e5f60",
+                "b3e4f5a6-7b8c-4d9e-8f0a-2b3c4d5e6f70",
+            ]
+        );
+        assert!(events.iter().all(|event| {
+            event.event.app_id.as_deref() == Some("queued-app")
+                && event.event.namespace.as_deref() == Some("queued")
+        }));
+
+        let targets: Vec<_> = events
+            .iter()
+            .map(|event| event.event.unstruct_event.as_ref().unwrap().0.data["target"].clone())
+            .collect();
+        assert_eq!(targets, ["button", "link", "menu"]);
+    }
+}
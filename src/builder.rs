@@ -0,0 +1,529 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+A fluent [`TrackerBuilder`], for setting up a [`Tracker`] along with its
+[`Emitter`], subject, global contexts and plugins in one go.
+
+```
+use snowplow_tracker::{Platform, Subject, Tracker};
+
+# fn example() -> Result<(), snowplow_tracker::builder::BuildError> {
+let tracker = Tracker::builder()
+    .collector("collector.example.com")
+    .namespace("ns")
+    .app_id("my-app")
+    .platform(Platform::ServerSide)
+    .subject(Subject {
+        language: Some("en-GB".to_owned()),
+        ..Subject::default()
+    })
+    .build()?;
+# Ok(())
+# }
+```
+*/
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use reqwest::Url;
+use thiserror::Error;
+
+use crate::circuit_breaker::CircuitBreakerPolicy;
+use crate::emitter::{Compression, Emitter, FailoverPolicy, RetryPolicy, TP2_PATH};
+use crate::payload::{Platform, SelfDescribingJson};
+use crate::pii::PiiPolicy;
+use crate::platform::PlatformContext;
+use crate::plugin::TrackerPlugin;
+use crate::sampling::Sampler;
+use crate::subject::Subject;
+use crate::tracker::{Tracker, TrackerConfig};
+
+/// The namespace used by a [`TrackerBuilder`] if none is given
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// An error building a [`Tracker`] with a [`TrackerBuilder`]
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum BuildError {
+    /// No collector was given
+    #[error("No collector was given")]
+    MissingCollector,
+
+    /// Both a collector and a shared emitter were given
+    #[error("A collector can't be given along with a shared emitter")]
+    CollectorWithSharedEmitter,
+
+    /// No app ID was given
+    #[error("No app ID was given")]
+    MissingAppId,
+
+    /// The namespace was empty
+    #[error("The namespace can't be empty")]
+    EmptyNamespace,
+
+    /// The collector couldn't be parsed as a URL
+    #[error("Invalid collector URL {collector:?}")]
+    InvalidCollector {
+        /// The collector, as it was given
+        collector: String,
+
+        /// The reason it couldn't be parsed
+        #[source]
+        source: url::ParseError,
+    },
+
+    /// The collector URL had a scheme other than `http` or `https`
+    #[error("Unsupported collector URL scheme {0:?}; expected http or https")]
+    UnsupportedScheme(String),
+}
+
+/// Turn a collector, given as either a bare host (like
+/// `collector.example.com`) or a full URL, into the URL of its tp2 endpoint.
+/// Bare hosts use https (which needs the default `rustls-tls` feature), and
+/// URLs without a path get the tp2 path; URLs with any other path are left
+/// as-is, for collectors with a custom path.
+fn collector_endpoint(collector: &str) -> Result<Url, BuildError> {
+    let collector = collector.trim();
+    let full_url = if collector.contains("://") {
+        Cow::Borrowed(collector)
+    } else {
+        Cow::Owned(format!("https://{collector}"))
+    };
+
+    let mut url = Url::parse(&full_url).map_err(|source| BuildError::InvalidCollector {
+        collector: collector.to_owned(),
+        source,
+    })?;
+
+    match url.scheme() {
+        "http" | "https" => {}
+        scheme => return Err(BuildError::UnsupportedScheme(scheme.to_owned())),
+    }
+
+    if url.path() == "/" {
+        url.set_path(TP2_PATH);
+    }
+
+    Ok(url)
+}
+
+/// A builder for a [`Tracker`]; see [`Tracker::builder`]. Only the collector
+/// (or a [shared emitter][Self::shared_emitter]) and app ID are required. The namespace defaults to
+/// [`DEFAULT_NAMESPACE`], the platform to [`Platform::App`], and the emitter
+/// options to the same defaults as [`Emitter::new`].
+#[must_use]
+pub struct TrackerBuilder {
+    collector: Option<String>,
+    secondary_collectors: Vec<String>,
+    failover_policy: FailoverPolicy,
+    emitter: Option<Arc<Emitter>>,
+    namespace: Cow<'static, str>,
+    app_id: Option<String>,
+    platform: Platform,
+    client: Option<reqwest::Client>,
+    retry_policy: RetryPolicy,
+    max_batch_size: Option<usize>,
+    compression: Compression,
+    circuit_breaker: Option<CircuitBreakerPolicy>,
+    subject: Subject,
+    global_contexts: Vec<SelfDescribingJson>,
+    platform_contexts: Vec<PlatformContext>,
+    plugins: Vec<Box<dyn TrackerPlugin>>,
+    sampler: Option<Sampler>,
+    pii_policy: Option<PiiPolicy>,
+}
+
+impl Default for TrackerBuilder {
+    fn default() -> Self {
+        Self {
+            collector: None,
+            secondary_collectors: Vec::new(),
+            failover_policy: FailoverPolicy::default(),
+            emitter: None,
+            namespace: Cow::Borrowed(DEFAULT_NAMESPACE),
+            app_id: None,
+            platform: Platform::default(),
+            client: None,
+            retry_policy: RetryPolicy::default(),
+            max_batch_size: None,
+            compression: Compression::default(),
+            circuit_breaker: None,
+            subject: Subject::default(),
+            global_contexts: Vec::new(),
+            platform_contexts: Vec::new(),
+            plugins: Vec::new(),
+            sampler: None,
+            pii_policy: None,
+        }
+    }
+}
+
+impl TrackerBuilder {
+    /// Create a new builder with nothing set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the collector, as either a bare host like
+    /// `collector.example.com` (which uses https), or a full URL. If the URL
+    /// has no path, `/com.snowplowanalytics.snowplow/tp2` is used.
+    pub fn collector(mut self, collector: impl Into<String>) -> Self {
+        self.collector = Some(collector.into());
+        self
+    }
+
+    /// Add a secondary collector, given like the
+    /// [primary one][Self::collector], which events are sent to when the
+    /// collectors before it are failing; see [`Emitter::with_failover`].
+    pub fn secondary_collector(mut self, collector: impl Into<String>) -> Self {
+        self.secondary_collectors.push(collector.into());
+        self
+    }
+
+    /// Set how the emitter fails over between collectors, if there are any
+    /// [secondary collectors][Self::secondary_collector]
+    pub fn failover_policy(mut self, policy: FailoverPolicy) -> Self {
+        self.failover_policy = policy;
+        self
+    }
+
+    /// Send events through an existing [`Emitter`], shared with other
+    /// trackers (see [`Tracker::shared_emitter`]), instead of creating a new
+    /// one. In that case there's no need for a collector, and the emitter
+    /// options (client, retry policy, batch size, compression, circuit
+    /// breaker and failover) of this builder aren't used.
+    pub fn shared_emitter(mut self, emitter: Arc<Emitter>) -> Self {
+        self.emitter = Some(emitter);
+        self
+    }
+
+    /// Set the tracker namespace
+    pub fn namespace(mut self, namespace: impl Into<Cow<'static, str>>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Set the application ID
+    pub fn app_id(mut self, app_id: impl Into<String>) -> Self {
+        self.app_id = Some(app_id.into());
+        self
+    }
+
+    /// Set the platform
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Set the HTTP client used to send events
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Set the emitter's [`RetryPolicy`]; see
+    /// [`Emitter::with_retry_policy`].
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Set the emitter's maximum batch size; see
+    /// [`Emitter::with_max_batch_size`].
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = Some(max_batch_size);
+        self
+    }
+
+    /// Set the emitter's [`Compression`]; see [`Emitter::with_compression`].
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Give the emitter a circuit breaker; see
+    /// [`Emitter::with_circuit_breaker`].
+    pub fn circuit_breaker(mut self, policy: CircuitBreakerPolicy) -> Self {
+        self.circuit_breaker = Some(policy);
+        self
+    }
+
+    /// Set the tracker's initial [`Subject`]
+    pub fn subject(mut self, subject: Subject) -> Self {
+        self.subject = subject;
+        self
+    }
+
+    /// Add a context entity to attach to every event; see
+    /// [`Tracker::add_global_context`].
+    pub fn global_context(mut self, context: SelfDescribingJson) -> Self {
+        self.global_contexts.push(context);
+        self
+    }
+
+    /// Add a [`PlatformContext`] entity to attach to every event; see
+    /// [`Tracker::set_platform_contexts`].
+    pub fn platform_context(mut self, context: PlatformContext) -> Self {
+        self.platform_contexts.push(context);
+        self
+    }
+
+    /// Add a [`TrackerPlugin`]. Plugins are run in the order they're added.
+    pub fn plugin(mut self, plugin: impl TrackerPlugin + 'static) -> Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// Set the tracker's [`Sampler`]
+    pub fn sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Set the tracker's [`PiiPolicy`]
+    pub fn pii_policy(mut self, policy: PiiPolicy) -> Self {
+        self.pii_policy = Some(policy);
+        self
+    }
+
+    /// Validate the settings and build the [`Tracker`]
+    pub fn build(self) -> Result<Tracker, BuildError> {
+        let app_id = self.app_id.ok_or(BuildError::MissingAppId)?;
+
+        if self.namespace.is_empty() {
+            return Err(BuildError::EmptyNamespace);
+        }
+
+        if self.emitter.is_some() && !self.secondary_collectors.is_empty() {
+            return Err(BuildError::CollectorWithSharedEmitter);
+        }
+
+        let emitter = match (self.emitter, self.collector) {
+            (Some(_), Some(_)) => return Err(BuildError::CollectorWithSharedEmitter),
+            (None, None) => return Err(BuildError::MissingCollector),
+            (Some(emitter), None) => emitter,
+            (None, Some(collector)) => {
+                let emitter = Emitter::new(
+                    collector_endpoint(&collector)?,
+                    self.client.unwrap_or_default(),
+                )
+                .with_retry_policy(self.retry_policy)
+                .with_compression(self.compression);
+
+                let emitter = match self.max_batch_size {
+                    Some(max_batch_size) => emitter.with_max_batch_size(max_batch_size),
+                    None => emitter,
+                };
+
+                let emitter = if self.secondary_collectors.is_empty() {
+                    emitter
+                } else {
+                    let secondaries = self
+                        .secondary_collectors
+                        .iter()
+                        .map(|collector| collector_endpoint(collector))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    emitter.with_failover(secondaries, self.failover_policy)
+                };
+
+                Arc::new(match self.circuit_breaker {
+                    Some(policy) => emitter.with_circuit_breaker(policy),
+                    None => emitter,
+                })
+            }
+        };
+
+        let mut tracker = Tracker::new(
+            emitter,
+            TrackerConfig {
+                namespace: self.namespace,
+                platform: self.platform,
+                app_id,
+            },
+        );
+
+        tracker.set_subject(self.subject);
+        self.global_contexts
+            .into_iter()
+            .for_each(|context| tracker.add_global_context(context));
+        tracker.set_platform_contexts(self.platform_contexts);
+        self.plugins
+            .into_iter()
+            .for_each(|plugin| tracker.add_boxed_plugin(plugin));
+
+        if let Some(sampler) = self.sampler {
+            tracker.set_sampler(sampler);
+        }
+
+        if let Some(policy) = self.pii_policy {
+            tracker.set_pii_policy(policy);
+        }
+
+        Ok(tracker)
+    }
+}
+
+impl Tracker {
+    /// Create a [`TrackerBuilder`], for setting up a tracker with
+    /// validation and sensible defaults.
+    pub fn builder() -> TrackerBuilder {
+        TrackerBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+    #[cfg(feature = "rustls-tls")]
+    use tokio::{io::AsyncReadExt as _, net::TcpListener};
+
+    use super::{collector_endpoint, BuildError};
+    use crate::testing::CaptureServer;
+    use crate::{Schema, SchemaVersion, SelfDescribingJson, Subject, TrackedEvent, Tracker};
+
+    #[test]
+    fn test_collector_endpoint() {
+        for (collector, expected) in [
+            (
+                "collector.example.com",
+                "https://collector.example.com/com.snowplowanalytics.snowplow/tp2",
+            ),
+            (
+                "http://localhost:9090",
+                "http://localhost:9090/com.snowplowanalytics.snowplow/tp2",
+            ),
+            (
+                "https://collector.example.com/com.snowplowanalytics.snowplow/tp2",
+                "https://collector.example.com/com.snowplowanalytics.snowplow/tp2",
+            ),
+            (
+                "https://collector.example.com/custom/path",
+                "https://collector.example.com/custom/path",
+            ),
+        ] {
+            assert_eq!(collector_endpoint(collector).unwrap().as_str(), expected);
+        }
+
+        assert_eq!(
+            collector_endpoint("ftp://collector.example.com"),
+            Err(BuildError::UnsupportedScheme("ftp".to_owned()))
+        );
+        assert!(matches!(
+            collector_endpoint("http://"),
+            Err(BuildError::InvalidCollector { .. })
+        ));
+    }
+
+    #[test]
+    fn test_missing_settings() {
+        assert_eq!(
+            Tracker::builder().app_id("app").build().err(),
+            Some(BuildError::MissingCollector)
+        );
+        assert_eq!(
+            Tracker::builder()
+                .collector("collector.example.com")
+                .build()
+                .err(),
+            Some(BuildError::MissingAppId)
+        );
+
+        let tracker = Tracker::builder()
+            .collector("collector.example.com")
+            .app_id("app")
+            .build()
+            .unwrap();
+        assert_eq!(
+            Tracker::builder()
+                .collector("collector.example.com")
+                .shared_emitter(tracker.shared_emitter())
+                .app_id("app")
+                .build()
+                .err(),
+            Some(BuildError::CollectorWithSharedEmitter)
+        );
+        assert_eq!(
+            Tracker::builder()
+                .collector("collector.example.com")
+                .app_id("app")
+                .namespace("")
+                .build()
+                .err(),
+            Some(BuildError::EmptyNamespace)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_global_contexts_and_subject() {
+        let server = CaptureServer::start(200).await;
+        let tracker = Tracker::builder()
+            .collector(server.url().as_str())
+            .app_id("app")
+            .subject(Subject {
+                user_id: Some("user".to_owned()),
+                ..Subject::default()
+            })
+            .global_context(SelfDescribingJson::new(
+                Schema::new("com.example", "global", SchemaVersion::new(1, 0, 0)),
+                json!({"a": 1}),
+            ))
+            .build()
+            .unwrap();
+
+        tracker
+            .track(TrackedEvent::new(SelfDescribingJson::new(
+                Schema::new("com.example", "test", SchemaVersion::new(1, 0, 0)),
+                json!({}),
+            )))
+            .await
+            .unwrap();
+
+        let body: JsonValue = serde_json::from_str(&server.bodies()[0]).unwrap();
+        let event = &body["data"][0];
+        assert_eq!(event["tna"], "default");
+        assert_eq!(event["uid"], "user");
+
+        let contexts: JsonValue = serde_json::from_str(event["co"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            contexts["data"],
+            json!([{"schema": "iglu:com.example/global/jsonschema/1-0-0", "data": {"a": 1}}])
+        );
+    }
+
+    #[cfg(feature = "rustls-tls")]
+    #[tokio::test]
+    async fn test_bare_hosts_use_tls() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let tracker = Tracker::builder()
+            .collector(address.to_string())
+            .app_id("app")
+            .build()
+            .unwrap();
+
+        let first_byte = async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.read_u8().await.unwrap()
+        };
+        let track = tracker.track(TrackedEvent::new(SelfDescribingJson::new(
+            Schema::new("com.example", "test", SchemaVersion::new(1, 0, 0)),
+            json!({}),
+        )));
+
+        // The tracker starts a TLS handshake, which begins with a handshake
+        // record (type 0x16)
+        tokio::select! {
+            result = track => panic!("tracking finished before connecting: {result:?}"),
+            first_byte = first_byte => assert_eq!(first_byte, 0x16),
+        }
+    }
+}
@@ -0,0 +1,373 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+A circuit breaker for an [`Emitter`][crate::emitter::Emitter], so that a
+collector that's down doesn't make every `track` call wait for a network
+timeout; see [`Emitter::with_circuit_breaker`][crate::emitter::Emitter::with_circuit_breaker].
+
+The breaker starts [closed](CircuitState::Closed), with requests going
+through as usual. After [`failure_threshold`](CircuitBreakerPolicy::failure_threshold)
+batches in a row fail to reach the collector, it [opens](CircuitState::Open):
+for the next [`cooldown`](CircuitBreakerPolicy::cooldown), batches fail
+immediately with [`SendError::CircuitOpen`][crate::emitter::SendError::CircuitOpen],
+without any network calls. After that it's [half-open](CircuitState::HalfOpen):
+a single batch is sent as a probe, and the breaker closes again if it's
+delivered, or opens for another cooldown if it isn't.
+
+Only failures to reach a healthy collector count: connection errors, and
+5xx or 429 responses. Other error responses mean the collector is up, so they
+count as successes, as far as the breaker is concerned.
+
+State transitions are logged under the `snowplow_tracker::circuit_breaker`
+target (opening at `warn` level, half-opening and closing at `info`), and the
+number of times the breaker opened is counted in the emitter's
+[metrics][crate::metrics].
+*/
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::Url;
+
+/// When an emitter's circuit breaker opens, and for how long; see the
+/// [module docs][self].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerPolicy {
+    /// The number of batches in a row that have to fail before the breaker
+    /// opens. A value of 0 is treated the same as 1.
+    pub failure_threshold: u32,
+
+    /// How long the breaker stays open before probing the collector again
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreakerPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The state of a circuit breaker; see the [module docs][self].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent as usual
+    Closed,
+
+    /// Requests fail immediately, without any network calls
+    Open,
+
+    /// The cooldown is over, and the next batch will be sent as a probe
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probing: bool },
+}
+
+/// A change in the state of a [`CircuitBreaker`], returned so that the
+/// emitter can update its metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Transition {
+    /// The cooldown is over, and a probe is being sent
+    HalfOpened,
+
+    /// Too many batches failed in a row
+    Opened { failures: u32 },
+
+    /// The probe failed
+    Reopened,
+
+    /// A batch succeeded while the breaker was open or half-open
+    Closed,
+}
+
+impl Transition {
+    /// Whether this transition opened the breaker
+    pub(crate) fn opened(self) -> bool {
+        matches!(self, Transition::Opened { .. } | Transition::Reopened)
+    }
+}
+
+/// The live state of a circuit breaker. This is used both for the emitter
+/// as a whole and, with failover, for each of its collector endpoints; the
+/// two log their state transitions with different messages.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    policy: CircuitBreakerPolicy,
+    state: Mutex<State>,
+    /// The collector endpoint this breaker tracks the health of, if it's one
+    /// of the endpoints of an emitter with failover
+    endpoint: Option<Url>,
+}
+
+impl CircuitBreaker {
+    /// Create the circuit breaker of an emitter
+    pub(crate) fn new(policy: CircuitBreakerPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(State::Closed { failures: 0 }),
+            endpoint: None,
+        }
+    }
+
+    /// Create a circuit breaker tracking the health of a single collector
+    /// endpoint, for failover
+    pub(crate) fn for_endpoint(policy: CircuitBreakerPolicy, endpoint: Url) -> Self {
+        Self {
+            endpoint: Some(endpoint),
+            ..Self::new(policy)
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner())
+    }
+
+    /// Get the current state of the breaker
+    pub(crate) fn state(&self) -> CircuitState {
+        match *self.lock() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { until } if Instant::now() < until => CircuitState::Open,
+            State::Open { .. } | State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Check if a batch can be sent now. If the breaker is half-open, this
+    /// makes the batch the probe, and no other batches are allowed until its
+    /// outcome is [recorded][Permit::record], or its permit is dropped.
+    pub(crate) fn allow(&self) -> Option<Permit<'_>> {
+        let mut state = self.lock();
+        let probe = match *state {
+            State::Closed { .. } => false,
+            State::Open { until } if Instant::now() < until => return None,
+            State::Open { .. } => {
+                self.log(Transition::HalfOpened);
+                true
+            }
+            State::HalfOpen { probing: true } => return None,
+            State::HalfOpen { probing: false } => true,
+        };
+
+        if probe {
+            *state = State::HalfOpen { probing: true };
+        }
+
+        Some(Permit {
+            breaker: self,
+            probe,
+        })
+    }
+
+    /// Send a batch regardless of the state of the breaker. Its outcome is
+    /// still recorded, but it's never the probe.
+    pub(crate) fn bypass(&self) -> Permit<'_> {
+        Permit {
+            breaker: self,
+            probe: false,
+        }
+    }
+
+    /// Record whether an allowed batch reached a healthy collector
+    fn record(&self, healthy: bool) -> Option<Transition> {
+        let mut state = self.lock();
+        let open = State::Open {
+            until: Instant::now() + self.policy.cooldown,
+        };
+
+        let transition = match (&*state, healthy) {
+            (State::Closed { .. }, true) => {
+                *state = State::Closed { failures: 0 };
+                None
+            }
+            (State::Open { .. }, true) | (State::HalfOpen { .. }, true) => {
+                *state = State::Closed { failures: 0 };
+                Some(Transition::Closed)
+            }
+            (&State::Closed { failures }, false) => {
+                let failures = failures + 1;
+                if failures >= self.policy.failure_threshold.max(1) {
+                    *state = open;
+                    Some(Transition::Opened { failures })
+                } else {
+                    *state = State::Closed { failures };
+                    None
+                }
+            }
+            (State::HalfOpen { .. }, false) => {
+                *state = open;
+                Some(Transition::Reopened)
+            }
+            // A batch that was allowed before the breaker opened; it's
+            // already open, so there's nothing more to do
+            (State::Open { .. }, false) => None,
+        };
+
+        if let Some(transition) = transition {
+            self.log(transition);
+        }
+        transition
+    }
+
+    /// A probe was abandoned before its outcome was known, so let the next
+    /// batch be the probe instead
+    fn abandon_probe(&self) {
+        let mut state = self.lock();
+        if let State::HalfOpen { probing: true } = *state {
+            match &self.endpoint {
+                None => tracing::debug!("circuit breaker probe was cancelled"),
+                Some(url) => {
+                    tracing::debug!(endpoint = %url, "collector endpoint probe was cancelled")
+                }
+            }
+            *state = State::HalfOpen { probing: false };
+        }
+    }
+
+    fn log(&self, transition: Transition) {
+        let cooldown_ms = self.policy.cooldown.as_millis() as u64;
+
+        match (&self.endpoint, transition) {
+            (None, Transition::HalfOpened) => {
+                tracing::info!("circuit breaker half-open; probing the collector")
+            }
+            (None, Transition::Opened { failures }) => tracing::warn!(
+                failures,
+                cooldown_ms,
+                "circuit breaker opened; the collector is failing"
+            ),
+            (None, Transition::Reopened) => {
+                tracing::warn!(cooldown_ms, "circuit breaker probe failed; opened again")
+            }
+            (None, Transition::Closed) => {
+                tracing::info!("circuit breaker closed; the collector is reachable again")
+            }
+            (Some(url), Transition::HalfOpened) => tracing::info!(
+                endpoint = %url,
+                "collector endpoint half-open; probing it"
+            ),
+            (Some(url), Transition::Opened { failures }) => tracing::warn!(
+                endpoint = %url,
+                failures,
+                cooldown_ms,
+                "collector endpoint unhealthy; skipping it"
+            ),
+            (Some(url), Transition::Reopened) => tracing::warn!(
+                endpoint = %url,
+                cooldown_ms,
+                "collector endpoint probe failed; skipping it again"
+            ),
+            (Some(url), Transition::Closed) => tracing::info!(
+                endpoint = %url,
+                "collector endpoint healthy again"
+            ),
+        }
+    }
+}
+
+/// Permission from a [`CircuitBreaker`] to send a batch. The outcome of the
+/// batch should be [recorded][Self::record]; if the batch is the half-open
+/// probe, and the permit is dropped without recording anything (for
+/// instance, because the future sending the batch was cancelled), the next
+/// batch becomes the probe instead.
+#[derive(Debug)]
+#[must_use]
+pub(crate) struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl Permit<'_> {
+    /// Record whether the batch reached a healthy collector, returning the
+    /// resulting state transition, if any
+    pub(crate) fn record(mut self, healthy: bool) -> Option<Transition> {
+        self.probe = false;
+        self.breaker.record(healthy)
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.abandon_probe();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CircuitBreaker, CircuitBreakerPolicy, CircuitState, Transition};
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerPolicy {
+            failure_threshold: 2,
+            cooldown: Duration::from_millis(20),
+        })
+    }
+
+    #[test]
+    fn test_state_transitions() {
+        let breaker = breaker();
+
+        assert_eq!(breaker.allow().unwrap().record(false), None);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(
+            breaker.allow().unwrap().record(false),
+            Some(Transition::Opened { failures: 2 })
+        );
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.allow().is_none());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        // Only one probe at a time
+        let probe = breaker.allow().unwrap();
+        assert!(breaker.allow().is_none());
+        assert_eq!(probe.record(false), Some(Transition::Reopened));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(30));
+        let probe = breaker.allow().unwrap();
+        assert_eq!(probe.record(true), Some(Transition::Closed));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.allow().is_some());
+    }
+
+    #[test]
+    fn test_dropped_probe() {
+        let breaker = breaker();
+        for _ in 0..2 {
+            let _ = breaker.allow().unwrap().record(false);
+        }
+
+        std::thread::sleep(Duration::from_millis(30));
+        let probe = breaker.allow().unwrap();
+        assert!(breaker.allow().is_none());
+
+        // The probe never finished, so the next batch becomes the probe
+        drop(probe);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        let probe = breaker.allow().unwrap();
+        assert!(breaker.allow().is_none());
+        assert_eq!(probe.record(true), Some(Transition::Closed));
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+User consent. A [`Tracker`][crate::Tracker] has a [`ConsentLevel`], which can
+be changed at any time while it's in use, and which controls how much (if
+anything) the tracker sends. This module also contains the Snowplow
+first-party consent events, which record consent changes themselves.
+*/
+
+use serde::Serialize;
+
+use crate::payload::{HasSchema, Schema, SchemaVersion, SelfDescribingJson};
+
+/// How much the user has consented to being tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum ConsentLevel {
+    /// Events are tracked normally, with all of the user identifiers in the
+    /// tracker's [`Subject`][crate::Subject].
+    #[default]
+    Full = 0,
+
+    /// Events are tracked, but user identifiers (user ID, domain and network
+    /// user IDs, session ID and IP address) are stripped, and the collector
+    /// is asked not to record any identifying information of its own.
+    Anonymous = 1,
+
+    /// Nothing is tracked. Events are silently dropped.
+    None = 2,
+}
+
+impl ConsentLevel {
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            0 => ConsentLevel::Full,
+            1 => ConsentLevel::Anonymous,
+            _ => ConsentLevel::None,
+        }
+    }
+}
+
+/// A document describing what the user consented to (a privacy policy, for
+/// instance). Attached as a context entity to [`ConsentGranted`] and
+/// [`ConsentWithdrawn`] events.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConsentDocument {
+    /// An identifier for the document
+    pub id: String,
+
+    /// The version of the document
+    pub version: String,
+
+    /// The name of the document
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// A description of the document
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl ConsentDocument {
+    /// Create a new consent document with just an ID and a version
+    pub fn new(id: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            version: version.into(),
+            name: None,
+            description: None,
+        }
+    }
+}
+
+impl HasSchema for ConsentDocument {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("consent_document", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+impl From<&ConsentDocument> for SelfDescribingJson {
+    fn from(document: &ConsentDocument) -> Self {
+        SelfDescribingJson::from_payload(document)
+            .expect("consent documents always serialize successfully")
+    }
+}
+
+/// An event recording that the user granted consent to the attached
+/// [`ConsentDocument`]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConsentGranted {
+    /// When this consent expires, as an ISO 8601 date-time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry: Option<String>,
+}
+
+impl HasSchema for ConsentGranted {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("consent_granted", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// An event recording that the user withdrew consent to the attached
+/// [`ConsentDocument`]s, or to everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConsentWithdrawn {
+    /// If true, the user withdrew consent to everything, not just the
+    /// attached documents
+    pub all: bool,
+}
+
+impl HasSchema for ConsentWithdrawn {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("consent_withdrawn", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{ConsentDocument, ConsentGranted, ConsentLevel, ConsentWithdrawn};
+    use crate::testing::CaptureServer;
+    use crate::{Schema, SchemaVersion, SelfDescribingJson, Subject, TrackedEvent};
+
+    fn test_event() -> TrackedEvent<SelfDescribingJson> {
+        TrackedEvent::new(SelfDescribingJson::new(
+            Schema::new("com.example", "test", SchemaVersion::new(1, 0, 0)),
+            json!({}),
+        ))
+    }
+
+    fn subject() -> Subject {
+        Subject {
+            user_id: Some("user".to_owned()),
+            ip_address: Some("203.0.113.42".to_owned()),
+            network_user_id: Some("nuid".to_owned()),
+            domain_user_id: Some("duid".to_owned()),
+            session_id: Some("session".to_owned()),
+            language: Some("en-GB".to_owned()),
+            ..Subject::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consent_levels() {
+        let server = CaptureServer::start(200).await;
+        let tracker = server.tracker();
+        tracker.set_subject(subject());
+
+        tracker.track(test_event()).await.unwrap();
+
+        tracker.set_consent(ConsentLevel::Anonymous);
+        tracker.track(test_event()).await.unwrap();
+
+        tracker.set_consent(ConsentLevel::None);
+        tracker.track(test_event()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let events = server.events();
+
+        let full = &events[0].params;
+        assert_eq!(requests[0].header("sp-anonymous"), None);
+        assert_eq!(full["uid"], "user");
+        assert_eq!(full["ip"], "203.0.113.42");
+
+        let anonymous = events[1].params.as_object().unwrap();
+        assert_eq!(requests[1].header("sp-anonymous"), Some("*"));
+        for field in ["uid", "ip", "tnuid", "duid", "sid"] {
+            assert!(!anonymous.contains_key(field), "{field} was sent");
+        }
+        assert_eq!(anonymous["lang"], "en-GB");
+    }
+
+    #[tokio::test]
+    async fn test_consent_events() {
+        let server = CaptureServer::start(200).await;
+        let tracker = server.tracker();
+        tracker.set_subject(subject());
+        tracker.set_consent(ConsentLevel::None);
+
+        let documents = [ConsentDocument::new("privacy-policy", "2")];
+
+        tracker
+            .grant_consent(
+                ConsentLevel::Anonymous,
+                ConsentGranted::default(),
+                &documents,
+            )
+            .await
+            .unwrap();
+        assert_eq!(tracker.consent(), ConsentLevel::Anonymous);
+
+        tracker
+            .withdraw_consent(
+                ConsentLevel::None,
+                ConsentWithdrawn { all: true },
+                &documents,
+            )
+            .await
+            .unwrap();
+        assert_eq!(tracker.consent(), ConsentLevel::None);
+
+        let events = server.events();
+        assert_eq!(events.len(), 2);
+
+        assert_eq!(
+            events[0].payload.as_ref().unwrap()["schema"],
+            "iglu:com.snowplowanalytics.snowplow/consent_granted/jsonschema/1-0-0"
+        );
+        assert_eq!(
+            events[1].contexts[0],
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/consent_document/jsonschema/1-0-0",
+                "data": {"id": "privacy-policy", "version": "2"},
+            })
+        );
+    }
+}
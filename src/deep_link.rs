@@ -0,0 +1,277 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Deep link attribution. When the app is opened from a deep link, track it with
+[`Tracker::deep_link_received`][crate::Tracker::deep_link_received]; like the
+mobile trackers, the tracker then attaches the link as a `deep_link` entity to
+the next [screen view][crate::events::ScreenView], so that the screen can be
+attributed to the link (and to whatever referred the user to it).
+
+```no_run
+use snowplow_tracker::deep_link::DeepLink;
+use snowplow_tracker::events::ScreenView;
+use snowplow_tracker::{TrackedEvent, Tracker};
+use uuid::Uuid;
+
+# async fn example(tracker: Tracker) -> Result<(), Box<dyn std::error::Error>> {
+let link = DeepLink::parse(
+    "onepassword://open/vault?id=42",
+    Some("https://start.1password.com/"),
+)?;
+tracker.deep_link_received(link).await?;
+
+// The deep link is attached to this screen view
+tracker
+    .track(TrackedEvent::new(ScreenView::new("vault", Uuid::new_v4())))
+    .await?;
+# Ok(())
+# }
+```
+*/
+
+use serde::Serialize;
+use thiserror::Error;
+use url::Url;
+
+use crate::payload::{HasSchema, Schema, SchemaVersion};
+
+/// The vendor of the deep link schemas
+const MOBILE_VENDOR: &str = "com.snowplowanalytics.mobile";
+
+/// An error parsing a [`DeepLink`]
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum DeepLinkError {
+    /// The deep link URL was invalid
+    #[error("Invalid deep link URL {url:?}")]
+    Url {
+        /// The URL, as it was given
+        url: String,
+
+        /// The reason it couldn't be parsed
+        #[source]
+        source: url::ParseError,
+    },
+
+    /// The referrer URL was invalid
+    #[error("Invalid deep link referrer {referrer:?}")]
+    Referrer {
+        /// The referrer, as it was given
+        referrer: String,
+
+        /// The reason it couldn't be parsed
+        #[source]
+        source: url::ParseError,
+    },
+}
+
+/// A deep link that the app was opened from. This is attached to the next
+/// screen view after it's received as a `deep_link` entity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeepLink {
+    /// The URL of the deep link
+    pub url: Url,
+
+    /// The URL of the page or app that referred the user to the deep link
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referrer: Option<Url>,
+}
+
+impl DeepLink {
+    /// Create a new deep link with no referrer
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            referrer: None,
+        }
+    }
+
+    /// Parse a deep link from its URL and optional referrer
+    pub fn parse(url: &str, referrer: Option<&str>) -> Result<Self, DeepLinkError> {
+        let url = Url::parse(url).map_err(|source| DeepLinkError::Url {
+            url: url.to_owned(),
+            source,
+        })?;
+
+        let referrer = referrer
+            .map(|referrer| {
+                Url::parse(referrer).map_err(|source| DeepLinkError::Referrer {
+                    referrer: referrer.to_owned(),
+                    source,
+                })
+            })
+            .transpose()?;
+
+        Ok(Self { url, referrer })
+    }
+
+    /// Set the referrer of the deep link
+    #[must_use]
+    pub fn with_referrer(mut self, referrer: Url) -> Self {
+        self.referrer = Some(referrer);
+        self
+    }
+}
+
+impl HasSchema for DeepLink {
+    fn schema(&self) -> Schema {
+        Schema::new(MOBILE_VENDOR, "deep_link", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// An event recording that the app received a [`DeepLink`]. Usually this is
+/// tracked with [`Tracker::deep_link_received`][crate::Tracker::deep_link_received].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct DeepLinkReceived(pub DeepLink);
+
+impl HasSchema for DeepLinkReceived {
+    fn schema(&self) -> Schema {
+        Schema::new(
+            MOBILE_VENDOR,
+            "deep_link_received",
+            SchemaVersion::new(1, 0, 0),
+        )
+    }
+}
+
+/// Check if a schema is for a screen view, of any version
+pub(crate) fn is_screen_view(schema: &Schema) -> bool {
+    schema.vendor == "com.snowplowanalytics.snowplow" && schema.name == "screen_view"
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+    use uuid::Uuid;
+
+    use super::{DeepLink, DeepLinkError};
+    use crate::events::{ScreenView, Timing};
+    use crate::testing::CaptureServer;
+    use crate::{PluginAction, SelfDescribingJson, TrackedEvent, Tracker, TrackerPlugin};
+
+    #[test]
+    fn test_parse() {
+        let link = DeepLink::parse(
+            "onepassword://open/vault?id=42",
+            Some("https://example.com"),
+        )
+        .unwrap();
+
+        assert_eq!(link.url.scheme(), "onepassword");
+        assert_eq!(
+            serde_json::to_value(&link).unwrap(),
+            json!({
+                "url": "onepassword://open/vault?id=42",
+                "referrer": "https://example.com/",
+            })
+        );
+
+        assert!(matches!(
+            DeepLink::parse("https://example.com", Some("not a url")),
+            Err(DeepLinkError::Referrer { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_deep_link_is_attached_to_next_screen_view() {
+        let server = CaptureServer::start(200).await;
+        let tracker = Tracker::builder()
+            .collector(server.url().as_str())
+            .app_id("app")
+            .build()
+            .unwrap();
+
+        let link = DeepLink::parse("onepassword://open/vault", None).unwrap();
+        tracker.deep_link_received(link).await.unwrap();
+
+        tracker
+            .track(TrackedEvent::new(Timing::new("startup", "load", 10)))
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            tracker
+                .track(TrackedEvent::new(ScreenView::new("vault", Uuid::new_v4())))
+                .await
+                .unwrap();
+        }
+
+        let events: Vec<JsonValue> = server
+            .bodies()
+            .iter()
+            .map(|body| serde_json::from_str::<JsonValue>(body).unwrap()["data"][0].clone())
+            .collect();
+
+        let payload: JsonValue =
+            serde_json::from_str(events[0]["ue_pr"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            payload["data"],
+            json!({
+                "schema": "iglu:com.snowplowanalytics.mobile/deep_link_received/jsonschema/1-0-0",
+                "data": {"url": "onepassword://open/vault"},
+            })
+        );
+
+        // Only the first screen view gets the deep link
+        assert!(events[1].get("co").is_none());
+        let contexts: JsonValue = serde_json::from_str(events[2]["co"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            contexts["data"],
+            json!([{
+                "schema": "iglu:com.snowplowanalytics.mobile/deep_link/jsonschema/1-0-0",
+                "data": {"url": "onepassword://open/vault"},
+            }])
+        );
+        assert!(events[3].get("co").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_deep_link_survives_dropped_screen_view() {
+        struct DropScreen;
+
+        impl TrackerPlugin for DropScreen {
+            fn before_track(&self, event: &mut TrackedEvent<SelfDescribingJson>) -> PluginAction {
+                if event.payload.data["name"] == "dropped" {
+                    PluginAction::Drop
+                } else {
+                    PluginAction::Keep
+                }
+            }
+        }
+
+        let server = CaptureServer::start(200).await;
+        let mut tracker = Tracker::builder()
+            .collector(server.url().as_str())
+            .app_id("app")
+            .build()
+            .unwrap();
+        tracker.add_plugin(DropScreen);
+
+        let link = DeepLink::parse("onepassword://open/vault", None).unwrap();
+        tracker.deep_link_received(link).await.unwrap();
+        tracker
+            .track_batch([
+                TrackedEvent::new(ScreenView::new("dropped", Uuid::new_v4())),
+                TrackedEvent::new(ScreenView::new("vault", Uuid::new_v4())),
+            ])
+            .await
+            .unwrap();
+
+        let body: JsonValue = serde_json::from_str(&server.bodies()[1]).unwrap();
+        let events = body["data"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        let contexts: JsonValue = serde_json::from_str(events[0]["co"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            contexts["data"][0]["schema"],
+            "iglu:com.snowplowanalytics.mobile/deep_link/jsonschema/1-0-0"
+        );
+    }
+}
@@ -0,0 +1,724 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Ecommerce tracking. There are two ways to track purchases with Snowplow:
+
+- The legacy [`Transaction`] and [`TransactionItem`] events, which use
+  dedicated tracker protocol fields (`tr_*` and `ti_*`) rather than a schema.
+  Track them with
+  [`Tracker::track_transaction`][crate::Tracker::track_transaction], which
+  sends one transaction event and one event for each of its items.
+- The [`EcommerceAction`] self-describing event, which describes any step of
+  the shopping journey (viewing a product, adding it to the cart, checking
+  out, etc), with [`Product`], [`Cart`] and [`EcommerceTransaction`] entities
+  attached. Track it with
+  [`Tracker::track_ecommerce_action`][crate::Tracker::track_ecommerce_action].
+
+```no_run
+use snowplow_tracker::ecommerce::{
+    ActionType, EcommerceAction, EcommerceTransaction, Product, Transaction, TransactionItem,
+};
+use snowplow_tracker::Tracker;
+
+# async fn example(tracker: Tracker) -> Result<(), snowplow_tracker::TrackError> {
+tracker
+    .track_transaction(
+        &Transaction::new("order-1", 19.9)
+            .with_currency("EUR")
+            .with_item(TransactionItem::new("sku-1", 9.95, 2).with_name("Socks")),
+    )
+    .await?;
+
+tracker
+    .track_ecommerce_action(
+        EcommerceAction::new(ActionType::Transaction)
+            .with_product(Product::new("sku-1", "clothing", 9.95, "EUR").with_quantity(2))
+            .with_transaction(EcommerceTransaction::new("order-1", 19.9, "EUR", "card")),
+    )
+    .await?;
+# Ok(())
+# }
+```
+*/
+
+use serde::Serialize;
+
+use crate::payload::{HasSchema, Schema, SchemaVersion, SelfDescribingJson};
+use crate::tracker::TrackedEvent;
+
+/// The vendor of the [`EcommerceAction`] event and its entities
+const ECOMMERCE_VENDOR: &str = "com.snowplowanalytics.snowplow.ecommerce";
+
+/// A legacy ecommerce transaction, tracked with
+/// [`Tracker::track_transaction`][crate::Tracker::track_transaction].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    /// The order ID
+    pub order_id: String,
+
+    /// The total value of the transaction
+    pub total: f64,
+
+    /// The affiliation (store, or partner) of the transaction
+    pub affiliation: Option<String>,
+
+    /// The tax included in the total
+    pub tax: Option<f64>,
+
+    /// The shipping cost included in the total
+    pub shipping: Option<f64>,
+
+    /// The delivery city
+    pub city: Option<String>,
+
+    /// The delivery state or province
+    pub state: Option<String>,
+
+    /// The delivery country
+    pub country: Option<String>,
+
+    /// The currency of the transaction and its items, as an ISO 4217 code
+    pub currency: Option<String>,
+
+    /// The items in the transaction
+    pub items: Vec<TransactionItem>,
+}
+
+impl Transaction {
+    /// Create a new transaction, without any items
+    pub fn new(order_id: impl Into<String>, total: f64) -> Self {
+        Self {
+            order_id: order_id.into(),
+            total,
+            affiliation: None,
+            tax: None,
+            shipping: None,
+            city: None,
+            state: None,
+            country: None,
+            currency: None,
+            items: Vec::new(),
+        }
+    }
+
+    /// Set the affiliation of the transaction
+    #[must_use]
+    pub fn with_affiliation(mut self, affiliation: impl Into<String>) -> Self {
+        self.affiliation = Some(affiliation.into());
+        self
+    }
+
+    /// Set the tax included in the total
+    #[must_use]
+    pub fn with_tax(mut self, tax: f64) -> Self {
+        self.tax = Some(tax);
+        self
+    }
+
+    /// Set the shipping cost included in the total
+    #[must_use]
+    pub fn with_shipping(mut self, shipping: f64) -> Self {
+        self.shipping = Some(shipping);
+        self
+    }
+
+    /// Set the delivery address: city, state or province, and country
+    #[must_use]
+    pub fn with_address(
+        mut self,
+        city: impl Into<String>,
+        state: impl Into<String>,
+        country: impl Into<String>,
+    ) -> Self {
+        self.city = Some(city.into());
+        self.state = Some(state.into());
+        self.country = Some(country.into());
+        self
+    }
+
+    /// Set the currency of the transaction and its items
+    #[must_use]
+    pub fn with_currency(mut self, currency: impl Into<String>) -> Self {
+        self.currency = Some(currency.into());
+        self
+    }
+
+    /// Add an item to the transaction
+    #[must_use]
+    pub fn with_item(mut self, item: TransactionItem) -> Self {
+        self.items.push(item);
+        self
+    }
+}
+
+/// An item in a legacy ecommerce [`Transaction`]. Items are sent as separate
+/// events, with the order ID and currency of their transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionItem {
+    /// The SKU of the product
+    pub sku: String,
+
+    /// The unit price of the product
+    pub price: f64,
+
+    /// The number of units bought
+    pub quantity: i64,
+
+    /// The name of the product
+    pub name: Option<String>,
+
+    /// The category of the product
+    pub category: Option<String>,
+}
+
+impl TransactionItem {
+    /// Create a new transaction item
+    pub fn new(sku: impl Into<String>, price: f64, quantity: i64) -> Self {
+        Self {
+            sku: sku.into(),
+            price,
+            quantity,
+            name: None,
+            category: None,
+        }
+    }
+
+    /// Set the name of the product
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the category of the product
+    #[must_use]
+    pub fn with_category(mut self, category: impl Into<String>) -> Self {
+        self.category = Some(category.into());
+        self
+    }
+}
+
+/// The kind of an [`EcommerceAction`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionType {
+    /// Products were added to the cart
+    AddToCart,
+
+    /// Products were removed from the cart
+    RemoveFromCart,
+
+    /// A product's page or details were viewed
+    ProductView,
+
+    /// A product in a list was clicked
+    ListClick,
+
+    /// A list of products was viewed
+    ListView,
+
+    /// A promotion was clicked
+    PromoClick,
+
+    /// A promotion was viewed
+    PromoView,
+
+    /// A step of the checkout was completed
+    CheckoutStep,
+
+    /// A transaction was completed
+    Transaction,
+
+    /// A transaction was refunded
+    Refund,
+
+    /// A transaction failed
+    #[serde(rename = "trns_error")]
+    TransactionError,
+}
+
+/// A self-describing ecommerce event, with its [`Product`], [`Cart`] and
+/// [`EcommerceTransaction`] entities. Track it with
+/// [`Tracker::track_ecommerce_action`][crate::Tracker::track_ecommerce_action],
+/// or convert it into a [`TrackedEvent`] with
+/// [`into_tracked_event`][Self::into_tracked_event].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EcommerceAction {
+    /// The kind of action
+    #[serde(rename = "type")]
+    pub action_type: ActionType,
+
+    /// The name of the list, for list actions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The products the action applies to, each sent as a `product` entity
+    #[serde(skip)]
+    pub products: Vec<Product>,
+
+    /// The cart the action applies to, sent as a `cart` entity
+    #[serde(skip)]
+    pub cart: Option<Cart>,
+
+    /// The transaction the action applies to, sent as a `transaction` entity
+    #[serde(skip)]
+    pub transaction: Option<EcommerceTransaction>,
+}
+
+impl EcommerceAction {
+    /// Create a new ecommerce action, without any entities
+    pub fn new(action_type: ActionType) -> Self {
+        Self {
+            action_type,
+            name: None,
+            products: Vec::new(),
+            cart: None,
+            transaction: None,
+        }
+    }
+
+    /// Set the name of the list, for list actions
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Add a product the action applies to
+    #[must_use]
+    pub fn with_product(mut self, product: Product) -> Self {
+        self.products.push(product);
+        self
+    }
+
+    /// Set the cart the action applies to
+    #[must_use]
+    pub fn with_cart(mut self, cart: Cart) -> Self {
+        self.cart = Some(cart);
+        self
+    }
+
+    /// Set the transaction the action applies to
+    #[must_use]
+    pub fn with_transaction(mut self, transaction: EcommerceTransaction) -> Self {
+        self.transaction = Some(transaction);
+        self
+    }
+
+    /// Convert this action into an event, with its entities attached as
+    /// context entities
+    pub fn into_tracked_event(self) -> TrackedEvent<Self> {
+        let products = self.products.iter().map(SelfDescribingJson::from);
+        let cart = self.cart.iter().map(SelfDescribingJson::from);
+        let transaction = self.transaction.iter().map(SelfDescribingJson::from);
+        let contexts = products.chain(cart).chain(transaction).collect();
+
+        TrackedEvent {
+            contexts,
+            ..TrackedEvent::new(self)
+        }
+    }
+}
+
+impl HasSchema for EcommerceAction {
+    fn schema(&self) -> Schema {
+        Schema::new(
+            ECOMMERCE_VENDOR,
+            "snowplow_ecommerce_action",
+            SchemaVersion::new(1, 0, 2),
+        )
+    }
+}
+
+/// A product entity, attached to an [`EcommerceAction`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Product {
+    /// The SKU or ID of the product
+    pub id: String,
+
+    /// The category of the product
+    pub category: String,
+
+    /// The price of the product, at the time of the action
+    pub price: f64,
+
+    /// The currency of the price, as an ISO 4217 code
+    pub currency: String,
+
+    /// The name of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The recommended or advertised price of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_price: Option<f64>,
+
+    /// The quantity of the product in the action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<u64>,
+
+    /// The size of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+
+    /// The variant of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+
+    /// The brand of the product
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brand: Option<String>,
+
+    /// Whether the product is in stock, like `"in stock"` or `"backorder"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inventory_status: Option<String>,
+
+    /// The position of the product in a list, starting from 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<u64>,
+}
+
+impl Product {
+    /// Create a new product entity
+    pub fn new(
+        id: impl Into<String>,
+        category: impl Into<String>,
+        price: f64,
+        currency: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            category: category.into(),
+            price,
+            currency: currency.into(),
+            name: None,
+            list_price: None,
+            quantity: None,
+            size: None,
+            variant: None,
+            brand: None,
+            inventory_status: None,
+            position: None,
+        }
+    }
+
+    /// Set the name of the product
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the recommended or advertised price of the product
+    #[must_use]
+    pub fn with_list_price(mut self, list_price: f64) -> Self {
+        self.list_price = Some(list_price);
+        self
+    }
+
+    /// Set the quantity of the product in the action
+    #[must_use]
+    pub fn with_quantity(mut self, quantity: u64) -> Self {
+        self.quantity = Some(quantity);
+        self
+    }
+
+    /// Set the brand of the product
+    #[must_use]
+    pub fn with_brand(mut self, brand: impl Into<String>) -> Self {
+        self.brand = Some(brand.into());
+        self
+    }
+
+    /// Set the position of the product in a list
+    #[must_use]
+    pub fn with_position(mut self, position: u64) -> Self {
+        self.position = Some(position);
+        self
+    }
+}
+
+impl HasSchema for Product {
+    fn schema(&self) -> Schema {
+        Schema::new(ECOMMERCE_VENDOR, "product", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// A cart entity, attached to an [`EcommerceAction`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cart {
+    /// The total value of the cart, after the action
+    pub total_value: f64,
+
+    /// The currency of the cart, as an ISO 4217 code
+    pub currency: String,
+
+    /// The ID of the cart
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cart_id: Option<String>,
+}
+
+impl Cart {
+    /// Create a new cart entity
+    pub fn new(total_value: f64, currency: impl Into<String>) -> Self {
+        Self {
+            total_value,
+            currency: currency.into(),
+            cart_id: None,
+        }
+    }
+
+    /// Set the ID of the cart
+    #[must_use]
+    pub fn with_cart_id(mut self, cart_id: impl Into<String>) -> Self {
+        self.cart_id = Some(cart_id.into());
+        self
+    }
+}
+
+impl HasSchema for Cart {
+    fn schema(&self) -> Schema {
+        Schema::new(ECOMMERCE_VENDOR, "cart", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// A transaction entity, attached to an [`EcommerceAction`]. This is
+/// unrelated to the legacy [`Transaction`] event.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EcommerceTransaction {
+    /// The ID of the transaction
+    pub transaction_id: String,
+
+    /// The total value of the transaction
+    pub revenue: f64,
+
+    /// The currency of the transaction, as an ISO 4217 code
+    pub currency: String,
+
+    /// How the transaction was paid for, like `"card"`
+    pub payment_method: String,
+
+    /// The total number of items in the transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_quantity: Option<u64>,
+
+    /// The tax included in the revenue
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tax: Option<f64>,
+
+    /// The shipping cost included in the revenue
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping: Option<f64>,
+
+    /// The discount code used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discount_code: Option<String>,
+
+    /// The discount amount taken off the revenue
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discount_amount: Option<f64>,
+
+    /// Whether the transaction was paid on credit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credit_order: Option<bool>,
+}
+
+impl EcommerceTransaction {
+    /// Create a new transaction entity
+    pub fn new(
+        transaction_id: impl Into<String>,
+        revenue: f64,
+        currency: impl Into<String>,
+        payment_method: impl Into<String>,
+    ) -> Self {
+        Self {
+            transaction_id: transaction_id.into(),
+            revenue,
+            currency: currency.into(),
+            payment_method: payment_method.into(),
+            total_quantity: None,
+            tax: None,
+            shipping: None,
+            discount_code: None,
+            discount_amount: None,
+            credit_order: None,
+        }
+    }
+
+    /// Set the total number of items in the transaction
+    #[must_use]
+    pub fn with_total_quantity(mut self, total_quantity: u64) -> Self {
+        self.total_quantity = Some(total_quantity);
+        self
+    }
+
+    /// Set the tax and shipping cost included in the revenue
+    #[must_use]
+    pub fn with_costs(mut self, tax: f64, shipping: f64) -> Self {
+        self.tax = Some(tax);
+        self.shipping = Some(shipping);
+        self
+    }
+
+    /// Set the discount code used, and the amount it took off
+    #[must_use]
+    pub fn with_discount(mut self, code: impl Into<String>, amount: f64) -> Self {
+        self.discount_code = Some(code.into());
+        self.discount_amount = Some(amount);
+        self
+    }
+}
+
+impl HasSchema for EcommerceTransaction {
+    fn schema(&self) -> Schema {
+        Schema::new(ECOMMERCE_VENDOR, "transaction", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+macro_rules! entity_into_self_describing {
+    ($($entity:ident)*) => {$(
+        impl From<&$entity> for SelfDescribingJson {
+            fn from(entity: &$entity) -> Self {
+                SelfDescribingJson::from_payload(entity)
+                    .expect("ecommerce entities always serialize successfully")
+            }
+        }
+    )*};
+}
+
+entity_into_self_describing! { Product Cart EcommerceTransaction }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        ActionType, Cart, EcommerceAction, EcommerceTransaction, Product, Transaction,
+        TransactionItem,
+    };
+    use crate::payload::Envelope;
+    use crate::testing::CaptureServer;
+
+    #[tokio::test]
+    async fn test_transaction() {
+        let server = CaptureServer::start(200).await;
+        let transaction = Transaction::new("order-1", 25.5)
+            .with_affiliation("web")
+            .with_tax(4.25)
+            .with_shipping(3.0)
+            .with_address("Berlin", "Berlin", "DE")
+            .with_currency("EUR")
+            .with_item(TransactionItem::new("sku-1", 9.25, 2).with_name("Socks"))
+            .with_item(TransactionItem::new("sku-2", 4.0, 1).with_category("misc"));
+
+        server
+            .tracker()
+            .track_transaction(&transaction)
+            .await
+            .unwrap();
+
+        let events = server.events();
+        assert_eq!(events.len(), 3);
+
+        let transaction = events[0].params.as_object().unwrap();
+        assert_eq!(transaction["e"], "tr");
+        assert!(!transaction.contains_key("ue_pr"));
+        for (field, value) in [
+            ("tr_id", "order-1"),
+            ("tr_af", "web"),
+            ("tr_tt", "25.5"),
+            ("tr_tx", "4.25"),
+            ("tr_sh", "3"),
+            ("tr_ci", "Berlin"),
+            ("tr_st", "Berlin"),
+            ("tr_co", "DE"),
+            ("tr_cu", "EUR"),
+        ] {
+            assert_eq!(transaction[field], value, "{field}");
+        }
+
+        let item = events[1].params.as_object().unwrap();
+        assert_eq!(item["e"], "ti");
+        for (field, value) in [
+            ("ti_id", "order-1"),
+            ("ti_sk", "sku-1"),
+            ("ti_nm", "Socks"),
+            ("ti_pr", "9.25"),
+            ("ti_qu", "2"),
+            ("ti_cu", "EUR"),
+        ] {
+            assert_eq!(item[field], value, "{field}");
+        }
+        assert!(!item.contains_key("ti_ca"));
+        assert_eq!(events[2].params["ti_ca"], "misc");
+    }
+
+    #[tokio::test]
+    async fn test_ecommerce_action() {
+        let server = CaptureServer::start(200).await;
+        let action = EcommerceAction::new(ActionType::Transaction)
+            .with_product(
+                Product::new("sku-1", "clothing", 9.25, "EUR")
+                    .with_name("Socks")
+                    .with_quantity(2),
+            )
+            .with_cart(Cart::new(18.5, "EUR").with_cart_id("cart-1"))
+            .with_transaction(
+                EcommerceTransaction::new("order-1", 18.5, "EUR", "card").with_total_quantity(2),
+            );
+
+        assert_eq!(
+            serde_json::to_value(Envelope(&action)).unwrap(),
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow.ecommerce/snowplow_ecommerce_action/jsonschema/1-0-2",
+                "data": {"type": "transaction"},
+            })
+        );
+
+        server
+            .tracker()
+            .track_ecommerce_action(action)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            json!(server.events()[0].contexts),
+            json!([
+                {
+                    "schema": "iglu:com.snowplowanalytics.snowplow.ecommerce/product/jsonschema/1-0-0",
+                    "data": {
+                        "id": "sku-1",
+                        "category": "clothing",
+                        "price": 9.25,
+                        "currency": "EUR",
+                        "name": "Socks",
+                        "quantity": 2,
+                    },
+                },
+                {
+                    "schema": "iglu:com.snowplowanalytics.snowplow.ecommerce/cart/jsonschema/1-0-0",
+                    "data": {"total_value": 18.5, "currency": "EUR", "cart_id": "cart-1"},
+                },
+                {
+                    "schema": "iglu:com.snowplowanalytics.snowplow.ecommerce/transaction/jsonschema/1-0-0",
+                    "data": {
+                        "transaction_id": "order-1",
+                        "revenue": 18.5,
+                        "currency": "EUR",
+                        "payment_method": "card",
+                        "total_quantity": 2,
+                    },
+                },
+            ])
+        );
+    }
+}
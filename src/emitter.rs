@@ -16,21 +16,41 @@ events over HTTP to a Collector. Generally you should prefer to use a
 of the bookkeeping required to construct full snowplow events.
  */
 
-use std::future::ready;
+use std::future::{ready, Future};
+use std::io::{self, Write as _};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
 
+use flate2::write::GzEncoder;
 use futures::TryStreamExt as _;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE, COOKIE, SET_COOKIE,
+};
 use reqwest::Client;
+use reqwest::StatusCode;
 use reqwest::Url;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::broadcast;
+use uuid::Uuid;
 
+use crate::circuit_breaker::{
+    CircuitBreaker, CircuitBreakerPolicy, CircuitState, Permit, Transition,
+};
+use crate::metrics::{EmitterMetrics, MetricsSnapshot, Queued};
 use crate::payload::{Envelope, HasSchema, Schema, SchemaVersion, SnowplowEvent};
 
 /// The outermost type that is actually sent to snowplow as a JSON payload.
-/// Includes an outermost schema and a Vec of [`SnowplowEvent`].
+/// Includes an outermost schema and a Vec of [`SnowplowEvent`]. This is also
+/// known as a `payload_data` batch.
 // TODO: It will be exceedingly common to only need to send a single event;
 // create an optimized version of this type to handle that use case.
-type EventContainer<'a, Payload> = Envelope<Vec<SnowplowEvent<'a, Payload>>>;
+pub type EventContainer<'a, Payload> = Envelope<Vec<SnowplowEvent<'a, Payload>>>;
 
 impl<'a, Payload: HasSchema> EventContainer<'a, Payload> {
     /// Create a new event container. This will collect all of the given events
@@ -46,22 +66,581 @@ impl<'a, Payload: HasSchema> HasSchema for Vec<SnowplowEvent<'a, Payload>> {
     }
 }
 
+/// The path of the collector endpoint for POST requests, which is where an
+/// [`Emitter`] usually sends its events
+pub const TP2_PATH: &str = "/com.snowplowanalytics.snowplow/tp2";
+
+/// The header that tells a collector to not record any identifying
+/// information (IP address, network user ID cookie) for a request.
+const ANONYMOUS_HEADER: HeaderName = HeaderName::from_static("sp-anonymous");
+
+/// The name of the cookie in which the collector sets the network user ID
+const NETWORK_USER_ID_COOKIE: &str = "sp";
+
+/// A hook that can modify the headers of every request; see
+/// [`Emitter::with_request_decorator`]
+type RequestDecorator =
+    Box<dyn Fn(HeaderMap) -> Pin<Box<dyn Future<Output = HeaderMap> + Send>> + Send + Sync>;
+
+/// A hook that sees the status and headers of every response; see
+/// [`Emitter::with_response_hook`]
+type ResponseHook = Box<dyn Fn(StatusCode, &HeaderMap) + Send + Sync>;
+
+/// Options for sending a single batch of events
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SendOptions {
+    /// If true, the collector will be asked (via the `SP-Anonymous` header)
+    /// not to record any identifying information about this request, like
+    /// the IP address or network user ID cookie.
+    pub anonymous: bool,
+}
+
+/// How an [`Emitter`] compresses request bodies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Request bodies are sent uncompressed
+    #[default]
+    None,
+
+    /// Request bodies are compressed with gzip, and sent with a
+    /// `Content-Encoding: gzip` header. Make sure your collector supports
+    /// this before enabling it.
+    Gzip,
+}
+
+/// An error parsing a [`Compression`] from a string
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("Invalid compression {0:?}; expected \"none\" or \"gzip\"")]
+pub struct CompressionParseError(String);
+
+impl FromStr for Compression {
+    type Err = CompressionParseError;
+
+    fn from_str(compression: &str) -> Result<Self, Self::Err> {
+        match compression {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            _ => Err(CompressionParseError(compression.to_owned())),
+        }
+    }
+}
+
+/// Serialize some JSON into a gzip-compressed buffer
+fn gzip_json(value: &impl Serialize) -> Result<Vec<u8>, serde_json::Error> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    serde_json::to_writer(&mut encoder, value)?;
+    encoder
+        .flush()
+        .and_then(|()| encoder.finish())
+        .map_err(serde_json::Error::io)
+}
+
+/// The number of outcomes that can be buffered for each subscriber before
+/// the oldest ones are lost; see [`Emitter::subscribe`].
+const OUTCOME_CHANNEL_CAPACITY: usize = 1024;
+
+/// How an [`Emitter`] retries requests that fail. Requests are retried if
+/// they fail to connect, or if the collector responds with a 5xx or 429
+/// status; other failures are final. The delay between attempts starts at
+/// `initial_backoff` and doubles after each attempt, up to `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts for each request, including the first
+    /// one. A value of 0 is treated the same as 1.
+    pub max_attempts: u32,
+
+    /// The delay before the first retry
+    pub initial_backoff: Duration,
+
+    /// The longest delay between retries
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub const fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    /// The delay after the given (1-indexed) attempt failed
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Find the network user ID in the `Set-Cookie` headers of a response
+fn network_user_id_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .find_map(|cookie| {
+            let (name, value) = cookie.split(';').next()?.split_once('=')?;
+            (name.trim() == NETWORK_USER_ID_COOKIE).then(|| value.trim())
+        })
+        .filter(|id| !id.is_empty())
+}
+
+/// The network user ID captured from collector responses, optionally
+/// persisted to a file; see [`Emitter::with_network_user_id`].
+#[derive(Debug, Default)]
+struct NetworkUserId {
+    id: RwLock<Option<String>>,
+    path: Option<PathBuf>,
+}
+
+impl NetworkUserId {
+    /// Load a previously persisted network user ID from a file. A missing
+    /// file just means there's no ID yet.
+    fn load(path: PathBuf) -> Self {
+        let id = match std::fs::read_to_string(&path) {
+            Ok(id) => Some(id.trim().to_owned()).filter(|id| !id.is_empty()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                tracing::warn!(path = %path.display(), error = %err, "failed to load network user ID");
+                None
+            }
+        };
+
+        Self {
+            id: RwLock::new(id),
+            path: Some(path),
+        }
+    }
+
+    fn get(&self) -> Option<String> {
+        self.id
+            .read()
+            .unwrap_or_else(|poison| poison.into_inner())
+            .clone()
+    }
+
+    /// Remember a network user ID from a response, persisting it if it
+    /// changed
+    fn update(&self, new_id: &str) {
+        {
+            let mut id = self.id.write().unwrap_or_else(|poison| poison.into_inner());
+            if id.as_deref() == Some(new_id) {
+                return;
+            }
+            *id = Some(new_id.to_owned());
+        }
+
+        tracing::debug!("captured new network user ID");
+        if let Some(path) = &self.path {
+            if let Err(err) = std::fs::write(path, new_id) {
+                tracing::warn!(path = %path.display(), error = %err, "failed to persist network user ID");
+            }
+        }
+    }
+}
+
+/// An error sending a batch of events to the collector
+#[derive(Debug, Error)]
+pub enum SendError {
+    /// The request failed: it couldn't reach the collector, or the collector
+    /// responded with an HTTP error code
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    /// The emitter's [circuit breaker][crate::circuit_breaker] is open,
+    /// because the collector has been failing, so no request was made
+    #[error("The collector is unavailable; the circuit breaker is open")]
+    CircuitOpen,
+}
+
+impl SendError {
+    /// The HTTP status the collector responded with, if it responded
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            SendError::Http(err) => err.status(),
+            SendError::CircuitOpen => None,
+        }
+    }
+}
+
+/// How an [`Emitter`] with several collector endpoints picks which one to
+/// send each batch to; see [`Emitter::with_failover`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailoverStrategy {
+    /// Send every batch to the first healthy endpoint, in order, so that
+    /// secondary endpoints are only used while the primary one is failing
+    #[default]
+    Primary,
+
+    /// Spread batches across the healthy endpoints in turn
+    RoundRobin,
+}
+
+/// How an [`Emitter`] fails over between its collector endpoints; see
+/// [`Emitter::with_failover`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailoverPolicy {
+    /// Which endpoint each batch is sent to first
+    pub strategy: FailoverStrategy,
+
+    /// When an endpoint is considered unhealthy, and how long it's skipped
+    /// for before it's tried again. Each endpoint has its own
+    /// [circuit breaker][crate::circuit_breaker] with this policy.
+    pub endpoint_health: CircuitBreakerPolicy,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            strategy: FailoverStrategy::Primary,
+            endpoint_health: CircuitBreakerPolicy {
+                failure_threshold: 1,
+                cooldown: Duration::from_secs(30),
+            },
+        }
+    }
+}
+
+/// A collector endpoint of an [`Emitter`] with failover, and its health
+#[derive(Debug)]
+struct Endpoint {
+    url: Url,
+    health: CircuitBreaker,
+}
+
+/// The collector endpoints of an [`Emitter`] with failover
+#[derive(Debug)]
+struct Failover {
+    /// All of the endpoints, starting with the primary one
+    endpoints: Vec<Endpoint>,
+    strategy: FailoverStrategy,
+    /// The endpoint that the next batch starts at, for round-robin
+    next: AtomicUsize,
+}
+
+impl Failover {
+    /// The order in which the endpoints should be tried for the next batch
+    fn order(&self) -> impl Iterator<Item = &Endpoint> + Clone {
+        let start = match self.strategy {
+            FailoverStrategy::Primary => 0,
+            FailoverStrategy::RoundRobin => {
+                self.next.fetch_add(1, Ordering::Relaxed) % self.endpoints.len()
+            }
+        };
+
+        self.endpoints[start..]
+            .iter()
+            .chain(&self.endpoints[..start])
+    }
+}
+
+/// Whether an error from an endpoint means that the next endpoint should be
+/// tried: it couldn't be reached, or it responded with a 5xx status. Unlike
+/// [`is_retryable`], a 429 isn't an endpoint failure.
+fn is_endpoint_failure(error: &reqwest::Error) -> bool {
+    is_retryable(error) && error.status() != Some(StatusCode::TOO_MANY_REQUESTS)
+}
+
+/// Whether an error from the collector is worth retrying. This is also what
+/// counts as a failure for the circuit breaker.
+fn is_retryable(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        None => !error.is_builder(),
+    }
+}
+
+/// The final delivery status of an event; see [`EventOutcome`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    /// The collector accepted the event
+    Delivered,
+
+    /// The event couldn't be delivered, and won't be retried
+    Failed {
+        /// The HTTP status the collector responded with, if it responded
+        status: Option<StatusCode>,
+
+        /// A description of the failure
+        reason: String,
+    },
+}
+
+/// The outcome of sending a single event to the collector, reported by
+/// [`Emitter::subscribe`] once the emitter is done with the event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventOutcome {
+    /// The ID of the event
+    pub event_id: Uuid,
+
+    /// The number of attempts made to send the event
+    pub attempts: u32,
+
+    /// Whether the event was ultimately delivered
+    pub status: DeliveryStatus,
+}
+
 /// Emitter is responsible for emitting tracked events to the Snowplow
 /// Collector. It takes care of the low-level HTTP stuff. You should probably
 /// be using [`Tracker`][crate::Tracker] instead.
 pub struct Emitter {
     collector_url: Url,
     client: Client,
+    retry_policy: RetryPolicy,
+    max_batch_size: usize,
+    compression: Compression,
+    /// Created when the first subscriber subscribes
+    outcomes: OnceLock<broadcast::Sender<EventOutcome>>,
+    metrics: EmitterMetrics,
+    request_decorators: Vec<RequestDecorator>,
+    response_hooks: Vec<ResponseHook>,
+    network_user_id: Option<NetworkUserId>,
+    circuit_breaker: Option<CircuitBreaker>,
+    failover: Option<Failover>,
 }
 
 impl Emitter {
     /// Create a new emitter that will send events to the given Url using the
-    /// given client.
+    /// given client. The URL is used as-is; use
+    /// [`Tracker::builder`][crate::Tracker::builder] to have it validated.
     pub const fn new(collector_url: Url, client: Client) -> Emitter {
-        // TODO: log a warning if the Url doesn't look right
         Emitter {
             collector_url,
             client,
+            retry_policy: RetryPolicy::none(),
+            max_batch_size: usize::MAX,
+            compression: Compression::None,
+            outcomes: OnceLock::new(),
+            metrics: EmitterMetrics::new(),
+            request_decorators: Vec::new(),
+            response_hooks: Vec::new(),
+            network_user_id: None,
+            circuit_breaker: None,
+            failover: None,
+        }
+    }
+
+    /// Set the [`RetryPolicy`] for this emitter. By default, requests aren't
+    /// retried.
+    #[must_use]
+    pub const fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Set the maximum number of events sent in a single request. Larger
+    /// batches are split across several requests. By default, every batch is
+    /// sent in a single request. A value of 0 is treated the same as 1.
+    #[must_use]
+    pub const fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    /// Set the [`Compression`] used for request bodies. By default, they
+    /// aren't compressed.
+    #[must_use]
+    pub const fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Add a [circuit breaker][crate::circuit_breaker] with the given policy,
+    /// so that batches fail immediately while the collector is down. By
+    /// default, there's no circuit breaker.
+    #[must_use]
+    pub fn with_circuit_breaker(mut self, policy: CircuitBreakerPolicy) -> Self {
+        self.circuit_breaker = Some(CircuitBreaker::new(policy));
+        self
+    }
+
+    /// Get the state of the emitter's circuit breaker, if it has one
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.circuit_breaker.as_ref().map(CircuitBreaker::state)
+    }
+
+    /**
+    Send events to several collector endpoints: the one the emitter was
+    created with is the primary endpoint, followed by the given secondary
+    endpoints, in order. If an endpoint can't be reached, or responds with a
+    5xx status, the batch is sent to the next one, until one of them accepts
+    it (or they've all been tried).
+
+    Each endpoint's health is tracked separately, according to the policy's
+    [`endpoint_health`][FailoverPolicy::endpoint_health]; unhealthy endpoints
+    are skipped, unless none of them are healthy. This is independent of the
+    emitter's own [circuit breaker][Self::with_circuit_breaker], which sees
+    each batch as failed only if every endpoint it was sent to failed. The
+    [retry policy][Self::with_retry_policy] applies to each batch as a whole,
+    so every retry goes through the endpoints again.
+
+    Changes in an endpoint's health are logged with the endpoint's URL, under
+    the `snowplow_tracker::circuit_breaker` target, and the number of times
+    an endpoint was marked unhealthy is counted in the emitter's
+    [metrics][crate::metrics].
+    */
+    #[must_use]
+    pub fn with_failover(
+        mut self,
+        secondary_endpoints: impl IntoIterator<Item = Url>,
+        policy: FailoverPolicy,
+    ) -> Self {
+        let endpoints = std::iter::once(self.collector_url.clone())
+            .chain(secondary_endpoints)
+            .map(|url| Endpoint {
+                health: CircuitBreaker::for_endpoint(policy.endpoint_health, url.clone()),
+                url,
+            })
+            .collect();
+
+        self.failover = Some(Failover {
+            endpoints,
+            strategy: policy.strategy,
+            next: AtomicUsize::new(0),
+        });
+        self
+    }
+
+    /// Get each of the emitter's collector endpoints, starting with the
+    /// primary one, along with its health. Without
+    /// [failover][Self::with_failover], this is just the collector URL, which
+    /// is always considered healthy.
+    pub fn endpoints(&self) -> Vec<(Url, CircuitState)> {
+        match &self.failover {
+            None => vec![(self.collector_url.clone(), CircuitState::Closed)],
+            Some(failover) => failover
+                .endpoints
+                .iter()
+                .map(|endpoint| (endpoint.url.clone(), endpoint.health.state()))
+                .collect(),
+        }
+    }
+
+    /**
+    Capture the network user ID that the collector sets in its `sp` cookie,
+    and send it back with every later request, so that the collector keeps
+    using the same ID. A [`Tracker`][crate::Tracker] using this emitter also
+    sends it as the `tnuid` of its events, unless its
+    [`Subject`][crate::Subject] has a network user ID of its own.
+
+    This is meant for apps where a single user is tracked, like desktop
+    apps; server-side apps tracking many users shouldn't use it, since every
+    user would get the same ID. The ID is only kept in memory; see
+    [`with_network_user_id_file`][Self::with_network_user_id_file] to keep it
+    across restarts.
+    */
+    #[must_use]
+    pub fn with_network_user_id(mut self) -> Self {
+        self.network_user_id = Some(NetworkUserId::default());
+        self
+    }
+
+    /// Capture the network user ID, like
+    /// [`with_network_user_id`][Self::with_network_user_id], and persist it
+    /// in a file, so that it's kept across restarts. If the file already
+    /// exists, the ID in it is used from the start.
+    #[must_use]
+    pub fn with_network_user_id_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.network_user_id = Some(NetworkUserId::load(path.into()));
+        self
+    }
+
+    /// Get the network user ID captured from the collector, if any; see
+    /// [`with_network_user_id`][Self::with_network_user_id].
+    pub fn network_user_id(&self) -> Option<String> {
+        self.network_user_id.as_ref()?.get()
+    }
+
+    /**
+    Add a hook that can modify the headers of every request before it's
+    sent; for instance, to add an `Authorization` header with a token that
+    needs to be refreshed periodically. The decorator is given the headers
+    the emitter would send (like `Content-Type`), and returns the headers to
+    send instead. It's called again for every attempt, so retries get fresh
+    headers too.
+
+    Decorators are run in the order they're added. A decorator can't fail
+    the request; if it can't get what it needs, it should return the headers
+    it was given, so that the collector (or a proxy in front of it) rejects
+    the request.
+
+    ```no_run
+    use reqwest::header::{HeaderValue, AUTHORIZATION};
+    use snowplow_tracker::emitter::Emitter;
+
+    # async fn fetch_token() -> String { String::new() }
+    # fn example(url: reqwest::Url) -> Emitter {
+    Emitter::new(url, reqwest::Client::new()).with_request_decorator(|mut headers| async move {
+        let token = fetch_token().await;
+        if let Ok(value) = HeaderValue::try_from(format!("Bearer {token}")) {
+            headers.insert(AUTHORIZATION, value);
+        }
+        headers.insert("x-tenant", HeaderValue::from_static("acme"));
+        headers
+    })
+    # }
+    ```
+    */
+    #[must_use]
+    pub fn with_request_decorator<F, Fut>(mut self, decorator: F) -> Self
+    where
+        F: Fn(HeaderMap) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HeaderMap> + Send + 'static,
+    {
+        self.request_decorators
+            .push(Box::new(move |headers| Box::pin(decorator(headers))));
+        self
+    }
+
+    /// Add a hook that's called with the status and headers of every
+    /// response from the collector, including error responses, before the
+    /// emitter decides whether the request succeeded. Hooks are run in the
+    /// order they're added. Requests that fail without a response (like
+    /// connection errors) aren't seen by response hooks.
+    #[must_use]
+    pub fn with_response_hook(
+        mut self,
+        hook: impl Fn(StatusCode, &HeaderMap) + Send + Sync + 'static,
+    ) -> Self {
+        self.response_hooks.push(Box::new(hook));
+        self
+    }
+
+    /**
+    Subscribe to the outcome of every event sent by this emitter. Once an
+    event has been delivered, or has failed and won't be retried, an
+    [`EventOutcome`] is sent to every subscriber. Only events with an
+    [`event_id`][SnowplowEvent::event_id] are reported; the
+    [`Tracker`][crate::Tracker] always assigns one.
+
+    Outcomes are only reported while there is at least one subscriber. If a
+    subscriber falls more than 1024 outcomes behind, it will miss some; see
+    [`broadcast::Receiver`] for details.
+    */
+    pub fn subscribe(&self) -> broadcast::Receiver<EventOutcome> {
+        self.outcomes
+            .get_or_init(|| broadcast::channel(OUTCOME_CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Get a snapshot of this emitter's [metrics][crate::metrics]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Record that some events were dropped before reaching this emitter
+    pub(crate) fn record_dropped(&self, count: usize) {
+        if count > 0 {
+            self.metrics.dropped(count);
         }
     }
 
@@ -69,44 +648,364 @@ impl Emitter {
     pub async fn track_events<Payload: HasSchema + Serialize>(
         &self,
         events: impl IntoIterator<Item = SnowplowEvent<'_, Payload>>,
-    ) -> Result<(), reqwest::Error> {
-        let events = EventContainer::new(events);
+    ) -> Result<(), SendError> {
+        self.send_events(events, SendOptions::default()).await
+    }
 
-        let response = self
-            .client
-            .post(self.collector_url.clone())
-            .json(&events)
-            .send()
-            .await?;
+    /// Track a batch of events, sending them to the snowplow collector with
+    /// some specific [`SendOptions`]. If the batch is larger than the
+    /// emitter's maximum batch size, it's split across several requests, and
+    /// the first error (if any) is returned once they've all been attempted.
+    #[tracing::instrument(
+        name = "send_events",
+        level = "debug",
+        skip_all,
+        fields(events = tracing::field::Empty)
+    )]
+    pub async fn send_events<Payload: HasSchema + Serialize>(
+        &self,
+        events: impl IntoIterator<Item = SnowplowEvent<'_, Payload>>,
+        options: SendOptions,
+    ) -> Result<(), SendError> {
+        let mut events: Vec<_> = events.into_iter().collect();
+        tracing::Span::current().record("events", events.len());
+        let mut queued = self.metrics.enqueued(events.len());
+
+        let max_batch_size = self.max_batch_size.max(1);
+        let mut result = Ok(());
+
+        loop {
+            let rest = events.split_off(events.len().min(max_batch_size));
+            let batch_result = self
+                .send_batch(&EventContainer::new(events), options, &mut queued)
+                .await;
+            result = result.and(batch_result);
+
+            events = rest;
+            if events.is_empty() {
+                break result;
+            }
+        }
+    }
+
+    /// Send a single batch of events in a single request, retrying it
+    /// according to the retry policy, and marking it as finished in `queued`
+    #[tracing::instrument(
+        name = "send_batch",
+        level = "debug",
+        skip_all,
+        fields(batch_size = events.0.len())
+    )]
+    async fn send_batch<Payload: HasSchema + Serialize>(
+        &self,
+        events: &EventContainer<'_, Payload>,
+        options: SendOptions,
+        queued: &mut Queued<'_>,
+    ) -> Result<(), SendError> {
+        let permit = match &self.circuit_breaker {
+            None => None,
+            Some(breaker) => match breaker.allow() {
+                Some(permit) => Some(permit),
+                None => {
+                    tracing::debug!(
+                        dropped = events.0.len(),
+                        "circuit breaker is open; failing batch without a request"
+                    );
+                    let result = Err(SendError::CircuitOpen);
+                    queued.finished(events.0.len(), false);
+                    self.report_outcomes(events, 0, &result);
+                    return result;
+                }
+            },
+        };
+
+        let body = match self.encode_body(events) {
+            Ok(body) => Some(body),
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to serialize batch");
+                None
+            }
+        };
+
+        let mut attempts = 0;
+        let result = loop {
+            attempts += 1;
+            let start = Instant::now();
+            let result = self.send_attempt(events, body.as_deref(), options).await;
+            let latency_ms = start.elapsed().as_millis() as u64;
+
+            match result {
+                Ok(status) => {
+                    tracing::debug!(attempt = attempts, %status, latency_ms, "batch delivered");
+                    break Ok(());
+                }
+                Err(err) if attempts < self.retry_policy.max_attempts && is_retryable(&err) => {
+                    let backoff = self.retry_policy.backoff(attempts);
+                    tracing::warn!(
+                        attempt = attempts,
+                        status = err.status().map(|status| status.as_u16()),
+                        latency_ms,
+                        backoff_ms = backoff.as_millis() as u64,
+                        error = %err,
+                        "request failed; retrying"
+                    );
+                    self.metrics.retried();
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(err) => {
+                    tracing::error!(
+                        attempts,
+                        status = err.status().map(|status| status.as_u16()),
+                        latency_ms,
+                        dropped = events.0.len(),
+                        error = %err,
+                        "failed to deliver batch"
+                    );
+                    break Err(err);
+                }
+            }
+        };
+
+        if let Some(permit) = permit {
+            let healthy = result
+                .as_ref()
+                .map_or_else(|err| !is_retryable(err), |()| true);
+            if permit.record(healthy).is_some_and(Transition::opened) {
+                self.metrics.circuit_opened();
+            }
+        }
+
+        let result = result.map_err(SendError::from);
+        queued.finished(events.0.len(), result.is_ok());
+        self.report_outcomes(events, attempts, &result);
+        result
+    }
+
+    /// Serialize (and maybe compress) a batch of events into a request body
+    fn encode_body<Payload: HasSchema + Serialize>(
+        &self,
+        events: &EventContainer<'_, Payload>,
+    ) -> Result<Vec<u8>, serde_json::Error> {
+        let body = match self.compression {
+            Compression::None => serde_json::to_vec(events)?,
+            Compression::Gzip => gzip_json(events)?,
+        };
+
+        tracing::trace!(bytes = body.len(), compression = ?self.compression, "encoded batch");
+        Ok(body)
+    }
+
+    /// Make a single attempt to send a batch of events, given its encoded
+    /// body. With failover, this goes through the endpoints until one of
+    /// them accepts the batch; otherwise it's a single request.
+    async fn send_attempt<Payload: HasSchema + Serialize>(
+        &self,
+        events: &EventContainer<'_, Payload>,
+        body: Option<&[u8]>,
+        options: SendOptions,
+    ) -> Result<StatusCode, reqwest::Error> {
+        let Some(failover) = &self.failover else {
+            return self
+                .send_request(&self.collector_url, events, body, options)
+                .await;
+        };
+
+        let order = failover.order();
+        let mut candidates: Box<dyn Iterator<Item = (&Endpoint, Permit<'_>)> + Send> = Box::new(
+            order
+                .clone()
+                .filter_map(|endpoint| Some((endpoint, endpoint.health.allow()?))),
+        );
+
+        // If every endpoint is unhealthy, try them all anyway, rather than
+        // dropping the batch
+        let first = match candidates.next() {
+            Some(candidate) => candidate,
+            None => {
+                tracing::debug!("no healthy collector endpoints; trying all of them");
+                candidates = Box::new(order.map(|endpoint| (endpoint, endpoint.health.bypass())));
+                candidates
+                    .next()
+                    .expect("an emitter always has an endpoint")
+            }
+        };
+
+        let (mut endpoint, mut permit) = first;
+        loop {
+            let result = self
+                .send_request(&endpoint.url, events, body, options)
+                .await;
+            let failed = result.as_ref().err().filter(|err| is_endpoint_failure(err));
+
+            if permit
+                .record(failed.is_none())
+                .is_some_and(Transition::opened)
+            {
+                self.metrics.endpoint_unhealthy(&endpoint.url);
+            }
+
+            let Some(err) = failed else {
+                return result;
+            };
+
+            match candidates.next() {
+                Some((next, next_permit)) => {
+                    tracing::warn!(
+                        endpoint = %endpoint.url,
+                        next = %next.url,
+                        error = %err,
+                        "collector endpoint failed; failing over"
+                    );
+                    endpoint = next;
+                    permit = next_permit;
+                }
+                None => return result,
+            }
+        }
+    }
+
+    /// Send a batch of events to a collector endpoint in a single request,
+    /// given its encoded body. If the body couldn't be encoded, the events are
+    /// given to reqwest to serialize, so that it reports the serialization
+    /// error.
+    async fn send_request<Payload: HasSchema + Serialize>(
+        &self,
+        url: &Url,
+        events: &EventContainer<'_, Payload>,
+        body: Option<&[u8]>,
+        options: SendOptions,
+    ) -> Result<StatusCode, reqwest::Error> {
+        let start = Instant::now();
+        let result = self.make_request(url, events, body, options).await;
+        self.metrics
+            .request(start.elapsed(), body.map_or(0, <[u8]>::len));
+        result
+    }
+
+    async fn make_request<Payload: HasSchema + Serialize>(
+        &self,
+        url: &Url,
+        events: &EventContainer<'_, Payload>,
+        body: Option<&[u8]>,
+        options: SendOptions,
+    ) -> Result<StatusCode, reqwest::Error> {
+        let mut headers = HeaderMap::new();
+        let request = self.client.post(url.clone());
+        let request = match body {
+            Some(body) => {
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                if self.compression == Compression::Gzip {
+                    headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+                }
+                request.body(body.to_vec())
+            }
+            None => request.json(events),
+        };
+        if options.anonymous {
+            headers.insert(ANONYMOUS_HEADER, HeaderValue::from_static("*"));
+        } else if let Some(id) = self.network_user_id() {
+            match HeaderValue::try_from(format!("{NETWORK_USER_ID_COOKIE}={id}")) {
+                Ok(cookie) => {
+                    headers.insert(COOKIE, cookie);
+                }
+                Err(err) => tracing::warn!(error = %err, "invalid network user ID"),
+            }
+        }
+
+        for decorator in &self.request_decorators {
+            headers = decorator(headers).await;
+        }
+
+        let response = request.headers(headers).send().await?;
+
+        if let (Some(network_user_id), false) = (&self.network_user_id, options.anonymous) {
+            if let Some(id) = network_user_id_cookie(response.headers()) {
+                network_user_id.update(id);
+            }
+        }
+
+        for hook in &self.response_hooks {
+            hook(response.status(), response.headers());
+        }
+
+        let response = response.error_for_status()?;
+        let status = response.status();
 
         // Snowplow responses don't contain anything useful, so just drain the
         // response content.
         response
             .bytes_stream()
             .try_for_each(|_chunk| ready(Ok(())))
-            .await
+            .await?;
+
+        Ok(status)
+    }
+
+    /// Report the outcome of a batch to any subscribers
+    fn report_outcomes<Payload: HasSchema>(
+        &self,
+        events: &EventContainer<'_, Payload>,
+        attempts: u32,
+        result: &Result<(), SendError>,
+    ) {
+        let Some(sender) = self.outcomes.get() else {
+            return;
+        };
+
+        let status = match result {
+            Ok(()) => DeliveryStatus::Delivered,
+            Err(err) => DeliveryStatus::Failed {
+                status: err.status(),
+                reason: err.to_string(),
+            },
+        };
+
+        events
+            .0
+            .iter()
+            .filter_map(|event| event.event_id)
+            .for_each(|event_id| {
+                // An error here just means there are no subscribers left
+                let _ = sender.send(EventOutcome {
+                    event_id,
+                    attempts,
+                    status: status.clone(),
+                });
+            });
     }
 
     /// Track a single event
     pub async fn track_event<Payload: HasSchema + Serialize>(
         &self,
         event: SnowplowEvent<'_, Payload>,
-    ) -> Result<(), reqwest::Error> {
+    ) -> Result<(), SendError> {
         self.track_events([event]).await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::emitter::EventContainer;
+    use crate::circuit_breaker::{CircuitBreakerPolicy, CircuitState};
+    use crate::emitter::{
+        network_user_id_cookie, Compression, DeliveryStatus, Emitter, EventContainer, EventOutcome,
+        FailoverPolicy, FailoverStrategy, RetryPolicy,
+    };
+    use crate::micro::Micro;
+    use crate::testing::{CaptureServer, CapturedLogs};
     use crate::{
-        payload::{EventType, PayloadWrapper, SnowplowEvent, SnowplowTimestamp},
-        util::JsonString,
-        HasSchema, Platform, Schema, SchemaVersion, TrackedEvent,
+        payload::{EventData, PayloadWrapper, SnowplowEvent, SnowplowTimestamp},
+        HasSchema, Platform, Schema, SchemaVersion, TrackError, TrackedEvent, Tracker,
+        TrackerConfig,
     };
+    use futures::FutureExt as _;
+    use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, SET_COOKIE};
+    use reqwest::StatusCode;
     use serde::Serialize;
     use serde_test::{assert_ser_tokens, Configure, Token};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
     use std::time::{Duration, SystemTime};
+    use tracing::Level;
     use uuid::Uuid;
 
     #[derive(Debug, Serialize)]
@@ -206,15 +1105,26 @@ mod tests {
             id: Some(test_uuid),
             timestamp: Some(SnowplowTimestamp::from(event_created)),
             payload: test_payload,
+            contexts: Vec::new(),
         };
 
         let events = [test_event].into_iter().map(|event| SnowplowEvent {
-            event_type: EventType::SelfDescribingEvent,
-            payload: JsonString(PayloadWrapper::new(event.payload)),
+            data: EventData::self_describing(event.payload),
+            contexts: None,
             platform: Platform::Desktop,
-            app_id: "test id",
-            tracker_id: "test tracker ID",
-            namespace: "test namespace",
+            app_id: "test id".into(),
+            tracker_id: "test tracker ID".into(),
+            namespace: "test namespace".into(),
+            user_id: None,
+            ip_address: None,
+            useragent: None,
+            timezone: None,
+            language: None,
+            screen_resolution: None,
+            domain_user_id: None,
+            network_user_id: None,
+            session_id: None,
+            session_index: None,
             event_id: event.id,
             created_timestamp: event
                 .timestamp
@@ -234,9 +1144,9 @@ mod tests {
                 Token::Str("iglu:com.snowplowanalytics.snowplow/payload_data/jsonschema/1-0-4"),
                 Token::Str("data"),
                 Token::Seq { len: Some(1), },
-                Token::Struct { name: "SnowplowEvent", len: 9, },
+                Token::Map { len: None, },
                 Token::Str("e"),
-                Token::UnitVariant { name: "EventType", variant: "ue", },
+                Token::Str("ue"),
                 Token::Str("ue_pr"),
                 Token::Str("{\"schema\":\"iglu:com.snowplowanalytics.snowplow/unstruct_event/jsonschema/1-0-0\",\"data\":{\"schema\":\"iglu:com.snowplowanalytics.snowplow/screen_view/jsonschema/1-0-0\",\"data\":{\"name\":\"test\",\"id\":\"test id\"}}}"),
                 Token::Str("p"),
@@ -254,10 +1164,583 @@ mod tests {
                 Token::Str(event_created_string),
                 Token::Str("stm"),
                 Token::Str(event_sent_string),
-                Token::StructEnd,
+                Token::MapEnd,
                 Token::SeqEnd,
                 Token::StructEnd,
             ]
         );
     }
+
+    /// Start a mock collector that accepts everything
+    fn micro() -> Micro {
+        Micro::start(([127, 0, 0, 1], 0).into()).expect("failed to start micro")
+    }
+
+    /// A tracker sending to `url` through an emitter configured by `emitter`
+    fn outcome_tracker(url: reqwest::Url, emitter: impl FnOnce(Emitter) -> Emitter) -> Tracker {
+        Tracker::new(
+            emitter(Emitter::new(url, reqwest::Client::new())),
+            TrackerConfig {
+                namespace: "ns".into(),
+                platform: Platform::Desktop,
+                app_id: "app".to_owned(),
+            },
+        )
+    }
+
+    fn test_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delivered_outcomes() {
+        let micro = micro();
+        let tracker = outcome_tracker(micro.collector_url(), |emitter| {
+            emitter.with_retry_policy(test_retry_policy())
+        });
+        let mut outcomes = tracker.emitter().subscribe();
+
+        let ids = [Uuid::new_v4(), Uuid::new_v4()];
+        tracker
+            .track_batch(ids.map(|id| TrackedEvent {
+                id: Some(id),
+                ..TrackedEvent::new(WebPage {
+                    name: "test".to_owned(),
+                    id: "test id".to_owned(),
+                })
+            }))
+            .await
+            .expect("failed to track events");
+
+        for id in ids {
+            assert_eq!(
+                outcomes.recv().await.unwrap(),
+                EventOutcome {
+                    event_id: id,
+                    attempts: 1,
+                    status: DeliveryStatus::Delivered,
+                }
+            );
+        }
+
+        let received: Vec<_> = micro
+            .good()
+            .into_iter()
+            .map(|event| event.event.event_id.unwrap())
+            .collect();
+        assert_eq!(received, ids.map(|id| id.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_failed_outcomes_are_retried() {
+        let server = CaptureServer::start(503).await;
+        let tracker = outcome_tracker(server.url(), |emitter| {
+            emitter.with_retry_policy(test_retry_policy())
+        });
+        let mut outcomes = tracker.emitter().subscribe();
+
+        let error = tracker
+            .track(TrackedEvent::new(WebPage {
+                name: "test".to_owned(),
+                id: "test id".to_owned(),
+            }))
+            .await
+            .expect_err("the collector is failing");
+        assert!(matches!(error, TrackError::HttpStatus(_)));
+
+        let outcome = outcomes.recv().await.unwrap();
+        assert_eq!(outcome.attempts, 3);
+        assert!(matches!(
+            outcome.status,
+            DeliveryStatus::Failed {
+                status: Some(StatusCode::SERVICE_UNAVAILABLE),
+                ..
+            }
+        ));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let server = CaptureServer::start(400).await;
+        let tracker = outcome_tracker(server.url(), |emitter| {
+            emitter.with_retry_policy(test_retry_policy())
+        });
+        let mut outcomes = tracker.emitter().subscribe();
+
+        tracker
+            .track(TrackedEvent::new(WebPage {
+                name: "test".to_owned(),
+                id: "test id".to_owned(),
+            }))
+            .await
+            .expect_err("the collector is rejecting requests");
+
+        assert_eq!(outcomes.recv().await.unwrap().attempts, 1);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_split_and_compressed() {
+        let micro = micro();
+        let tracker = |compression| {
+            outcome_tracker(micro.collector_url(), |emitter| {
+                emitter.with_max_batch_size(2).with_compression(compression)
+            })
+        };
+        let pages = || {
+            (0..5).map(|i| {
+                TrackedEvent::new(WebPage {
+                    name: format!("page {i}"),
+                    id: "test id".to_owned(),
+                })
+            })
+        };
+
+        let plain = tracker(Compression::None);
+        plain.track_batch(pages()).await.unwrap();
+        let gzipped = tracker(Compression::Gzip);
+        gzipped.track_batch(pages()).await.unwrap();
+
+        // Micro only accepts gzipped bodies with a `Content-Encoding: gzip`
+        // header
+        assert_eq!(micro.good().len(), 10);
+        assert!(micro.bad().is_empty());
+
+        let plain = plain.emitter().metrics();
+        let gzipped = gzipped.emitter().metrics();
+        assert_eq!(gzipped.requests, 3);
+        assert!(
+            gzipped.payload_bytes < plain.payload_bytes,
+            "{} gzipped bytes, {} plain bytes",
+            gzipped.payload_bytes,
+            plain.payload_bytes
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_and_response_hooks() {
+        let server = CaptureServer::start(503).await;
+        let token = Arc::new(AtomicUsize::new(0));
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+
+        let tracker = outcome_tracker(server.url(), |emitter| {
+            emitter
+                .with_retry_policy(test_retry_policy())
+                .with_request_decorator({
+                    let token = token.clone();
+                    move |mut headers| {
+                        // A new token for every attempt
+                        let token = token.fetch_add(1, Ordering::SeqCst);
+                        async move {
+                            tokio::task::yield_now().await;
+                            let value = format!("Bearer token-{token}").try_into().unwrap();
+                            headers.insert(AUTHORIZATION, value);
+                            headers
+                        }
+                    }
+                })
+                .with_request_decorator(|mut headers| async move {
+                    headers.insert("x-tenant", HeaderValue::from_static("acme"));
+                    headers
+                })
+                .with_response_hook({
+                    let statuses = statuses.clone();
+                    move |status, headers| {
+                        assert!(headers.contains_key(CONTENT_LENGTH));
+                        statuses.lock().unwrap().push(status);
+                    }
+                })
+        });
+
+        tracker
+            .track(TrackedEvent::new(WebPage {
+                name: "test".to_owned(),
+                id: "test id".to_owned(),
+            }))
+            .await
+            .expect_err("the collector is failing");
+
+        let requests = server.requests();
+        let authorizations: Vec<_> = requests
+            .iter()
+            .map(|request| request.header("authorization").unwrap())
+            .collect();
+        assert_eq!(
+            authorizations,
+            ["Bearer token-0", "Bearer token-1", "Bearer token-2"]
+        );
+        assert_eq!(requests[0].header("x-tenant"), Some("acme"));
+        assert_eq!(requests[0].header("content-type"), Some("application/json"));
+
+        assert_eq!(
+            *statuses.lock().unwrap(),
+            [StatusCode::SERVICE_UNAVAILABLE; 3]
+        );
+    }
+
+    #[test]
+    fn test_network_user_id_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, HeaderValue::from_static("other=1; Path=/"));
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("sp=c1a3e3b0-7f3a; Expires=Thu, 01 Jan 2099 00:00:00 GMT"),
+        );
+        assert_eq!(network_user_id_cookie(&headers), Some("c1a3e3b0-7f3a"));
+
+        headers.remove(SET_COOKIE);
+        headers.append(SET_COOKIE, HeaderValue::from_static("spx=1"));
+        assert_eq!(network_user_id_cookie(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_network_user_id_is_captured_and_persisted() {
+        let server =
+            CaptureServer::start_with_headers(200, &[("set-cookie", "sp=nuid-1; Path=/")]).await;
+        let path = std::env::temp_dir().join(format!("snowplow-nuid-{}", Uuid::new_v4()));
+        let tracker = outcome_tracker(server.url(), |emitter| {
+            emitter.with_network_user_id_file(&path)
+        });
+
+        for _ in 0..2 {
+            tracker
+                .track(TrackedEvent::new(WebPage {
+                    name: "test".to_owned(),
+                    id: "test id".to_owned(),
+                }))
+                .await
+                .expect("failed to track event");
+        }
+
+        let requests = server.requests();
+        let events: Vec<serde_json::Value> = requests
+            .iter()
+            .map(|request| serde_json::from_str::<serde_json::Value>(&request.body).unwrap())
+            .collect();
+
+        assert_eq!(requests[0].header("cookie"), None);
+        assert!(events[0]["data"][0].get("tnuid").is_none());
+        assert_eq!(requests[1].header("cookie"), Some("sp=nuid-1"));
+        assert_eq!(events[1]["data"][0]["tnuid"], "nuid-1");
+
+        // The ID survives a restart
+        let emitter =
+            Emitter::new(server.url(), reqwest::Client::new()).with_network_user_id_file(&path);
+        assert_eq!(emitter.network_user_id().as_deref(), Some("nuid-1"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let logs = CapturedLogs::start();
+        let server = CaptureServer::start(503).await;
+        let tracker = outcome_tracker(server.url(), |emitter| {
+            emitter.with_circuit_breaker(CircuitBreakerPolicy {
+                failure_threshold: 2,
+                cooldown: Duration::from_millis(50),
+            })
+        });
+        let mut outcomes = tracker.emitter().subscribe();
+        let page = || {
+            TrackedEvent::new(WebPage {
+                name: "test".to_owned(),
+                id: "test id".to_owned(),
+            })
+        };
+
+        for _ in 0..2 {
+            let error = tracker.track(page()).await.unwrap_err();
+            assert!(matches!(error, TrackError::HttpStatus(_)));
+        }
+        assert_eq!(tracker.emitter().circuit_state(), Some(CircuitState::Open));
+
+        // While the breaker is open, events fail without any requests
+        let error = tracker.track(page()).await.unwrap_err();
+        assert!(matches!(error, TrackError::CollectorUnavailable));
+        assert_eq!(server.requests().len(), 2);
+
+        let outcome = outcomes.recv().await.unwrap();
+        assert_eq!(outcome.attempts, 1);
+        let outcome = outcomes.recv().await.unwrap();
+        assert_eq!(outcome.attempts, 1);
+        let outcome = outcomes.recv().await.unwrap();
+        assert_eq!(outcome.attempts, 0);
+
+        // After the cooldown, one batch probes the collector, which is still
+        // failing
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(
+            tracker.emitter().circuit_state(),
+            Some(CircuitState::HalfOpen)
+        );
+        let error = tracker.track(page()).await.unwrap_err();
+        assert!(matches!(error, TrackError::HttpStatus(_)));
+        assert_eq!(server.requests().len(), 3);
+        assert_eq!(tracker.emitter().circuit_state(), Some(CircuitState::Open));
+
+        let metrics = tracker.emitter().metrics();
+        assert_eq!(metrics.circuit_opened, 2);
+        assert_eq!(metrics.events_failed, 4);
+
+        let transitions: Vec<_> = logs
+            .messages()
+            .into_iter()
+            .filter(|(level, message)| {
+                *level <= Level::INFO && message.starts_with("circuit breaker")
+            })
+            .collect();
+        assert_eq!(
+            transitions,
+            [
+                (
+                    Level::WARN,
+                    "circuit breaker opened; the collector is failing".to_owned()
+                ),
+                (
+                    Level::INFO,
+                    "circuit breaker half-open; probing the collector".to_owned()
+                ),
+                (
+                    Level::WARN,
+                    "circuit breaker probe failed; opened again".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_circuit_breaker_probe() {
+        // A collector that accepts connections, but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/com.snowplowanalytics.snowplow/tp2",
+            listener.local_addr().unwrap()
+        );
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let tracker = outcome_tracker(url.parse().unwrap(), |emitter| {
+            Emitter { client, ..emitter }.with_circuit_breaker(CircuitBreakerPolicy {
+                failure_threshold: 1,
+                cooldown: Duration::from_millis(20),
+            })
+        });
+        let page = || {
+            TrackedEvent::new(WebPage {
+                name: "test".to_owned(),
+                id: "test id".to_owned(),
+            })
+        };
+
+        let error = tracker.track(page()).await.unwrap_err();
+        assert!(matches!(error, TrackError::HttpConnection(_)));
+        assert_eq!(tracker.emitter().circuit_state(), Some(CircuitState::Open));
+
+        // Start the probe, and cancel it while it's waiting for a response
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let mut probe = Box::pin(tracker.track(page()));
+        assert!((&mut probe).now_or_never().is_none());
+        let error = tracker.track(page()).await.unwrap_err();
+        assert!(matches!(error, TrackError::CollectorUnavailable));
+        drop(probe);
+
+        // The next batch becomes the probe instead
+        assert_eq!(
+            tracker.emitter().circuit_state(),
+            Some(CircuitState::HalfOpen)
+        );
+        let error = tracker.track(page()).await.unwrap_err();
+        assert!(matches!(error, TrackError::HttpConnection(_)));
+        assert_eq!(tracker.emitter().circuit_state(), Some(CircuitState::Open));
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_secondary_endpoint() {
+        let primary = CaptureServer::start(503).await;
+        let secondary = micro();
+        let tracker = Tracker::builder()
+            .collector(primary.url().as_str())
+            .secondary_collector(secondary.collector_url().as_str())
+            .app_id("app")
+            .build()
+            .unwrap();
+        let page = || {
+            TrackedEvent::new(WebPage {
+                name: "test".to_owned(),
+                id: "test id".to_owned(),
+            })
+        };
+
+        tracker.track(page()).await.unwrap();
+        assert_eq!(primary.requests().len(), 1);
+        assert_eq!(secondary.good().len(), 1);
+
+        // The primary endpoint is skipped until its cooldown is over
+        tracker.track(page()).await.unwrap();
+        assert_eq!(primary.requests().len(), 1);
+        assert_eq!(secondary.good().len(), 2);
+
+        let endpoints = tracker.emitter().endpoints();
+        assert_eq!(endpoints[0].1, CircuitState::Open);
+        assert_eq!(endpoints[1].1, CircuitState::Closed);
+        let metrics = tracker.emitter().metrics();
+        assert_eq!(metrics.events_sent, 2);
+        assert_eq!(metrics.requests, 3);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_batches_during_endpoint_probe() {
+        let logs = CapturedLogs::start();
+        let primary = CaptureServer::start(503).await;
+        let secondary = micro();
+        let tracker = outcome_tracker(primary.url(), |emitter| {
+            emitter.with_failover(
+                [secondary.collector_url()],
+                FailoverPolicy {
+                    endpoint_health: CircuitBreakerPolicy {
+                        failure_threshold: 1,
+                        cooldown: Duration::from_millis(20),
+                    },
+                    ..FailoverPolicy::default()
+                },
+            )
+        });
+        let page = || {
+            TrackedEvent::new(WebPage {
+                name: "test".to_owned(),
+                id: "test id".to_owned(),
+            })
+        };
+
+        tracker.track(page()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(tracker.emitter().endpoints()[0].1, CircuitState::HalfOpen);
+
+        // One batch probes the primary endpoint, while the other one skips it
+        let (first, second) = tokio::join!(tracker.track(page()), tracker.track(page()));
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(primary.requests().len(), 2);
+        assert_eq!(secondary.good().len(), 3);
+        assert_eq!(tracker.emitter().endpoints()[0].1, CircuitState::Open);
+
+        let metrics = tracker.emitter().metrics();
+        assert_eq!(metrics.endpoints_unhealthy, 2);
+        assert_eq!(metrics.circuit_opened, 0);
+
+        let messages: Vec<_> = logs
+            .messages()
+            .into_iter()
+            .filter(|(level, _)| *level <= Level::INFO)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    Level::WARN,
+                    "collector endpoint unhealthy; skipping it".to_owned()
+                ),
+                (
+                    Level::WARN,
+                    "collector endpoint failed; failing over".to_owned()
+                ),
+                (
+                    Level::INFO,
+                    "collector endpoint half-open; probing it".to_owned()
+                ),
+                (
+                    Level::WARN,
+                    "collector endpoint probe failed; skipping it again".to_owned()
+                ),
+                (
+                    Level::WARN,
+                    "collector endpoint failed; failing over".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failover_from_unreachable_endpoint() {
+        let secondary = micro();
+        let unreachable = "http://127.0.0.1:1/com.snowplowanalytics.snowplow/tp2";
+        let tracker = outcome_tracker(unreachable.parse().unwrap(), |emitter| {
+            emitter.with_failover([secondary.collector_url()], FailoverPolicy::default())
+        });
+
+        tracker
+            .track(TrackedEvent::new(WebPage {
+                name: "test".to_owned(),
+                id: "test id".to_owned(),
+            }))
+            .await
+            .unwrap();
+        assert_eq!(secondary.good().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_endpoints() {
+        let first = micro();
+        let second = micro();
+        let tracker = Tracker::builder()
+            .collector(first.collector_url().as_str())
+            .secondary_collector(second.collector_url().as_str())
+            .failover_policy(FailoverPolicy {
+                strategy: FailoverStrategy::RoundRobin,
+                ..FailoverPolicy::default()
+            })
+            .app_id("app")
+            .build()
+            .unwrap();
+
+        for _ in 0..4 {
+            tracker
+                .track(TrackedEvent::new(WebPage {
+                    name: "test".to_owned(),
+                    id: "test id".to_owned(),
+                }))
+                .await
+                .unwrap();
+        }
+        assert_eq!(first.good().len(), 2);
+        assert_eq!(second.good().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_diagnostics() {
+        let logs = CapturedLogs::start();
+        let server = CaptureServer::start(503).await;
+        let tracker = outcome_tracker(server.url(), |emitter| {
+            emitter.with_retry_policy(test_retry_policy())
+        });
+
+        tracker
+            .track(TrackedEvent::new(WebPage {
+                name: "test".to_owned(),
+                id: "test id".to_owned(),
+            }))
+            .await
+            .expect_err("the collector is failing");
+
+        let problems: Vec<_> = logs
+            .messages()
+            .into_iter()
+            .filter(|(level, _)| *level <= Level::WARN)
+            .collect();
+
+        assert_eq!(
+            problems,
+            [
+                (Level::WARN, "request failed; retrying".to_owned()),
+                (Level::WARN, "request failed; retrying".to_owned()),
+                (Level::ERROR, "failed to deliver batch".to_owned()),
+            ]
+        );
+    }
 }
@@ -0,0 +1,673 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Snowplow first-party event payloads. Each of these types serializes to the
+data of its Iglu schema in `com.snowplowanalytics.snowplow`, so they can be
+tracked like any other [`HasSchema`] payload.
+
+Required fields are given to each type's `new` constructor, and optional
+fields can be set with its `with_*` methods, or directly.
+
+```no_run
+use snowplow_tracker::events::{ScreenView, Timing};
+use snowplow_tracker::{TrackedEvent, Tracker};
+use uuid::Uuid;
+
+# async fn example(tracker: Tracker) -> Result<(), snowplow_tracker::TrackError> {
+tracker
+    .track(TrackedEvent::new(
+        ScreenView::new("settings", Uuid::new_v4()).with_type("modal"),
+    ))
+    .await?;
+
+tracker
+    .track(TrackedEvent::new(
+        Timing::new("startup", "load_config", 42).with_label("cold"),
+    ))
+    .await?;
+# Ok(())
+# }
+```
+*/
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::payload::{HasSchema, Schema, SchemaVersion};
+
+/// An event recording that the user viewed a screen in an app.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenView {
+    /// The name of the screen
+    pub name: String,
+
+    /// A unique ID for this view of the screen
+    pub id: Uuid,
+
+    /// The type of the screen, like `"feed"` or `"modal"`
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub screen_type: Option<String>,
+
+    /// The name of the previous screen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_name: Option<String>,
+
+    /// The ID of the previous view
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_id: Option<Uuid>,
+
+    /// The type of the previous screen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_type: Option<String>,
+
+    /// How the user got from the previous screen to this one, like
+    /// `"push"` or `"swipe"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transition_type: Option<String>,
+}
+
+impl ScreenView {
+    /// Create a new screen view
+    pub fn new(name: impl Into<String>, id: Uuid) -> Self {
+        Self {
+            name: name.into(),
+            id,
+            screen_type: None,
+            previous_name: None,
+            previous_id: None,
+            previous_type: None,
+            transition_type: None,
+        }
+    }
+
+    /// Set the type of the screen
+    #[must_use]
+    pub fn with_type(mut self, screen_type: impl Into<String>) -> Self {
+        self.screen_type = Some(screen_type.into());
+        self
+    }
+
+    /// Set the previous screen, from its view's [`name`][Self::name],
+    /// [`id`][Self::id] and [`screen_type`][Self::screen_type]
+    #[must_use]
+    pub fn with_previous(mut self, previous: &ScreenView) -> Self {
+        self.previous_name = Some(previous.name.clone());
+        self.previous_id = Some(previous.id);
+        self.previous_type = previous.screen_type.clone();
+        self
+    }
+
+    /// Set how the user got to this screen
+    #[must_use]
+    pub fn with_transition_type(mut self, transition_type: impl Into<String>) -> Self {
+        self.transition_type = Some(transition_type.into());
+        self
+    }
+}
+
+impl HasSchema for ScreenView {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("screen_view", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// An event recording how long something took, in milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Timing {
+    /// The category of the timed thing, like `"startup"`
+    pub category: String,
+
+    /// What was timed, like `"load_config"`
+    pub variable: String,
+
+    /// How long it took, in milliseconds
+    pub timing: u64,
+
+    /// An optional label, to distinguish timings of the same variable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Timing {
+    /// Create a new timing event
+    pub fn new(category: impl Into<String>, variable: impl Into<String>, timing: u64) -> Self {
+        Self {
+            category: category.into(),
+            variable: variable.into(),
+            timing,
+            label: None,
+        }
+    }
+
+    /// Set the label of the timing
+    #[must_use]
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+}
+
+impl HasSchema for Timing {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("timing", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// An event recording an error in the application.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplicationError {
+    /// The error message
+    pub message: String,
+
+    /// The language the application is written in. Defaults to `"RUST"`.
+    pub programming_language: String,
+
+    /// The type or module where the error happened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class_name: Option<String>,
+
+    /// The name of the error type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exception_name: Option<String>,
+
+    /// Whether the error was fatal, like a panic
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_fatal: Option<bool>,
+
+    /// The line where the error happened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_number: Option<u32>,
+
+    /// The column where the error happened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_column: Option<u32>,
+
+    /// The stack trace (or backtrace) of the error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_trace: Option<String>,
+
+    /// The stack trace of the error's cause
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cause_stack_trace: Option<String>,
+
+    /// The ID of the thread where the error happened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<u64>,
+
+    /// The name of the thread where the error happened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_name: Option<String>,
+}
+
+impl ApplicationError {
+    /// Create a new application error event with just a message
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            programming_language: "RUST".to_owned(),
+            class_name: None,
+            exception_name: None,
+            is_fatal: None,
+            line_number: None,
+            line_column: None,
+            stack_trace: None,
+            cause_stack_trace: None,
+            thread_id: None,
+            thread_name: None,
+        }
+    }
+
+    /// Set the name of the error type
+    #[must_use]
+    pub fn with_exception_name(mut self, exception_name: impl Into<String>) -> Self {
+        self.exception_name = Some(exception_name.into());
+        self
+    }
+
+    /// Set whether the error was fatal
+    #[must_use]
+    pub fn with_fatal(mut self, is_fatal: bool) -> Self {
+        self.is_fatal = Some(is_fatal);
+        self
+    }
+
+    /// Set where the error happened: the type or module, the line and the
+    /// column. This works well with [`std::panic::Location`], or with the
+    /// [`module_path!`], [`line!`] and [`column!`] macros.
+    #[must_use]
+    pub fn with_location(mut self, class_name: impl Into<String>, line: u32, column: u32) -> Self {
+        self.class_name = Some(class_name.into());
+        self.line_number = Some(line);
+        self.line_column = Some(column);
+        self
+    }
+
+    /// Set the stack trace of the error
+    #[must_use]
+    pub fn with_stack_trace(mut self, stack_trace: impl Into<String>) -> Self {
+        self.stack_trace = Some(stack_trace.into());
+        self
+    }
+
+    /// Set the name of the thread where the error happened
+    #[must_use]
+    pub fn with_thread_name(mut self, thread_name: impl Into<String>) -> Self {
+        self.thread_name = Some(thread_name.into());
+        self
+    }
+}
+
+impl HasSchema for ApplicationError {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("application_error", SchemaVersion::new(1, 0, 2))
+    }
+}
+
+/// An event recording that the user clicked a link.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkClick {
+    /// The URL the link points to
+    pub target_url: String,
+
+    /// The ID of the link element
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element_id: Option<String>,
+
+    /// The classes of the link element
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub element_classes: Vec<String>,
+
+    /// The target of the link element, like `"_blank"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element_target: Option<String>,
+
+    /// The text content of the link element
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub element_content: Option<String>,
+}
+
+impl LinkClick {
+    /// Create a new link click event
+    pub fn new(target_url: impl Into<String>) -> Self {
+        Self {
+            target_url: target_url.into(),
+            element_id: None,
+            element_classes: Vec::new(),
+            element_target: None,
+            element_content: None,
+        }
+    }
+
+    /// Set the ID of the link element
+    #[must_use]
+    pub fn with_element_id(mut self, element_id: impl Into<String>) -> Self {
+        self.element_id = Some(element_id.into());
+        self
+    }
+
+    /// Add a class of the link element
+    #[must_use]
+    pub fn with_element_class(mut self, class: impl Into<String>) -> Self {
+        self.element_classes.push(class.into());
+        self
+    }
+
+    /// Set the target of the link element
+    #[must_use]
+    pub fn with_element_target(mut self, element_target: impl Into<String>) -> Self {
+        self.element_target = Some(element_target.into());
+        self
+    }
+
+    /// Set the text content of the link element
+    #[must_use]
+    pub fn with_element_content(mut self, element_content: impl Into<String>) -> Self {
+        self.element_content = Some(element_content.into());
+        self
+    }
+}
+
+impl HasSchema for LinkClick {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("link_click", SchemaVersion::new(1, 0, 1))
+    }
+}
+
+/// The value of a [`SiteSearch`] filter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum FilterValue {
+    /// A text filter, like `category: "shoes"`
+    Text(String),
+
+    /// An on/off filter, like `in_stock: true`
+    Flag(bool),
+}
+
+impl From<String> for FilterValue {
+    fn from(value: String) -> Self {
+        FilterValue::Text(value)
+    }
+}
+
+impl From<&str> for FilterValue {
+    fn from(value: &str) -> Self {
+        FilterValue::Text(value.to_owned())
+    }
+}
+
+impl From<bool> for FilterValue {
+    fn from(value: bool) -> Self {
+        FilterValue::Flag(value)
+    }
+}
+
+/// An event recording that the user searched the site or app.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteSearch {
+    /// The search terms
+    pub terms: Vec<String>,
+
+    /// The filters applied to the search
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub filters: BTreeMap<String, FilterValue>,
+
+    /// The total number of results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_results: Option<u64>,
+
+    /// The number of results shown on the first page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_results: Option<u64>,
+}
+
+impl SiteSearch {
+    /// Create a new site search event
+    pub fn new<T: Into<String>>(terms: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            terms: terms.into_iter().map(Into::into).collect(),
+            filters: BTreeMap::new(),
+            total_results: None,
+            page_results: None,
+        }
+    }
+
+    /// Add a filter applied to the search
+    #[must_use]
+    pub fn with_filter(mut self, name: impl Into<String>, value: impl Into<FilterValue>) -> Self {
+        self.filters.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set the number of results: the total, and on the first page
+    #[must_use]
+    pub fn with_results(mut self, total_results: u64, page_results: u64) -> Self {
+        self.total_results = Some(total_results);
+        self.page_results = Some(page_results);
+        self
+    }
+}
+
+impl HasSchema for SiteSearch {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("site_search", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// The kind of form element in a [`ChangeForm`] event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum NodeName {
+    /// An `<input>` element
+    Input,
+
+    /// A `<textarea>` element
+    Textarea,
+
+    /// A `<select>` element
+    Select,
+}
+
+/// An event recording that the user changed the value of a form element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeForm {
+    /// The ID of the form
+    pub form_id: String,
+
+    /// The ID of the element that changed
+    pub element_id: String,
+
+    /// The kind of element that changed
+    pub node_name: NodeName,
+
+    /// The type of the element, for `<input>` elements, like `"text"` or
+    /// `"checkbox"`
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub element_type: Option<String>,
+
+    /// The classes of the element
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub element_classes: Vec<String>,
+
+    /// The new value of the element. This is always sent, as `null` if it's
+    /// `None`, since the schema requires it.
+    pub value: Option<String>,
+}
+
+impl ChangeForm {
+    /// Create a new change form event
+    pub fn new(
+        form_id: impl Into<String>,
+        element_id: impl Into<String>,
+        node_name: NodeName,
+        value: Option<String>,
+    ) -> Self {
+        Self {
+            form_id: form_id.into(),
+            element_id: element_id.into(),
+            node_name,
+            element_type: None,
+            element_classes: Vec::new(),
+            value,
+        }
+    }
+
+    /// Set the type of the element
+    #[must_use]
+    pub fn with_type(mut self, element_type: impl Into<String>) -> Self {
+        self.element_type = Some(element_type.into());
+        self
+    }
+
+    /// Add a class of the element
+    #[must_use]
+    pub fn with_element_class(mut self, class: impl Into<String>) -> Self {
+        self.element_classes.push(class.into());
+        self
+    }
+}
+
+impl HasSchema for ChangeForm {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("change_form", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+    use uuid::Uuid;
+
+    use super::{
+        ApplicationError, ChangeForm, LinkClick, NodeName, ScreenView, SiteSearch, Timing,
+    };
+    use crate::payload::Envelope;
+    use crate::HasSchema;
+
+    /// Serialize a payload as self-describing JSON
+    fn to_json(payload: impl HasSchema + serde::Serialize) -> JsonValue {
+        serde_json::to_value(Envelope(payload)).unwrap()
+    }
+
+    #[test]
+    fn test_screen_view() {
+        let previous = ScreenView::new("home", Uuid::nil()).with_type("feed");
+        let id = Uuid::from_u128(1);
+
+        assert_eq!(
+            to_json(ScreenView::new("settings", id)),
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/screen_view/jsonschema/1-0-0",
+                "data": {"name": "settings", "id": "00000000-0000-0000-0000-000000000001"},
+            })
+        );
+        assert_eq!(
+            to_json(
+                ScreenView::new("settings", id)
+                    .with_type("modal")
+                    .with_previous(&previous)
+                    .with_transition_type("push")
+            )["data"],
+            json!({
+                "name": "settings",
+                "id": "00000000-0000-0000-0000-000000000001",
+                "type": "modal",
+                "previousName": "home",
+                "previousId": "00000000-0000-0000-0000-000000000000",
+                "previousType": "feed",
+                "transitionType": "push",
+            })
+        );
+    }
+
+    #[test]
+    fn test_timing() {
+        assert_eq!(
+            to_json(Timing::new("startup", "load_config", 42).with_label("cold")),
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/timing/jsonschema/1-0-0",
+                "data": {
+                    "category": "startup",
+                    "variable": "load_config",
+                    "timing": 42,
+                    "label": "cold",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_application_error() {
+        assert_eq!(
+            to_json(ApplicationError::new("disk full")),
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/application_error/jsonschema/1-0-2",
+                "data": {"message": "disk full", "programmingLanguage": "RUST"},
+            })
+        );
+        assert_eq!(
+            to_json(
+                ApplicationError::new("disk full")
+                    .with_exception_name("std::io::Error")
+                    .with_fatal(true)
+                    .with_location("app::storage", 12, 5)
+                    .with_stack_trace("backtrace")
+                    .with_thread_name("main")
+            )["data"],
+            json!({
+                "message": "disk full",
+                "programmingLanguage": "RUST",
+                "className": "app::storage",
+                "exceptionName": "std::io::Error",
+                "isFatal": true,
+                "lineNumber": 12,
+                "lineColumn": 5,
+                "stackTrace": "backtrace",
+                "threadName": "main",
+            })
+        );
+    }
+
+    #[test]
+    fn test_link_click() {
+        assert_eq!(
+            to_json(
+                LinkClick::new("https://example.com")
+                    .with_element_id("home")
+                    .with_element_class("nav")
+                    .with_element_class("active")
+                    .with_element_target("_blank")
+                    .with_element_content("Home")
+            ),
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/link_click/jsonschema/1-0-1",
+                "data": {
+                    "targetUrl": "https://example.com",
+                    "elementId": "home",
+                    "elementClasses": ["nav", "active"],
+                    "elementTarget": "_blank",
+                    "elementContent": "Home",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_site_search() {
+        assert_eq!(
+            to_json(
+                SiteSearch::new(["red", "shoes"])
+                    .with_filter("category", "footwear")
+                    .with_filter("in_stock", true)
+                    .with_results(120, 20)
+            ),
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/site_search/jsonschema/1-0-0",
+                "data": {
+                    "terms": ["red", "shoes"],
+                    "filters": {"category": "footwear", "in_stock": true},
+                    "totalResults": 120,
+                    "pageResults": 20,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_change_form() {
+        assert_eq!(
+            to_json(
+                ChangeForm::new("signup", "email", NodeName::Input, None)
+                    .with_type("email")
+                    .with_element_class("required")
+            ),
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/change_form/jsonschema/1-0-0",
+                "data": {
+                    "formId": "signup",
+                    "elementId": "email",
+                    "nodeName": "INPUT",
+                    "type": "email",
+                    "elementClasses": ["required"],
+                    "value": null,
+                },
+            })
+        );
+    }
+}
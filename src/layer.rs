@@ -0,0 +1,331 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+A [`tracing_subscriber`] [`Layer`] that tracks selected `tracing` events as
+Snowplow self-describing events, so that product analytics can piggyback on
+existing instrumentation. Requires the `tracing-layer` feature.
+
+Events are selected by target or by field name. The fields of each selected
+event (including its `message`, if it has one) become the JSON data of a
+self-describing event with the layer's schema. Optionally, the durations of
+selected spans can be tracked as Snowplow timing events.
+
+```no_run
+use std::sync::Arc;
+
+use snowplow_tracker::layer::SnowplowLayer;
+use snowplow_tracker::{Schema, SchemaVersion, Tracker};
+use tracing_subscriber::layer::SubscriberExt as _;
+
+# async fn example() -> Result<(), Box<dyn std::error::Error>> {
+let tracker = Tracker::builder()
+    .collector("collector.example.com")
+    .app_id("my-app")
+    .build()?;
+
+let layer = SnowplowLayer::new(
+    Arc::new(tracker),
+    Schema::new("com.example", "app_event", SchemaVersion::new(1, 0, 0)),
+)
+.with_target("my_app::analytics")
+.with_span_timing();
+
+tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))?;
+
+tracing::info!(target: "my_app::analytics", product = "shoe", price = 9.95, "purchase");
+# Ok(())
+# }
+```
+*/
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::events::Timing;
+use crate::payload::{HasSchema as _, Schema, SelfDescribingJson, SnowplowTimestamp};
+use crate::tracker::{TrackedEvent, Tracker};
+
+/// How a [`SnowplowLayer`] selects the events and spans it tracks
+#[derive(Debug, Clone)]
+enum Selector {
+    /// Events with this target, or a target nested in it
+    Target(String),
+
+    /// Events with a field with this name
+    Field(String),
+}
+
+/// Whether `target` is `parent`, or is nested in it, like `app::analytics`
+/// in `app`.
+fn is_target(target: &str, parent: &str) -> bool {
+    match target.strip_prefix(parent) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+/// The start time of a span selected for timing, kept in its extensions
+struct SpanStart(Instant);
+
+/// Records the fields of a tracing event into a JSON object
+struct JsonVisitor<'a>(&'a mut JsonMap<String, JsonValue>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{value:?}").into());
+    }
+}
+
+/**
+A [`Layer`] that tracks selected `tracing` events with a [`Tracker`]. See the
+[module docs][self] for an example.
+
+Events are only tracked if they match at least one of the targets or fields
+given with [`with_target`][Self::with_target] and
+[`with_field`][Self::with_field], so a layer without any of those tracks
+nothing. Events from this crate itself are never tracked.
+
+Tracking happens in a background task, which is spawned when the layer is
+created and runs until the layer is dropped. Errors tracking events are
+ignored, since reporting them through `tracing` could cause a feedback loop;
+[subscribe][crate::emitter::Emitter::subscribe] to the tracker's emitter to
+monitor delivery.
+*/
+pub struct SnowplowLayer {
+    sender: mpsc::UnboundedSender<TrackedEvent<SelfDescribingJson>>,
+    schema: Schema,
+    selectors: Vec<Selector>,
+    span_timing: bool,
+}
+
+impl SnowplowLayer {
+    /// Create a new layer that tracks events with the given schema.
+    ///
+    /// # Panics
+    ///
+    /// This spawns the task that does the tracking, so it panics if it's not
+    /// called from within a tokio runtime.
+    pub fn new(tracker: Arc<Tracker>, schema: Schema) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                let _ = tracker.track(event).await;
+            }
+        });
+
+        Self {
+            sender,
+            schema,
+            selectors: Vec::new(),
+            span_timing: false,
+        }
+    }
+
+    /// Track events (and spans, for timing) with this target, or any target
+    /// nested in it; for instance, `"app"` selects both `app` and
+    /// `app::analytics`.
+    #[must_use]
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.selectors.push(Selector::Target(target.into()));
+        self
+    }
+
+    /// Track events (and spans, for timing) that have a field with this
+    /// name, regardless of their target.
+    #[must_use]
+    pub fn with_field(mut self, field: impl Into<String>) -> Self {
+        self.selectors.push(Selector::Field(field.into()));
+        self
+    }
+
+    /// Also track the durations of selected spans, as Snowplow timing events.
+    /// The event's `category` is the span's target, its `variable` is the
+    /// span's name, and its `timing` is the time from when the span was
+    /// created until it closed, in milliseconds.
+    #[must_use]
+    pub fn with_span_timing(mut self) -> Self {
+        self.span_timing = true;
+        self
+    }
+
+    fn selects(&self, metadata: &Metadata<'_>) -> bool {
+        if is_target(metadata.target(), env!("CARGO_CRATE_NAME")) {
+            return false;
+        }
+
+        self.selectors.iter().any(|selector| match selector {
+            Selector::Target(target) => is_target(metadata.target(), target),
+            Selector::Field(field) => metadata.fields().field(field).is_some(),
+        })
+    }
+
+    fn track(&self, schema: Schema, data: JsonValue) {
+        let event = TrackedEvent {
+            timestamp: Some(SnowplowTimestamp::now()),
+            ..TrackedEvent::new(SelfDescribingJson::new(schema, data))
+        };
+
+        // An error here means the tracking task is gone, because the runtime
+        // is shutting down
+        let _ = self.sender.send(event);
+    }
+}
+
+impl<S> Layer<S> for SnowplowLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if !self.selects(event.metadata()) {
+            return;
+        }
+
+        let mut data = JsonMap::new();
+        event.record(&mut JsonVisitor(&mut data));
+        self.track(self.schema.clone(), JsonValue::Object(data));
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !self.span_timing || !self.selects(attrs.metadata()) {
+            return;
+        }
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanStart(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(SpanStart(start)) = span.extensions_mut().remove::<SpanStart>() else {
+            return;
+        };
+
+        let timing = Timing::new(
+            span.metadata().target(),
+            span.name(),
+            u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+        );
+        self.track(
+            timing.schema(),
+            serde_json::to_value(timing).expect("timings always serialize successfully"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use serde_json::json;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    use super::{is_target, SnowplowLayer};
+    use crate::testing::CaptureServer;
+    use crate::{Schema, SchemaVersion};
+
+    #[test]
+    fn test_is_target() {
+        assert!(is_target("app", "app"));
+        assert!(is_target("app::analytics", "app"));
+        assert!(!is_target("application", "app"));
+        assert!(!is_target("other::app", "app"));
+    }
+
+    #[tokio::test]
+    async fn test_layer() {
+        let server = CaptureServer::start(200).await;
+        let layer = SnowplowLayer::new(
+            Arc::new(server.tracker()),
+            Schema::new("com.example", "app_event", SchemaVersion::new(1, 0, 0)),
+        )
+        .with_target("app::analytics")
+        .with_field("track")
+        .with_span_timing();
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app::analytics", product = "shoe", price = 9.5, "purchase");
+            tracing::info!(target: "app::other", "not tracked");
+            tracing::info!(target: "app::other", track = true, "tracked by field");
+
+            let span = tracing::info_span!(target: "app::analytics", "checkout");
+            span.in_scope(|| std::thread::sleep(Duration::from_millis(5)));
+        });
+
+        for _ in 0..100 {
+            if server.bodies().len() >= 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let events: Vec<_> = server
+            .events()
+            .into_iter()
+            .map(|event| event.payload.unwrap())
+            .collect();
+        assert_eq!(events.len(), 3);
+
+        assert_eq!(
+            events[0],
+            json!({
+                "schema": "iglu:com.example/app_event/jsonschema/1-0-0",
+                "data": {"message": "purchase", "product": "shoe", "price": 9.5},
+            })
+        );
+        assert_eq!(events[1]["data"]["message"], "tracked by field");
+
+        let timing = &events[2];
+        assert_eq!(
+            timing["schema"],
+            "iglu:com.snowplowanalytics.snowplow/timing/jsonschema/1-0-0"
+        );
+        assert_eq!(timing["data"]["category"], "app::analytics");
+        assert_eq!(timing["data"]["variable"], "checkout");
+        assert!(timing["data"]["timing"].as_u64().unwrap() >= 5);
+    }
+}
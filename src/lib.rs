@@ -9,7 +9,6 @@
 // "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
 
-// TODO: logging with tracing
 // TODO: some more reorganization of modules
 
 //! # Snowplow Rust Tracker
@@ -18,30 +17,102 @@
 //!
 //! ## Example usage
 //!
-//! ```
-//! use snowplow_tracker::{Snowplow, SelfDescribingJson, SelfDescribingEvent};
+//! ```no_run
+//! use serde::Serialize;
 //! use serde_json::json;
+//! use snowplow_tracker::{
+//!     HasSchema, Platform, Schema, SchemaVersion, SelfDescribingJson, TrackedEvent, Tracker,
+//! };
+//!
+//! // Your own event type, with its Iglu schema
+//! #[derive(Serialize)]
+//! struct LinkClick {
+//!     #[serde(rename = "targetUrl")]
+//!     target_url: String,
+//! }
+//!
+//! impl HasSchema for LinkClick {
+//!     fn schema(&self) -> Schema {
+//!         Schema::new(
+//!             "com.snowplowanalytics.snowplow",
+//!             "link_click",
+//!             SchemaVersion::new(1, 0, 1),
+//!         )
+//!     }
+//! }
 //!
+//! # async fn example() -> Result<(), snowplow_tracker::TrackError> {
 //! // Initialize a tracker instance given a namespace, application ID, and Snowplow collector URL
-//! let tracker = Snowplow::create_tracker("ns", "app_id", "https://...");
+//! let tracker = Tracker::build(
+//!     "ns",
+//!     "app_id".to_owned(),
+//!     Platform::Desktop,
+//!     "https://collector.example.com/com.snowplowanalytics.snowplow/tp2"
+//!         .parse()
+//!         .expect("valid URL"),
+//!     reqwest::Client::new(),
+//! );
 //!
 //! // Tracking a self-describing event with a context entity
-//! tracker.track(
-//!     SelfDescribingEvent {
-//!         schema: "iglu:com.snowplowanalytics.snowplow/link_click/jsonschema/1-0-1".to_string(),
-//!         data: json!({"targetUrl": "http://a-target-url.com"})
-//!     },
-//!     Some(vec![
-//!         SelfDescribingJson::new("iglu:org.schema/WebPage/jsonschema/1-0-0", json!({"keywords": ["tester"]}))
-//!     ])
-//! );
+//! let event = TrackedEvent::new(LinkClick {
+//!     target_url: "http://a-target-url.com".to_owned(),
+//! })
+//! .with_context(SelfDescribingJson::new(
+//!     Schema::new("org.schema", "WebPage", SchemaVersion::new(1, 0, 0)),
+//!     json!({"keywords": ["tester"]}),
+//! ));
+//!
+//! tracker.track(event).await?;
+//! # Ok(())
+//! # }
 //! ```
+//!
+//! ## Diagnostics
+//!
+//! The tracker and emitter report what they're doing through [`tracing`],
+//! under the `snowplow_tracker::tracker` and `snowplow_tracker::emitter`
+//! targets: batch sizes and dropped events at `debug` level, request
+//! latencies and status codes at `debug`, retries at `warn`, and batches that
+//! couldn't be delivered at `error`. Delivery counters, like the number of
+//! events sent and failed, are available from
+//! [`Emitter::metrics`][emitter::Emitter::metrics], and through the
+//! `metrics` crate with the `metrics` feature; see [`metrics`][mod@metrics].
 #![deny(missing_docs)]
 
+pub mod analytics;
+pub mod builder;
+pub mod circuit_breaker;
+pub mod consent;
+pub mod deep_link;
+pub mod ecommerce;
 pub mod emitter;
+pub mod events;
+#[cfg(any(test, feature = "tracing-layer"))]
+pub mod layer;
+pub mod lifecycle;
+pub mod media;
+pub mod metrics;
+#[cfg(any(test, feature = "micro"))]
+pub mod micro;
 pub mod payload;
+pub mod pii;
+pub mod platform;
+pub mod plugin;
+pub mod registry;
+pub mod sampling;
+pub mod settings;
+pub mod subject;
 pub mod tracker;
 pub mod util;
 
-pub use payload::{HasSchema, Platform, Schema, SchemaVersion};
+#[cfg(test)]
+mod testing;
+
+pub use builder::TrackerBuilder;
+pub use consent::ConsentLevel;
+pub use payload::{HasSchema, Platform, Schema, SchemaVersion, SelfDescribingJson};
+pub use plugin::{PluginAction, TrackerPlugin};
+pub use registry::{default_tracker, get, register, set_default, unregister};
+pub use settings::TrackerSettings;
+pub use subject::Subject;
 pub use tracker::{TrackError, TrackedEvent, Tracker, TrackerConfig};
@@ -0,0 +1,360 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Application lifecycle events, like the ones the mobile trackers track
+automatically: the first launch after an install (with
+[`Tracker::track_install_once`]), the app moving to the foreground and
+background (with [`Tracker::app_foreground`] and [`Tracker::app_background`]),
+and crashes (with [`install_panic_hook`]).
+
+Moving to the background and foreground also feeds into the tracker's
+session handling: if the app spends longer than the
+[background session timeout][Tracker::set_background_session_timeout] in the
+background, a new session is started on the tracker's
+[`Subject`] when it comes back to the foreground.
+
+```no_run
+use std::sync::Arc;
+use std::time::Duration;
+
+use snowplow_tracker::lifecycle::install_panic_hook;
+use snowplow_tracker::Tracker;
+
+# async fn example(mut tracker: Tracker) -> Result<(), Box<dyn std::error::Error>> {
+tracker.set_background_session_timeout(Duration::from_secs(30 * 60));
+let tracker = Arc::new(tracker);
+
+install_panic_hook(tracker.clone(), Duration::from_secs(5));
+tracker.track_install_once("/var/lib/my-app/installed").await?;
+
+tracker.app_background().await?;
+tracker.app_foreground().await?;
+# Ok(())
+# }
+```
+*/
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::events::ApplicationError;
+use crate::payload::{HasSchema, Schema, SchemaVersion};
+use crate::subject::Subject;
+use crate::tracker::{TrackError, TrackedEvent, Tracker};
+
+/// An event recording the first launch of the app after it was installed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ApplicationInstall {}
+
+impl HasSchema for ApplicationInstall {
+    fn schema(&self) -> Schema {
+        Schema::new(
+            "com.snowplowanalytics.mobile",
+            "application_install",
+            SchemaVersion::new(1, 0, 0),
+        )
+    }
+}
+
+/// An event recording that the app moved to the foreground
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplicationForeground {
+    /// How many times the app has moved to the foreground, including this
+    /// time, since the tracker was created
+    pub foreground_index: u32,
+}
+
+impl HasSchema for ApplicationForeground {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("application_foreground", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// An event recording that the app moved to the background
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplicationBackground {
+    /// How many times the app has moved to the background, including this
+    /// time, since the tracker was created
+    pub background_index: u32,
+}
+
+impl HasSchema for ApplicationBackground {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("application_background", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// An error from [`Tracker::track_install_once`]
+#[derive(Debug, Error)]
+pub enum InstallError {
+    /// The install marker couldn't be read or written
+    #[error("Failed to access the install marker at {path:?}")]
+    Marker {
+        /// The path of the marker
+        path: PathBuf,
+
+        /// The underlying I/O error
+        #[source]
+        source: io::Error,
+    },
+
+    /// The install event couldn't be tracked. The marker isn't kept in this
+    /// case, so the event will be tracked again next time.
+    #[error("Failed to track the install event")]
+    Track(#[from] TrackError),
+}
+
+/// The lifecycle state of a [`Tracker`]: how many times the app has moved to
+/// the foreground and background, and when it last moved to the background.
+#[derive(Debug, Default)]
+pub(crate) struct LifecycleState {
+    foreground_index: u32,
+    background_index: u32,
+    backgrounded_at: Option<Instant>,
+    session_timeout: Option<Duration>,
+}
+
+impl LifecycleState {
+    pub(crate) fn set_session_timeout(&mut self, timeout: Duration) {
+        self.session_timeout = Some(timeout)
+    }
+
+    /// Record that the app moved to the foreground, starting a new session
+    /// on the subject if it was in the background for too long
+    pub(crate) fn foreground(&mut self, subject: &mut Subject) -> ApplicationForeground {
+        let backgrounded_at = self.backgrounded_at.take();
+
+        if let (Some(backgrounded_at), Some(timeout)) = (backgrounded_at, self.session_timeout) {
+            if backgrounded_at.elapsed() >= timeout {
+                tracing::debug!("background session timeout expired; starting a new session");
+                subject.session_id = Some(Uuid::new_v4().to_string());
+                subject.session_index = Some(subject.session_index.map_or(1, |index| index + 1));
+            }
+        }
+
+        self.foreground_index += 1;
+        ApplicationForeground {
+            foreground_index: self.foreground_index,
+        }
+    }
+
+    /// Record that the app moved to the background
+    pub(crate) fn background(&mut self) -> ApplicationBackground {
+        self.backgrounded_at = Some(Instant::now());
+        self.background_index += 1;
+        ApplicationBackground {
+            background_index: self.background_index,
+        }
+    }
+}
+
+/// Build the `application_error` event for a panic
+fn panic_event(info: &std::panic::PanicHookInfo<'_>) -> ApplicationError {
+    let payload = info.payload();
+    let message = match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.as_str(),
+            None => "Box<dyn Any>",
+        },
+    };
+
+    let mut event = ApplicationError::new(message)
+        .with_exception_name("panic")
+        .with_fatal(true);
+
+    if let Some(location) = info.location() {
+        event = event.with_location(location.file(), location.line(), location.column());
+    }
+
+    if let Some(name) = std::thread::current().name() {
+        event = event.with_thread_name(name);
+    }
+
+    // This respects `RUST_BACKTRACE`, like the default panic hook
+    let backtrace = Backtrace::capture();
+    if backtrace.status() == BacktraceStatus::Captured {
+        event = event.with_stack_trace(backtrace.to_string());
+    }
+
+    event
+}
+
+/// The name of the thread that reports panics from the panic hook
+const PANIC_REPORTER_THREAD: &str = "snowplow-panic-reporter";
+
+/**
+Install a panic hook that tracks an `application_error` event with the panic
+message and location (and a backtrace, if `RUST_BACKTRACE` is set), then runs
+the previously installed hook. The hook runs before the panic unwinds, so the
+event is sent even if the panic brings down the whole process.
+
+The event is sent from a separate thread with its own runtime, so that this
+works whether or not the panic happened inside an async runtime. The
+panicking thread waits at most `timeout` for it to be sent.
+*/
+pub fn install_panic_hook(tracker: Arc<Tracker>, timeout: Duration) {
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        // Don't try to report a panic in reporting a panic
+        if std::thread::current().name() == Some(PANIC_REPORTER_THREAD) {
+            return previous(info);
+        }
+
+        let event = TrackedEvent::new(panic_event(info));
+        let tracker = tracker.clone();
+
+        // If sending fails (or panics), there's nothing more we can do; the
+        // emitter has already logged why. The reporter thread is left to
+        // finish on its own if it takes too long, so that the panicking
+        // thread never waits longer than the timeout.
+        let (reported, report) = mpsc::channel();
+        let reporter = std::thread::Builder::new()
+            .name(PANIC_REPORTER_THREAD.to_owned())
+            .spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("failed to build a runtime to report a panic");
+
+                let _ = runtime
+                    .block_on(async { tokio::time::timeout(timeout, tracker.track(event)).await });
+
+                // Don't wait for anything still running on the runtime, like
+                // a blocking DNS lookup
+                runtime.shutdown_background();
+                let _ = reported.send(());
+            });
+
+        if reporter.is_ok() {
+            let _ = report.recv_timeout(timeout);
+        }
+
+        previous(info)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::testing::CaptureServer;
+    use crate::{ConsentLevel, Subject};
+
+    #[tokio::test]
+    async fn test_install_is_tracked_once() {
+        let server = CaptureServer::start(200).await;
+        let tracker = server.tracker();
+        let marker =
+            std::env::temp_dir().join(format!("snowplow-install-{}", uuid::Uuid::new_v4()));
+
+        assert!(tracker.track_install_once(&marker).await.unwrap());
+        assert!(!tracker.track_install_once(&marker).await.unwrap());
+        std::fs::remove_file(&marker).unwrap();
+
+        let events = server.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].payload,
+            Some(json!({
+                "schema": "iglu:com.snowplowanalytics.mobile/application_install/jsonschema/1-0-0",
+                "data": {},
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_install_is_retried() {
+        let server = CaptureServer::start(500).await;
+        let tracker = server.tracker();
+        let marker =
+            std::env::temp_dir().join(format!("snowplow-install-{}", uuid::Uuid::new_v4()));
+
+        tracker.track_install_once(&marker).await.unwrap_err();
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_install_is_retried() {
+        let server = CaptureServer::start(200).await;
+        let tracker = server.tracker();
+        let marker =
+            std::env::temp_dir().join(format!("snowplow-install-{}", uuid::Uuid::new_v4()));
+
+        tracker.set_consent(ConsentLevel::None);
+        assert!(!tracker.track_install_once(&marker).await.unwrap());
+        assert!(!marker.exists());
+
+        tracker.set_consent(ConsentLevel::Full);
+        assert!(tracker.track_install_once(&marker).await.unwrap());
+        std::fs::remove_file(&marker).unwrap();
+        assert_eq!(server.events().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_background_session_timeout() {
+        let server = CaptureServer::start(200).await;
+        let mut tracker = server
+            .tracker_builder()
+            .subject(Subject {
+                session_id: Some("first".to_owned()),
+                session_index: Some(1),
+                ..Subject::default()
+            })
+            .build()
+            .unwrap();
+        tracker.set_background_session_timeout(Duration::from_millis(30));
+
+        tracker.app_background().await.unwrap();
+        tracker.app_foreground().await.unwrap();
+        assert_eq!(tracker.subject().session_id.as_deref(), Some("first"));
+
+        tracker.app_background().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        tracker.app_foreground().await.unwrap();
+
+        let subject = tracker.subject();
+        assert_ne!(subject.session_id.as_deref(), Some("first"));
+        assert_eq!(subject.session_index, Some(2));
+
+        let events = server.events();
+        let data: Vec<_> = events
+            .iter()
+            .map(|event| event.payload.as_ref().unwrap()["data"].clone())
+            .collect();
+        assert_eq!(
+            data,
+            [
+                json!({"backgroundIndex": 1}),
+                json!({"foregroundIndex": 1}),
+                json!({"backgroundIndex": 2}),
+                json!({"foregroundIndex": 2}),
+            ]
+        );
+
+        // The foreground event is the first one in the new session
+        assert_eq!(events[2].params["sid"], "first");
+        assert_eq!(events[3].params["sid"], json!(subject.session_id));
+    }
+}
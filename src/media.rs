@@ -0,0 +1,812 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Media tracking. A [`MediaTracking`] follows a single media player (a video or
+audio player) through a playback session, keeping track of its state and
+tracking the Snowplow media events (`play_event`, `pause_event`, etc) as
+it's told about them. Every event has the player's state attached, as a
+[`MediaPlayer`] entity, along with a session entity identifying the
+playback session; while an ad is playing, its [`MediaAd`] and
+[`MediaAdBreak`] entities are attached too.
+
+```no_run
+use std::sync::Arc;
+use std::time::Duration;
+
+use snowplow_tracker::media::{MediaPlayer, MediaTracking};
+use snowplow_tracker::Tracker;
+
+# async fn example(tracker: Arc<Tracker>) -> Result<(), snowplow_tracker::TrackError> {
+let media = MediaTracking::new(tracker, MediaPlayer::new().with_duration(120.0))
+    .with_ping_interval(Duration::from_secs(30));
+
+media.play().await?;
+media.update_current_time(42.5);
+media.pause().await?;
+# Ok(())
+# }
+```
+*/
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use uuid::Uuid;
+
+use crate::payload::{HasSchema, Schema, SchemaVersion, SelfDescribingJson};
+use crate::tracker::{TrackError, TrackedEvent, Tracker};
+
+/// The vendor of the media events, and of most of their entities
+const MEDIA_VENDOR: &str = "com.snowplowanalytics.snowplow.media";
+
+/// The kind of media a [`MediaPlayer`] is playing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    /// A video
+    #[default]
+    Video,
+
+    /// Audio only
+    Audio,
+}
+
+/// The state of a media player. This is attached to every media event as a
+/// `media_player` entity.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaPlayer {
+    /// The current playback position, in seconds
+    pub current_time: f64,
+
+    /// The duration of the media, in seconds, if it's known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+
+    /// Whether playback has ended
+    pub ended: bool,
+
+    /// Whether playback is paused
+    pub paused: bool,
+
+    /// Whether the media is muted
+    pub muted: bool,
+
+    /// The volume, from 0 to 100
+    pub volume: u8,
+
+    /// The playback rate, where 1 is normal speed
+    pub playback_rate: f64,
+
+    /// Whether the media is a live stream
+    pub livestream: bool,
+
+    /// Whether the player is in fullscreen mode
+    pub fullscreen: bool,
+
+    /// The kind of media
+    pub media_type: MediaType,
+
+    /// A human-readable label for the media, like its title
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl MediaPlayer {
+    /// Create a new player state, for a paused video at the start, at full
+    /// volume and normal speed
+    pub fn new() -> Self {
+        Self {
+            current_time: 0.0,
+            duration: None,
+            ended: false,
+            paused: true,
+            muted: false,
+            volume: 100,
+            playback_rate: 1.0,
+            livestream: false,
+            fullscreen: false,
+            media_type: MediaType::Video,
+            label: None,
+        }
+    }
+
+    /// Set the duration of the media, in seconds
+    #[must_use]
+    pub fn with_duration(mut self, duration: f64) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Set the kind of media
+    #[must_use]
+    pub fn with_media_type(mut self, media_type: MediaType) -> Self {
+        self.media_type = media_type;
+        self
+    }
+
+    /// Set the label of the media
+    #[must_use]
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Mark the media as a live stream
+    #[must_use]
+    pub fn with_livestream(mut self) -> Self {
+        self.livestream = true;
+        self
+    }
+}
+
+impl Default for MediaPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HasSchema for MediaPlayer {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("media_player", SchemaVersion::new(2, 0, 0))
+    }
+}
+
+/// The playback session, attached to every media event as a `session`
+/// entity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MediaSession {
+    media_session_id: Uuid,
+
+    /// The ping interval, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ping_interval: Option<u64>,
+}
+
+impl HasSchema for MediaSession {
+    fn schema(&self) -> Schema {
+        Schema::new(MEDIA_VENDOR, "session", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// An ad, attached to media events while it's playing as an `ad` entity
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaAd {
+    /// The ID of the ad
+    pub ad_id: String,
+
+    /// The name of the ad
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The ID of the ad creative
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creative_id: Option<String>,
+
+    /// The duration of the ad, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+
+    /// Whether the ad can be skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skippable: Option<bool>,
+}
+
+impl MediaAd {
+    /// Create a new ad entity
+    pub fn new(ad_id: impl Into<String>) -> Self {
+        Self {
+            ad_id: ad_id.into(),
+            name: None,
+            creative_id: None,
+            duration: None,
+            skippable: None,
+        }
+    }
+
+    /// Set the name of the ad
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the duration of the ad, in seconds
+    #[must_use]
+    pub fn with_duration(mut self, duration: f64) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Set whether the ad can be skipped
+    #[must_use]
+    pub fn with_skippable(mut self, skippable: bool) -> Self {
+        self.skippable = Some(skippable);
+        self
+    }
+}
+
+impl HasSchema for MediaAd {
+    fn schema(&self) -> Schema {
+        Schema::new(MEDIA_VENDOR, "ad", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// A break for ads, attached to media events while it's in progress as an
+/// `ad_break` entity
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaAdBreak {
+    /// The ID of the break
+    pub break_id: String,
+
+    /// The playback position where the break started, in seconds
+    pub start_time: f64,
+
+    /// The name of the break
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl MediaAdBreak {
+    /// Create a new ad break entity
+    pub fn new(break_id: impl Into<String>, start_time: f64) -> Self {
+        Self {
+            break_id: break_id.into(),
+            start_time,
+            name: None,
+        }
+    }
+
+    /// Set the name of the break
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+impl HasSchema for MediaAdBreak {
+    fn schema(&self) -> Schema {
+        Schema::new(MEDIA_VENDOR, "ad_break", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// Everything a [`MediaTracking`] knows about its player
+#[derive(Debug)]
+struct MediaState {
+    player: MediaPlayer,
+    session: MediaSession,
+    ad: Option<MediaAd>,
+    ad_break: Option<MediaAdBreak>,
+}
+
+/// Convert a media entity to self-describing JSON
+fn entity(entity: &(impl HasSchema + Serialize)) -> SelfDescribingJson {
+    SelfDescribingJson::from_payload(entity).expect("media entities always serialize successfully")
+}
+
+impl MediaState {
+    /// Build a media event with the given schema name, with the current
+    /// entities attached
+    fn event(&self, name: &'static str, data: JsonValue) -> TrackedEvent<SelfDescribingJson> {
+        let mut contexts = vec![entity(&self.player), entity(&self.session)];
+        contexts.extend(self.ad.as_ref().map(entity));
+        contexts.extend(self.ad_break.as_ref().map(entity));
+
+        TrackedEvent {
+            contexts,
+            ..TrackedEvent::new(SelfDescribingJson::new(
+                Schema::new(MEDIA_VENDOR, name, SchemaVersion::new(1, 0, 0)),
+                data,
+            ))
+        }
+    }
+}
+
+fn lock(state: &Mutex<MediaState>) -> MutexGuard<'_, MediaState> {
+    state.lock().unwrap_or_else(|poison| poison.into_inner())
+}
+
+/// The task sending periodic pings, and the channel that asks it to stop
+#[derive(Debug)]
+struct Pings {
+    task: JoinHandle<()>,
+    stop: oneshot::Sender<()>,
+}
+
+/**
+Tracks the events of a single media player, with a [`Tracker`]. See the
+[module docs][self] for an example.
+
+Each method that tracks an event also updates the player's state to match;
+for instance, [`play`][Self::play] marks the player as not paused. Other
+changes to the player, like its playback position, can be made with
+[`update`][Self::update] or [`update_current_time`][Self::update_current_time],
+and are reflected in the next event.
+*/
+pub struct MediaTracking {
+    tracker: Arc<Tracker>,
+    state: Arc<Mutex<MediaState>>,
+    pings: Option<Pings>,
+}
+
+impl MediaTracking {
+    /// Start tracking a media player, with the given initial state. This
+    /// starts a new playback session.
+    pub fn new(tracker: Arc<Tracker>, player: MediaPlayer) -> Self {
+        let state = MediaState {
+            player,
+            session: MediaSession {
+                media_session_id: Uuid::new_v4(),
+                ping_interval: None,
+            },
+            ad: None,
+            ad_break: None,
+        };
+
+        Self {
+            tracker,
+            state: Arc::new(Mutex::new(state)),
+            pings: None,
+        }
+    }
+
+    /**
+    Track a `ping_event` periodically while the media is playing, so that
+    the time spent watching can be measured even if playback never pauses
+    or ends. Pings stop when [`stop`][Self::stop] is called, or when the
+    `MediaTracking` is dropped. The session entity reports the interval in
+    whole seconds, so it's rounded up to a whole number of seconds, and
+    intervals under a second (including zero) are one second.
+
+    # Panics
+
+    This spawns the task that sends the pings, so it panics if it's not
+    called from within a tokio runtime.
+    */
+    #[must_use]
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        let seconds = (interval.as_secs() + u64::from(interval.subsec_nanos() > 0)).max(1);
+        let interval = Duration::from_secs(seconds);
+        lock(&self.state).session.ping_interval = Some(seconds);
+
+        let tracker = self.tracker.clone();
+        let state = self.state.clone();
+        let (stop, mut stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            let start = tokio::time::Instant::now() + interval;
+            let mut ticks = tokio::time::interval_at(start, interval);
+            // A ping that took longer than the interval to send doesn't lead
+            // to a burst of pings to catch up
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                // A ping that's already being sent is allowed to finish, so
+                // that `stop` leaves nothing in flight
+                tokio::select! {
+                    _ = ticks.tick() => {}
+                    _ = &mut stopped => break,
+                }
+
+                let event = {
+                    let state = lock(&state);
+                    if state.player.paused || state.player.ended {
+                        continue;
+                    }
+                    state.event("ping_event", json!({}))
+                };
+
+                // Errors are already reported by the emitter's diagnostics
+                let _ = tracker.track(event).await;
+            }
+        });
+
+        if let Some(previous) = self.pings.replace(Pings { task, stop }) {
+            previous.task.abort();
+        }
+        self
+    }
+
+    /// Stop the periodic pings started by
+    /// [`with_ping_interval`][Self::with_ping_interval]. This waits for a
+    /// ping that's already being sent, so no more pings are tracked once it
+    /// returns.
+    pub async fn stop(&mut self) {
+        if let Some(Pings { task, stop }) = self.pings.take() {
+            // The task only exits once it's told to, so it can't have hung up
+            let _ = stop.send(());
+            let _ = task.await;
+        }
+    }
+
+    /// The ID of this playback session, attached to every event
+    pub fn session_id(&self) -> Uuid {
+        lock(&self.state).session.media_session_id
+    }
+
+    /// Get the current state of the player
+    pub fn player(&self) -> MediaPlayer {
+        lock(&self.state).player.clone()
+    }
+
+    /// Update the state of the player, without tracking anything
+    pub fn update(&self, update: impl FnOnce(&mut MediaPlayer)) {
+        update(&mut lock(&self.state).player);
+    }
+
+    /// Update the playback position, in seconds, without tracking anything
+    pub fn update_current_time(&self, current_time: f64) {
+        self.update(|player| player.current_time = current_time);
+    }
+
+    /// Update the state, then track an event with the given schema name
+    async fn track(
+        &self,
+        name: &'static str,
+        data: JsonValue,
+        update: impl FnOnce(&mut MediaState),
+    ) -> Result<(), TrackError> {
+        let event = {
+            let mut state = lock(&self.state);
+            update(&mut state);
+            state.event(name, data)
+        };
+
+        self.tracker.track(event).await
+    }
+
+    /// Track an event with the given schema name, which ends an ad or a
+    /// break: the event still has its entity attached, but it's cleared from
+    /// the state before the event is sent, so later events don't carry it
+    /// even if this one fails.
+    async fn track_end(
+        &self,
+        name: &'static str,
+        clear: impl FnOnce(&mut MediaState),
+    ) -> Result<(), TrackError> {
+        let event = {
+            let mut state = lock(&self.state);
+            let event = state.event(name, json!({}));
+            clear(&mut state);
+            event
+        };
+
+        self.tracker.track(event).await
+    }
+
+    /// Track that playback started or resumed
+    pub async fn play(&self) -> Result<(), TrackError> {
+        self.track("play_event", json!({}), |state| {
+            state.player.paused = false;
+            state.player.ended = false;
+        })
+        .await
+    }
+
+    /// Track that playback was paused
+    pub async fn pause(&self) -> Result<(), TrackError> {
+        self.track("pause_event", json!({}), |state| state.player.paused = true)
+            .await
+    }
+
+    /// Track that the user started seeking
+    pub async fn seek_start(&self) -> Result<(), TrackError> {
+        self.track("seek_start_event", json!({}), |_| ()).await
+    }
+
+    /// Track that the user finished seeking, to the given position in
+    /// seconds
+    pub async fn seek_end(&self, current_time: f64) -> Result<(), TrackError> {
+        self.track("seek_end_event", json!({}), |state| {
+            state.player.current_time = current_time;
+        })
+        .await
+    }
+
+    /// Track that playback reached the end of the media
+    pub async fn end(&self) -> Result<(), TrackError> {
+        self.track("end_event", json!({}), |state| {
+            state.player.ended = true;
+            state.player.paused = true;
+            if let Some(duration) = state.player.duration {
+                state.player.current_time = duration;
+            }
+        })
+        .await
+    }
+
+    /// Track that the volume changed, to a value from 0 to 100
+    pub async fn volume_change(&self, volume: u8) -> Result<(), TrackError> {
+        let previous = lock(&self.state).player.volume;
+        self.track(
+            "volume_change_event",
+            json!({"previousVolume": previous, "newVolume": volume}),
+            |state| state.player.volume = volume,
+        )
+        .await
+    }
+
+    /// Track that the playback rate changed
+    pub async fn playback_rate_change(&self, rate: f64) -> Result<(), TrackError> {
+        let previous = lock(&self.state).player.playback_rate;
+        self.track(
+            "playback_rate_change_event",
+            json!({"previousRate": previous, "newRate": rate}),
+            |state| state.player.playback_rate = rate,
+        )
+        .await
+    }
+
+    /// Track the start of a break for ads. The break is attached to every
+    /// event until [`ad_break_end`][Self::ad_break_end].
+    pub async fn ad_break_start(&self, ad_break: MediaAdBreak) -> Result<(), TrackError> {
+        self.track("ad_break_start_event", json!({}), |state| {
+            state.ad_break = Some(ad_break);
+        })
+        .await
+    }
+
+    /// Track the end of the current break for ads
+    pub async fn ad_break_end(&self) -> Result<(), TrackError> {
+        self.track_end("ad_break_end_event", |state| state.ad_break = None)
+            .await
+    }
+
+    /// Track the start of an ad. The ad is attached to every event until
+    /// it's completed or skipped.
+    pub async fn ad_start(&self, ad: MediaAd) -> Result<(), TrackError> {
+        self.track("ad_start_event", json!({}), |state| state.ad = Some(ad))
+            .await
+    }
+
+    /// Track that the user clicked the current ad
+    pub async fn ad_click(&self) -> Result<(), TrackError> {
+        self.track("ad_click_event", json!({}), |_| ()).await
+    }
+
+    /// Track that the current ad was skipped
+    pub async fn ad_skip(&self) -> Result<(), TrackError> {
+        self.track_end("ad_skip_event", |state| state.ad = None)
+            .await
+    }
+
+    /// Track that the current ad played to the end
+    pub async fn ad_complete(&self) -> Result<(), TrackError> {
+        self.track_end("ad_complete_event", |state| state.ad = None)
+            .await
+    }
+
+    /// Track a ping immediately, rather than waiting for the next periodic
+    /// one
+    pub async fn ping(&self) -> Result<(), TrackError> {
+        self.track("ping_event", json!({}), |_| ()).await
+    }
+}
+
+impl Drop for MediaTracking {
+    fn drop(&mut self) {
+        if let Some(pings) = &self.pings {
+            pings.task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::{lock, MediaAd, MediaAdBreak, MediaPlayer, MediaTracking};
+    use crate::testing::CaptureServer;
+    use crate::Tracker;
+
+    #[tokio::test]
+    async fn test_media_events() {
+        let server = CaptureServer::start(200).await;
+        let media = MediaTracking::new(
+            Arc::new(server.tracker()),
+            MediaPlayer::new().with_duration(60.0).with_label("Intro"),
+        );
+
+        media.play().await.unwrap();
+        media.update_current_time(12.5);
+        media.volume_change(50).await.unwrap();
+        media.ad_start(MediaAd::new("ad-1")).await.unwrap();
+        media.ad_complete().await.unwrap();
+        media.pause().await.unwrap();
+        media.end().await.unwrap();
+
+        let events = server.events();
+        let names: Vec<_> = events.iter().map(|event| event.name().unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                "play_event",
+                "volume_change_event",
+                "ad_start_event",
+                "ad_complete_event",
+                "pause_event",
+                "end_event"
+            ]
+        );
+
+        let entities = &events[1].contexts;
+        assert_eq!(
+            events[1].payload.as_ref().unwrap()["data"],
+            json!({"previousVolume": 100, "newVolume": 50})
+        );
+        assert_eq!(
+            entities[0],
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/media_player/jsonschema/2-0-0",
+                "data": {
+                    "currentTime": 12.5,
+                    "duration": 60.0,
+                    "ended": false,
+                    "paused": false,
+                    "muted": false,
+                    "volume": 50,
+                    "playbackRate": 1.0,
+                    "livestream": false,
+                    "fullscreen": false,
+                    "mediaType": "video",
+                    "label": "Intro",
+                },
+            })
+        );
+        assert_eq!(
+            entities[1],
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow.media/session/jsonschema/1-0-0",
+                "data": {"mediaSessionId": media.session_id()},
+            })
+        );
+
+        // The ad is only attached while it's playing
+        assert_eq!(events[3].contexts.len(), 3);
+        assert_eq!(events[3].contexts[2]["data"]["adId"], "ad-1");
+        assert_eq!(events[4].contexts.len(), 2);
+
+        let player = media.player();
+        assert!(player.ended && player.paused);
+        assert_eq!(player.current_time, 60.0);
+    }
+
+    /// Advance the paused clock, and let the ping task handle any tick
+    async fn advance(duration: Duration) {
+        tokio::time::advance(duration).await;
+        tokio::task::yield_now().await;
+    }
+
+    #[tokio::test]
+    async fn test_pings() {
+        let server = CaptureServer::start(200).await;
+        let mut media = MediaTracking::new(Arc::new(server.tracker()), MediaPlayer::new())
+            .with_ping_interval(Duration::from_millis(1500));
+
+        media
+            .ad_break_start(MediaAdBreak::new("break-1", 0.0))
+            .await
+            .unwrap();
+
+        // No pings while paused
+        tokio::time::pause();
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(server.bodies().len(), 1);
+
+        // The clock would auto-advance while waiting for the collector, so
+        // it runs in real time while the play event is sent
+        tokio::time::resume();
+        media.play().await.unwrap();
+        tokio::time::pause();
+
+        // The interval is rounded up to two seconds, so the next ping is due
+        // two seconds after the tick that was skipped while paused. Timers
+        // have millisecond precision, so the clock is advanced a little past
+        // each tick.
+        advance(Duration::from_millis(1700)).await;
+        assert_eq!(server.bodies().len(), 2);
+        advance(Duration::from_millis(800)).await;
+        server.wait_for_requests(3).await;
+        advance(Duration::from_millis(2500)).await;
+        server.wait_for_requests(4).await;
+        media.stop().await;
+
+        let events = server.events();
+        let pings: Vec<_> = events
+            .iter()
+            .filter(|event| event.name() == Some("ping_event"))
+            .collect();
+        assert_eq!(pings.len(), 2);
+        assert_eq!(pings[0].contexts[1]["data"]["pingInterval"], 2);
+        assert_eq!(pings[0].contexts[2]["data"]["breakId"], "break-1");
+
+        // No more pings once they're stopped, however long playback carries
+        // on
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(server.bodies().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_failed_ad_end_clears_ad() {
+        let server = CaptureServer::start(400).await;
+        let media = MediaTracking::new(Arc::new(server.tracker()), MediaPlayer::new());
+
+        // The collector rejects every event, but the ad and break still end
+        let _ = media
+            .ad_break_start(MediaAdBreak::new("break-1", 0.0))
+            .await;
+        let _ = media.ad_start(MediaAd::new("ad-1")).await;
+        media
+            .ad_skip()
+            .await
+            .expect_err("the collector is rejecting events");
+        media
+            .ad_break_end()
+            .await
+            .expect_err("the collector is rejecting events");
+        let _ = media.ping().await;
+
+        let events = server.events();
+        let names: Vec<_> = events.iter().map(|event| event.name().unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                "ad_break_start_event",
+                "ad_start_event",
+                "ad_skip_event",
+                "ad_break_end_event",
+                "ping_event"
+            ]
+        );
+
+        // The events that end the ad and the break still carry them
+        assert_eq!(events[2].contexts.len(), 4);
+        assert_eq!(events[3].contexts.len(), 3);
+        assert_eq!(events[3].contexts[2]["data"]["breakId"], "break-1");
+        assert_eq!(events[4].contexts.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_ping_interval_rounding() {
+        for (interval, seconds) in [
+            (Duration::ZERO, 1),
+            (Duration::from_millis(20), 1),
+            (Duration::from_secs(30), 30),
+            (Duration::from_millis(30_001), 31),
+        ] {
+            let tracker = Tracker::builder()
+                .collector("http://localhost:9090")
+                .app_id("app")
+                .build()
+                .unwrap();
+            let media = MediaTracking::new(Arc::new(tracker), MediaPlayer::new())
+                .with_ping_interval(interval);
+
+            assert_eq!(
+                lock(&media.state).session.ping_interval,
+                Some(seconds),
+                "{interval:?}"
+            );
+        }
+    }
+}
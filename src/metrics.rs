@@ -0,0 +1,422 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Delivery metrics for an [`Emitter`][crate::emitter::Emitter]. Every emitter
+keeps a set of counters, which can be read at any time as a
+[`MetricsSnapshot`] with [`Emitter::metrics`][crate::emitter::Emitter::metrics].
+
+With the `metrics` feature, the same measurements are also reported through
+the [`metrics`](https://docs.rs/metrics) facade, so they can be exported with
+any `metrics` recorder (Prometheus, StatsD, etc). The names reported are:
+
+| Name                                 | Kind      | Unit    |
+|--------------------------------------|-----------|---------|
+| `snowplow_events_enqueued_total`     | counter   | events  |
+| `snowplow_events_sent_total`         | counter   | events  |
+| `snowplow_events_failed_total`       | counter   | events  |
+| `snowplow_events_dropped_total`      | counter   | events  |
+| `snowplow_request_retries_total`     | counter   | retries |
+| `snowplow_circuit_opened_total`      | counter   | times   |
+| `snowplow_endpoint_unhealthy_total`  | counter   | times   |
+| `snowplow_queue_depth`               | gauge     | events  |
+| `snowplow_request_latency_seconds`   | histogram | seconds |
+| `snowplow_payload_bytes`             | histogram | bytes   |
+
+`snowplow_endpoint_unhealthy_total` is labeled with the `endpoint` that was
+marked unhealthy.
+*/
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use reqwest::Url;
+
+/// A point-in-time copy of an emitter's metrics. Counters are cumulative,
+/// from when the emitter was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// The number of events given to the emitter to send
+    pub events_enqueued: u64,
+
+    /// The number of events the collector accepted
+    pub events_sent: u64,
+
+    /// The number of events that couldn't be delivered, after any retries
+    pub events_failed: u64,
+
+    /// The number of events that were dropped by a tracker before reaching
+    /// the emitter; for instance, by a sampler, a plugin, or because there
+    /// was no consent to track them
+    pub events_dropped: u64,
+
+    /// The number of requests that were retried after failing
+    pub retries: u64,
+
+    /// The number of times the emitter's
+    /// [circuit breaker][crate::circuit_breaker] opened
+    pub circuit_opened: u64,
+
+    /// The number of times one of the emitter's collector endpoints was
+    /// marked unhealthy, with
+    /// [failover][crate::emitter::Emitter::with_failover]
+    pub endpoints_unhealthy: u64,
+
+    /// The number of events that have been enqueued, but that haven't been
+    /// sent or failed yet (or abandoned, because the send was cancelled)
+    pub queue_depth: u64,
+
+    /// The number of requests made to the collector, including retries
+    pub requests: u64,
+
+    /// The total time spent on requests to the collector
+    pub request_latency_total: Duration,
+
+    /// The longest time spent on a single request to the collector
+    pub request_latency_max: Duration,
+
+    /// The total size of the request bodies sent to the collector, after any
+    /// compression
+    pub payload_bytes: u64,
+}
+
+impl MetricsSnapshot {
+    /// The average time spent on a request to the collector, or `None` if
+    /// no requests have been made
+    pub fn mean_request_latency(&self) -> Option<Duration> {
+        let requests = u32::try_from(self.requests).ok().filter(|&n| n > 0)?;
+        Some(self.request_latency_total / requests)
+    }
+}
+
+fn duration_micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+/// The live counters behind a [`MetricsSnapshot`]. These are updated by the
+/// emitter (and, for dropped events, by the tracker) as events move through
+/// it.
+#[derive(Debug, Default)]
+pub(crate) struct EmitterMetrics {
+    events_enqueued: AtomicU64,
+    events_sent: AtomicU64,
+    events_failed: AtomicU64,
+    events_dropped: AtomicU64,
+    retries: AtomicU64,
+    circuit_opened: AtomicU64,
+    endpoints_unhealthy: AtomicU64,
+    queue_depth: AtomicU64,
+    requests: AtomicU64,
+    request_latency_total_us: AtomicU64,
+    request_latency_max_us: AtomicU64,
+    payload_bytes: AtomicU64,
+}
+
+impl EmitterMetrics {
+    pub(crate) const fn new() -> Self {
+        Self {
+            events_enqueued: AtomicU64::new(0),
+            events_sent: AtomicU64::new(0),
+            events_failed: AtomicU64::new(0),
+            events_dropped: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            circuit_opened: AtomicU64::new(0),
+            endpoints_unhealthy: AtomicU64::new(0),
+            queue_depth: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            request_latency_total_us: AtomicU64::new(0),
+            request_latency_max_us: AtomicU64::new(0),
+            payload_bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// Some events were given to the emitter to send. They count towards the
+    /// queue depth until they're [finished][Queued::finished], or until the
+    /// returned guard is dropped (for instance, because the future sending
+    /// them was cancelled).
+    pub(crate) fn enqueued(&self, count: usize) -> Queued<'_> {
+        let count = count as u64;
+        self.events_enqueued.fetch_add(count, Ordering::Relaxed);
+        self.queue_depth.fetch_add(count, Ordering::Relaxed);
+
+        #[cfg(feature = "metrics")]
+        {
+            ::metrics::counter!("snowplow_events_enqueued_total").increment(count);
+            ::metrics::gauge!("snowplow_queue_depth").increment(count as f64);
+        }
+
+        Queued {
+            metrics: self,
+            remaining: count,
+        }
+    }
+
+    fn dequeued(&self, count: u64) {
+        self.queue_depth.fetch_sub(count, Ordering::Relaxed);
+
+        #[cfg(feature = "metrics")]
+        ::metrics::gauge!("snowplow_queue_depth").decrement(count as f64);
+    }
+
+    /// Some events were dropped before reaching the emitter
+    pub(crate) fn dropped(&self, count: usize) {
+        let count = count as u64;
+        self.events_dropped.fetch_add(count, Ordering::Relaxed);
+
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("snowplow_events_dropped_total").increment(count);
+    }
+
+    /// A failed request is about to be retried
+    pub(crate) fn retried(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("snowplow_request_retries_total").increment(1);
+    }
+
+    /// The circuit breaker opened
+    pub(crate) fn circuit_opened(&self) {
+        self.circuit_opened.fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("snowplow_circuit_opened_total").increment(1);
+    }
+
+    /// A collector endpoint was marked unhealthy
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn endpoint_unhealthy(&self, endpoint: &Url) {
+        self.endpoints_unhealthy.fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("snowplow_endpoint_unhealthy_total", "endpoint" => endpoint.to_string())
+            .increment(1);
+    }
+
+    /// A request to the collector finished, successfully or not
+    pub(crate) fn request(&self, latency: Duration, bytes: usize) {
+        let latency_us = duration_micros(latency);
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.request_latency_total_us
+            .fetch_add(latency_us, Ordering::Relaxed);
+        self.request_latency_max_us
+            .fetch_max(latency_us, Ordering::Relaxed);
+        self.payload_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+
+        #[cfg(feature = "metrics")]
+        {
+            ::metrics::histogram!("snowplow_request_latency_seconds").record(latency.as_secs_f64());
+            ::metrics::histogram!("snowplow_payload_bytes").record(bytes as f64);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        MetricsSnapshot {
+            events_enqueued: load(&self.events_enqueued),
+            events_sent: load(&self.events_sent),
+            events_failed: load(&self.events_failed),
+            events_dropped: load(&self.events_dropped),
+            retries: load(&self.retries),
+            circuit_opened: load(&self.circuit_opened),
+            endpoints_unhealthy: load(&self.endpoints_unhealthy),
+            queue_depth: load(&self.queue_depth),
+            requests: load(&self.requests),
+            request_latency_total: Duration::from_micros(load(&self.request_latency_total_us)),
+            request_latency_max: Duration::from_micros(load(&self.request_latency_max_us)),
+            payload_bytes: load(&self.payload_bytes),
+        }
+    }
+}
+
+/// Events that count towards an emitter's queue depth, from when they're
+/// [enqueued][EmitterMetrics::enqueued] until they're finished. Whatever is
+/// left unfinished when this is dropped stops counting, without being counted
+/// as either sent or failed.
+#[derive(Debug)]
+#[must_use]
+pub(crate) struct Queued<'a> {
+    metrics: &'a EmitterMetrics,
+    remaining: u64,
+}
+
+impl Queued<'_> {
+    /// The emitter is done with a batch of these events, which were either
+    /// sent or failed
+    pub(crate) fn finished(&mut self, count: usize, delivered: bool) {
+        let count = (count as u64).min(self.remaining);
+        self.remaining -= count;
+
+        let counter = if delivered {
+            &self.metrics.events_sent
+        } else {
+            &self.metrics.events_failed
+        };
+        counter.fetch_add(count, Ordering::Relaxed);
+        self.metrics.dequeued(count);
+
+        #[cfg(feature = "metrics")]
+        {
+            let name = if delivered {
+                "snowplow_events_sent_total"
+            } else {
+                "snowplow_events_failed_total"
+            };
+            ::metrics::counter!(name).increment(count);
+        }
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        if self.remaining > 0 {
+            self.metrics.dequeued(self.remaining);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde::Serialize;
+    use tokio::net::TcpListener;
+
+    use super::MetricsSnapshot;
+    use crate::emitter::RetryPolicy;
+    use crate::testing::CaptureServer;
+    use crate::{ConsentLevel, HasSchema, Schema, SchemaVersion, TrackedEvent, Tracker};
+
+    #[derive(Serialize)]
+    struct Click {
+        target: &'static str,
+    }
+
+    impl HasSchema for Click {
+        fn schema(&self) -> Schema {
+            Schema::new("com.example", "click", SchemaVersion::new(1, 0, 0))
+        }
+    }
+
+    fn retry_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        }
+    }
+
+    fn clicks(count: usize) -> impl Iterator<Item = TrackedEvent<Click>> {
+        (0..count).map(|_| TrackedEvent::new(Click { target: "button" }))
+    }
+
+    #[tokio::test]
+    async fn test_delivered_metrics() {
+        let server = CaptureServer::start(200).await;
+        let tracker = server
+            .tracker_builder()
+            .max_batch_size(2)
+            .retry_policy(retry_policy(3))
+            .build()
+            .unwrap();
+
+        tracker.track_batch(clicks(3)).await.unwrap();
+
+        let metrics = tracker.emitter().metrics();
+        let body_bytes: usize = server
+            .requests()
+            .iter()
+            .map(|request| request.raw_body.len())
+            .sum();
+
+        assert_eq!(
+            metrics,
+            MetricsSnapshot {
+                events_enqueued: 3,
+                events_sent: 3,
+                requests: 2,
+                payload_bytes: body_bytes as u64,
+                request_latency_total: metrics.request_latency_total,
+                request_latency_max: metrics.request_latency_max,
+                ..MetricsSnapshot::default()
+            }
+        );
+        assert!(metrics.request_latency_max <= metrics.request_latency_total);
+        assert!(metrics.mean_request_latency().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_failed_metrics() {
+        let server = CaptureServer::start(503).await;
+        let tracker = server
+            .tracker_builder()
+            .max_batch_size(2)
+            .retry_policy(retry_policy(2))
+            .build()
+            .unwrap();
+
+        tracker.set_consent(ConsentLevel::None);
+        tracker.track_batch(clicks(2)).await.unwrap();
+        assert_eq!(tracker.emitter().metrics().events_dropped, 2);
+        assert_eq!(tracker.emitter().metrics().requests, 0);
+
+        tracker.set_consent(ConsentLevel::Full);
+        tracker.track_batch(clicks(3)).await.unwrap_err();
+
+        let metrics = tracker.emitter().metrics();
+        assert_eq!(metrics.events_enqueued, 3);
+        assert_eq!(metrics.events_sent, 0);
+        assert_eq!(metrics.events_failed, 3);
+        assert_eq!(metrics.retries, 2);
+        assert_eq!(metrics.requests, 4);
+        assert_eq!(metrics.queue_depth, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_metrics() {
+        // A collector that accepts connections, but never responds
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let collector = format!("http://{}", listener.local_addr().unwrap());
+        let accepting = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            loop {
+                let (connection, _) = listener.accept().await.unwrap();
+                connections.push(connection);
+            }
+        });
+
+        let tracker = Tracker::builder()
+            .collector(collector)
+            .app_id("app")
+            .max_batch_size(2)
+            .build()
+            .unwrap();
+
+        let mut send = Box::pin(tracker.track_batch(clicks(3)));
+        tokio::select! {
+            _ = &mut send => panic!("the collector never responds"),
+            () = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+        assert_eq!(tracker.emitter().metrics().queue_depth, 3);
+
+        // Cancelling the send takes its events out of the queue, without
+        // counting them as sent or failed
+        drop(send);
+        let metrics = tracker.emitter().metrics();
+        assert_eq!(metrics.events_enqueued, 3);
+        assert_eq!(metrics.queue_depth, 0);
+        assert_eq!(metrics.events_sent + metrics.events_failed, 0);
+
+        accepting.abort();
+    }
+}
@@ -0,0 +1,659 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+A mock Snowplow collector for local testing, modeled on
+[Snowplow Micro](https://docs.snowplow.io/docs/testing-debugging/snowplow-micro/).
+Requires the `micro` feature.
+
+[`Micro`] listens on localhost and accepts events on the usual collector
+endpoints (`POST /com.snowplowanalytics.snowplow/tp2` and `GET /i`); POST
+bodies may be gzip-compressed, with `Content-Encoding: gzip`. Each event is
+parsed and validated, then stored as either a [`GoodEvent`] or a
+[`BadEvent`]. The stored events can be inspected directly, or over HTTP with
+the same endpoints as Snowplow Micro:
+
+- `GET /micro/all`: a [`Summary`] of the number of good and bad events
+- `GET /micro/good`: all of the good events
+- `GET /micro/bad`: all of the bad events
+- `GET /micro/reset`: delete all stored events
+
+The `snowplow-micro-rs` binary runs a standalone instance.
+
+```no_run
+use snowplow_tracker::micro::Micro;
+use snowplow_tracker::{Platform, Tracker};
+
+# async fn example() {
+let micro = Micro::start(([127, 0, 0, 1], 0).into()).expect("failed to start micro");
+let tracker = Tracker::build(
+    "ns",
+    "app".to_owned(),
+    Platform::Desktop,
+    micro.collector_url(),
+    reqwest::Client::new(),
+);
+// ... track some events ...
+assert_eq!(micro.summary().bad, 0);
+# }
+```
+*/
+
+use std::collections::BTreeMap;
+use std::io::{self, Read as _};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use axum::body::Bytes;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use flate2::read::GzDecoder;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue};
+use tokio::sync::oneshot;
+
+use crate::payload::{
+    Contexts, Envelope, PayloadWrapper, Schema, SelfDescribingJson, UnstructWrapper,
+};
+use crate::util::{decode_base64_json, JsonString};
+
+pub use crate::emitter::TP2_PATH;
+
+/// A 1x1 transparent GIF, returned from `GET /i`
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x01, 0x44, 0x00, 0x3b,
+];
+
+/// A successfully parsed event, with the most commonly used fields pulled
+/// out of the raw parameters.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MicroEvent {
+    /// The event ID (`eid`)
+    pub event_id: Option<String>,
+
+    /// The application ID (`aid`)
+    pub app_id: Option<String>,
+
+    /// The platform (`p`)
+    pub platform: String,
+
+    /// The tracker namespace (`tna`)
+    pub namespace: Option<String>,
+
+    /// The tracker version (`tv`)
+    pub tracker_version: String,
+
+    /// The self-describing event payload, for `ue` events (`ue_pr`/`ue_px`)
+    pub unstruct_event: Option<Envelope<SelfDescribingJson>>,
+
+    /// The context entities attached to the event (`co`/`cx`)
+    pub contexts: Vec<Envelope<SelfDescribingJson>>,
+}
+
+/// An event that was received and successfully parsed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoodEvent {
+    /// The event type (`e`); for instance, `ue` for self-describing events
+    pub event_type: String,
+
+    /// The schema of the self-describing event, if this is one
+    pub schema: Option<Schema>,
+
+    /// The schemas of the context entities attached to the event
+    pub contexts: Vec<Schema>,
+
+    /// The parsed event
+    pub event: MicroEvent,
+
+    /// The raw tracker protocol parameters of the event
+    pub raw_event: BTreeMap<String, String>,
+}
+
+/// An event that was received, but failed to parse or validate
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BadEvent {
+    /// The raw event, as it was received. For requests that couldn't be
+    /// parsed at all, this is the whole request body as a string.
+    pub raw_event: JsonValue,
+
+    /// Everything that was wrong with the event
+    pub errors: Vec<String>,
+}
+
+/// The number of events received by a [`Micro`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Summary {
+    /// The total number of events
+    pub total: usize,
+
+    /// The number of good events
+    pub good: usize,
+
+    /// The number of bad events
+    pub bad: usize,
+}
+
+#[derive(Debug, Default)]
+struct MicroState {
+    good: Vec<GoodEvent>,
+    bad: Vec<BadEvent>,
+}
+
+type SharedState = Arc<Mutex<MicroState>>;
+
+fn lock(state: &SharedState) -> std::sync::MutexGuard<'_, MicroState> {
+    state.lock().unwrap_or_else(|poison| poison.into_inner())
+}
+
+/// A running mock collector. The server is shut down when this is dropped.
+#[derive(Debug)]
+pub struct Micro {
+    address: SocketAddr,
+    state: SharedState,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl Micro {
+    /// Start a mock collector listening on the given address. Use port 0 to
+    /// pick any free port; [`url`][Self::url] reports the actual address.
+    ///
+    /// This must be called from within a tokio runtime, which the server
+    /// will run on.
+    pub fn start(address: SocketAddr) -> io::Result<Self> {
+        let listener = std::net::TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let state = SharedState::default();
+        let (shutdown, shutdown_signal) = oneshot::channel();
+
+        let router = Router::new()
+            .route(TP2_PATH, post(receive_post))
+            .route("/i", get(receive_get))
+            .route("/micro/all", get(all))
+            .route("/micro/good", get(good))
+            .route("/micro/bad", get(bad))
+            .route("/micro/reset", get(reset).post(reset))
+            .with_state(state.clone());
+
+        let server = axum::Server::from_tcp(listener)
+            .map_err(io::Error::other)?
+            .serve(router.into_make_service())
+            .with_graceful_shutdown(async {
+                let _ = shutdown_signal.await;
+            });
+
+        tokio::spawn(server);
+
+        Ok(Self {
+            address,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// The base URL of this collector, like `http://127.0.0.1:9090/`
+    pub fn url(&self) -> Url {
+        format!("http://{}/", self.address)
+            .parse()
+            .expect("a socket address is always a valid host")
+    }
+
+    /// The full URL that trackers should POST events to
+    pub fn collector_url(&self) -> Url {
+        self.url().join(TP2_PATH).expect("TP2_PATH is a valid path")
+    }
+
+    /// The number of events received so far
+    pub fn summary(&self) -> Summary {
+        let state = lock(&self.state);
+
+        Summary {
+            total: state.good.len() + state.bad.len(),
+            good: state.good.len(),
+            bad: state.bad.len(),
+        }
+    }
+
+    /// All of the good events received so far
+    pub fn good(&self) -> Vec<GoodEvent> {
+        lock(&self.state).good.clone()
+    }
+
+    /// All of the bad events received so far
+    pub fn bad(&self) -> Vec<BadEvent> {
+        lock(&self.state).bad.clone()
+    }
+
+    /// Delete all of the stored events
+    pub fn reset(&self) {
+        *lock(&self.state) = MicroState::default();
+    }
+}
+
+impl Drop for Micro {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Store the result of parsing a single event
+fn store(state: &SharedState, raw_event: JsonValue) {
+    let mut state = lock(state);
+
+    match parse_event(&raw_event) {
+        Ok(event) => state.good.push(event),
+        Err(errors) => state.bad.push(BadEvent { raw_event, errors }),
+    }
+}
+
+/// Decode a POST body, decompressing it if it's gzipped
+fn decode_body(headers: &HeaderMap, body: &[u8]) -> io::Result<String> {
+    let gzip = headers
+        .get(header::CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding.as_bytes().eq_ignore_ascii_case(b"gzip"));
+
+    if gzip {
+        let mut decoded = String::new();
+        GzDecoder::new(body).read_to_string(&mut decoded)?;
+        Ok(decoded)
+    } else {
+        String::from_utf8(body.to_vec()).map_err(io::Error::other)
+    }
+}
+
+async fn receive_post(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let body = match decode_body(&headers, &body) {
+        Ok(body) => body,
+        Err(err) => {
+            lock(&state).bad.push(BadEvent {
+                raw_event: JsonValue::String(String::from_utf8_lossy(&body).into_owned()),
+                errors: vec![format!("request body could not be decoded: {err}")],
+            });
+            return StatusCode::OK;
+        }
+    };
+
+    let payload: JsonValue = match serde_json::from_str(&body) {
+        Ok(payload) => payload,
+        Err(err) => {
+            lock(&state).bad.push(BadEvent {
+                raw_event: JsonValue::String(body),
+                errors: vec![format!("request body is not valid JSON: {err}")],
+            });
+            return StatusCode::OK;
+        }
+    };
+
+    let schema = payload.get("schema").and_then(JsonValue::as_str);
+    match (schema, payload.get("data")) {
+        (Some(schema), Some(JsonValue::Array(events))) if is_payload_data(schema) => {
+            for event in events {
+                store(&state, event.clone());
+            }
+        }
+        _ => lock(&state).bad.push(BadEvent {
+            raw_event: payload,
+            errors: vec!["request body is not a payload_data envelope".to_owned()],
+        }),
+    }
+
+    StatusCode::OK
+}
+
+async fn receive_get(
+    State(state): State<SharedState>,
+    Query(parameters): Query<BTreeMap<String, String>>,
+) -> impl IntoResponse {
+    let raw_event = parameters
+        .into_iter()
+        .map(|(key, value)| (key, JsonValue::String(value)))
+        .collect::<Map<_, _>>();
+
+    store(&state, JsonValue::Object(raw_event));
+
+    ([(header::CONTENT_TYPE, "image/gif")], PIXEL)
+}
+
+async fn all(State(state): State<SharedState>) -> Json<Summary> {
+    let state = lock(&state);
+
+    Json(Summary {
+        total: state.good.len() + state.bad.len(),
+        good: state.good.len(),
+        bad: state.bad.len(),
+    })
+}
+
+async fn good(State(state): State<SharedState>) -> Json<Vec<GoodEvent>> {
+    Json(lock(&state).good.clone())
+}
+
+async fn bad(State(state): State<SharedState>) -> Json<Vec<BadEvent>> {
+    Json(lock(&state).bad.clone())
+}
+
+async fn reset(State(state): State<SharedState>) -> Json<Summary> {
+    *lock(&state) = MicroState::default();
+    Json(Summary::default())
+}
+
+fn is_payload_data(schema: &str) -> bool {
+    schema.starts_with("iglu:com.snowplowanalytics.snowplow/payload_data/jsonschema/1-")
+}
+
+/// Decode a JSON field that may be sent either plain (`plain`) or base64
+/// encoded (`encoded`)
+fn decode_json_field<T: DeserializeOwned>(
+    parameters: &BTreeMap<String, String>,
+    plain: &str,
+    encoded: &str,
+) -> Result<Option<T>, String> {
+    if let Some(json) = parameters.get(plain) {
+        return serde_json::from_str(json)
+            .map(Some)
+            .map_err(|err| format!("{plain} is invalid: {err}"));
+    }
+
+    if let Some(base64) = parameters.get(encoded) {
+        return decode_base64_json(base64)
+            .map(|JsonString(value)| Some(value))
+            .map_err(|err| format!("{encoded} is invalid: {err}"));
+    }
+
+    Ok(None)
+}
+
+/// Parse and validate a single event
+fn parse_event(raw_event: &JsonValue) -> Result<GoodEvent, Vec<String>> {
+    let Some(object) = raw_event.as_object() else {
+        return Err(vec!["event is not a JSON object".to_owned()]);
+    };
+
+    let mut errors = Vec::new();
+    let mut parameters = BTreeMap::new();
+
+    for (key, value) in object {
+        match value {
+            JsonValue::String(value) => {
+                parameters.insert(key.clone(), value.clone());
+            }
+            _ => errors.push(format!("{key} is not a string")),
+        }
+    }
+
+    for required in ["e", "p", "tv"] {
+        if !object.contains_key(required) {
+            errors.push(format!("{required} is missing"));
+        }
+    }
+
+    let unstruct_event = match decode_json_field::<PayloadWrapper<SelfDescribingJson>>(
+        &parameters,
+        "ue_pr",
+        "ue_px",
+    ) {
+        Ok(payload) => payload.map(|Envelope(UnstructWrapper(payload))| payload),
+        Err(err) => {
+            errors.push(err);
+            None
+        }
+    };
+
+    if parameters.get("e").map(String::as_str) == Some("ue") && unstruct_event.is_none() {
+        errors.push("self-describing event has no valid payload".to_owned());
+    }
+
+    let contexts = match decode_json_field::<Envelope<Contexts>>(&parameters, "co", "cx") {
+        Ok(contexts) => contexts
+            .map(|Envelope(Contexts(contexts))| contexts)
+            .unwrap_or_default(),
+        Err(err) => {
+            errors.push(err);
+            Vec::new()
+        }
+    };
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let event = MicroEvent {
+        event_id: parameters.get("eid").cloned(),
+        app_id: parameters.get("aid").cloned(),
+        platform: parameters["p"].clone(),
+        namespace: parameters.get("tna").cloned(),
+        tracker_version: parameters["tv"].clone(),
+        unstruct_event,
+        contexts,
+    };
+
+    Ok(GoodEvent {
+        event_type: parameters["e"].clone(),
+        schema: event
+            .unstruct_event
+            .as_ref()
+            .map(|Envelope(event)| event.schema.clone()),
+        contexts: event
+            .contexts
+            .iter()
+            .map(|Envelope(context)| context.schema.clone())
+            .collect(),
+        event,
+        raw_event: parameters,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+
+    use super::{Micro, Summary, TP2_PATH};
+    use crate::emitter::{Compression, Emitter};
+    use crate::{
+        Platform, Schema, SchemaVersion, SelfDescribingJson, TrackedEvent, Tracker, TrackerConfig,
+    };
+
+    fn start() -> Micro {
+        Micro::start(([127, 0, 0, 1], 0).into()).expect("failed to start micro")
+    }
+
+    #[tokio::test]
+    async fn test_tracked_events_are_good() {
+        let micro = start();
+        let tracker = Tracker::build(
+            "ns",
+            "app".to_owned(),
+            Platform::Desktop,
+            micro.collector_url(),
+            reqwest::Client::new(),
+        );
+
+        let event = TrackedEvent::new(SelfDescribingJson::new(
+            Schema::new("com.example", "test", SchemaVersion::new(1, 0, 0)),
+            json!({"value": 1}),
+        ))
+        .with_context(SelfDescribingJson::new(
+            Schema::new("com.example", "context", SchemaVersion::new(1, 0, 0)),
+            json!({"context": true}),
+        ));
+
+        tracker.track(event).await.expect("failed to track event");
+
+        assert_eq!(
+            micro.summary(),
+            Summary {
+                total: 1,
+                good: 1,
+                bad: 0
+            }
+        );
+
+        let good = micro.good();
+        assert_eq!(good[0].event_type, "ue");
+        assert_eq!(
+            good[0].schema,
+            Some(Schema::new(
+                "com.example",
+                "test",
+                SchemaVersion::new(1, 0, 0)
+            ))
+        );
+        assert_eq!(
+            good[0].contexts,
+            [Schema::new(
+                "com.example",
+                "context",
+                SchemaVersion::new(1, 0, 0)
+            )]
+        );
+        assert_eq!(good[0].event.app_id.as_deref(), Some("app"));
+        assert_eq!(
+            good[0].event.unstruct_event.as_ref().unwrap().0.data,
+            json!({"value": 1})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gzipped_events() {
+        let micro = start();
+        let tracker = Tracker::new(
+            Emitter::new(micro.collector_url(), reqwest::Client::new())
+                .with_compression(Compression::Gzip),
+            TrackerConfig {
+                namespace: "ns".into(),
+                platform: Platform::Desktop,
+                app_id: "app".to_owned(),
+            },
+        );
+
+        let event = |value| {
+            TrackedEvent::new(SelfDescribingJson::new(
+                Schema::new("com.example", "test", SchemaVersion::new(1, 0, 0)),
+                json!({ "value": value }),
+            ))
+        };
+        tracker
+            .track_batch([event(1), event(2)])
+            .await
+            .expect("failed to track events");
+
+        let values: Vec<_> = micro
+            .good()
+            .into_iter()
+            .map(|event| event.event.unstruct_event.unwrap().0.data["value"].clone())
+            .collect();
+        assert_eq!(values, [1, 2]);
+
+        // A body that claims to be gzipped, but isn't
+        reqwest::Client::new()
+            .post(micro.collector_url())
+            .header("content-encoding", "gzip")
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+
+        let bad = micro.bad();
+        assert_eq!(bad.len(), 1);
+        assert!(
+            bad[0].errors[0].starts_with("request body could not be decoded"),
+            "{:?}",
+            bad[0].errors
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_endpoints() {
+        let micro = start();
+        let client = reqwest::Client::new();
+
+        // A GET event with a base64 payload
+        let pixel = client
+            .get(micro.url().join("/i").unwrap())
+            .query(&[
+                ("e", "ue"),
+                ("p", "web"),
+                ("tv", "js-3.0.0"),
+                // {"schema":"iglu:com.snowplowanalytics.snowplow/unstruct_event/jsonschema/1-0-0",
+                //  "data":{"schema":"iglu:com.example/test/jsonschema/1-0-0","data":{}}}
+                ("ue_px", "eyJzY2hlbWEiOiJpZ2x1OmNvbS5zbm93cGxvd2FuYWx5dGljcy5zbm93cGxvdy91bnN0cnVjdF9ldmVudC9qc29uc2NoZW1hLzEtMC0wIiwiZGF0YSI6eyJzY2hlbWEiOiJpZ2x1OmNvbS5leGFtcGxlL3Rlc3QvanNvbnNjaGVtYS8xLTAtMCIsImRhdGEiOnt9fX0"),
+            ])
+            .send()
+            .await
+            .unwrap();
+        assert!(pixel.status().is_success());
+
+        // A POST with one good and one bad event
+        client
+            .post(micro.url().join(TP2_PATH).unwrap())
+            .json(&json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/payload_data/jsonschema/1-0-4",
+                "data": [
+                    {"e": "pv", "p": "web", "tv": "js-3.0.0", "url": "http://example.com"},
+                    {"e": "ue", "p": "web", "tv": 3, "ue_pr": "{not json"},
+                ],
+            }))
+            .send()
+            .await
+            .unwrap();
+
+        let get = |path: &'static str| {
+            let client = client.clone();
+            let url = micro.url().join(path).unwrap();
+            async move {
+                client
+                    .get(url)
+                    .send()
+                    .await
+                    .unwrap()
+                    .json::<JsonValue>()
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert_eq!(
+            get("/micro/all").await,
+            json!({"total": 3, "good": 2, "bad": 1})
+        );
+
+        let good = get("/micro/good").await;
+        assert_eq!(good[0]["schema"], "iglu:com.example/test/jsonschema/1-0-0");
+        assert_eq!(good[1]["eventType"], "pv");
+        assert_eq!(good[1]["rawEvent"]["url"], "http://example.com");
+
+        let bad = get("/micro/bad").await;
+        assert_eq!(bad[0]["errors"].as_array().unwrap().len(), 3);
+
+        assert_eq!(
+            get("/micro/reset").await,
+            json!({"total": 0, "good": 0, "bad": 0})
+        );
+        assert_eq!(micro.summary(), Summary::default());
+    }
+}
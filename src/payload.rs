@@ -19,14 +19,21 @@ Generally you should focus on the types and traits that are part of the
 are only for very custom or very advanced use cases.
 */
 
+use std::borrow::Cow;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
-use std::time::SystemTime;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
+use serde::de::{self, DeserializeOwned};
 use serde::ser::SerializeStruct as _;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value as JsonValue;
+use thiserror::Error;
 
+use crate::subject::Resolution;
+use crate::util::decode_base64_json;
 use crate::util::JsonString;
 use crate::util::Stringify;
 
@@ -44,20 +51,30 @@ impl<T: ToString> Serialize for StringySerde<T> {
     }
 }
 
-/// The event type we're sending. Currently we only support "self-describing"
-/// events.
-#[derive(Default, Serialize, Clone, Copy, Debug)]
+/// The event type we're sending. Almost everything is a "self-describing"
+/// event; the legacy ecommerce transaction events are also supported.
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EventType {
     /// An unstructured event, described by a schema.
     #[default]
-    #[serde(rename(serialize = "ue"))]
+    #[serde(rename = "ue")]
     SelfDescribingEvent,
+
+    /// A legacy ecommerce transaction; see
+    /// [`Transaction`][crate::ecommerce::Transaction]
+    #[serde(rename = "tr")]
+    Transaction,
+
+    /// An item in a legacy ecommerce transaction; see
+    /// [`TransactionItem`][crate::ecommerce::TransactionItem]
+    #[serde(rename = "ti")]
+    TransactionItem,
 }
 
 /// The platform this tracker is being used on. This is generally fixed at
 /// compile time, but this library is broadly cross-platform, so it still needs
 /// to be provided during [`Tracker`][crate::Tracker] configuration.
-#[derive(Debug, Default, Serialize, Clone, Copy)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum Platform {
     /// Websites
     #[serde(rename = "web")]
@@ -93,6 +110,30 @@ pub enum Platform {
     Thing,
 }
 
+/// An error parsing a [`Platform`] from a string
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("Invalid platform {0:?}; expected one of web, mob, pc, srv, app, tv, cnsl or iot")]
+pub struct PlatformParseError(String);
+
+/// Parses the platform codes used in events, like `"pc"` or `"srv"`
+impl FromStr for Platform {
+    type Err = PlatformParseError;
+
+    fn from_str(platform: &str) -> Result<Self, Self::Err> {
+        match platform {
+            "web" => Ok(Platform::Web),
+            "mob" => Ok(Platform::Mobile),
+            "pc" => Ok(Platform::Desktop),
+            "srv" => Ok(Platform::ServerSide),
+            "app" => Ok(Platform::App),
+            "tv" => Ok(Platform::Tv),
+            "cnsl" => Ok(Platform::GameConsole),
+            "iot" => Ok(Platform::Thing),
+            _ => Err(PlatformParseError(platform.to_owned())),
+        }
+    }
+}
+
 /// A snowplow timestamp. Serializes as the number of seconds since the unix
 /// epoch.
 ///
@@ -125,12 +166,18 @@ impl Serialize for SnowplowTimestamp {
     where
         S: Serializer,
     {
-        // TODO: log a warning. Bring in tracing for general logging.
-        let timestamp_millis = self
-            .timestamp
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map(|duration| duration.as_millis())
-            .unwrap_or(0);
+        // Snowplow timestamps can't be negative, so times before the epoch
+        // are clamped to it
+        let timestamp_millis = match self.timestamp.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(duration) => duration.as_millis(),
+            Err(err) => {
+                tracing::warn!(
+                    before_epoch_ms = err.duration().as_millis() as u64,
+                    "timestamp is before the unix epoch; sending it as 0"
+                );
+                0
+            }
+        };
 
         let mut buffer = itoa::Buffer::new();
         let formatted = buffer.format(timestamp_millis);
@@ -139,6 +186,157 @@ impl Serialize for SnowplowTimestamp {
     }
 }
 
+impl<'de> Deserialize<'de> for SnowplowTimestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Stringify(timestamp_millis) = Stringify::<u64>::deserialize(deserializer)?;
+
+        Ok(Self {
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(timestamp_millis),
+        })
+    }
+}
+
+/// The type-specific part of a [`SnowplowEvent`]: its event type (`e`), and
+/// the fields that go with it.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "e")]
+pub enum EventData<'a, Payload: HasSchema> {
+    /// A self-describing event
+    #[serde(rename = "ue")]
+    SelfDescribing {
+        /// The self-describing event payload
+        #[serde(rename = "ue_pr")]
+        payload: JsonString<PayloadWrapper<Payload>>,
+    },
+
+    /// A legacy ecommerce transaction
+    #[serde(rename = "tr")]
+    Transaction(TransactionParams<'a>),
+
+    /// An item in a legacy ecommerce transaction
+    #[serde(rename = "ti")]
+    TransactionItem(TransactionItemParams<'a>),
+}
+
+impl<'a, Payload: HasSchema> EventData<'a, Payload> {
+    /// Create the data of a self-describing event with the given payload
+    pub fn self_describing(payload: Payload) -> Self {
+        EventData::SelfDescribing {
+            payload: JsonString(PayloadWrapper::new(payload)),
+        }
+    }
+
+    /// The event type
+    pub fn event_type(&self) -> EventType {
+        match self {
+            EventData::SelfDescribing { .. } => EventType::SelfDescribingEvent,
+            EventData::Transaction(_) => EventType::Transaction,
+            EventData::TransactionItem(_) => EventType::TransactionItem,
+        }
+    }
+
+    /// The payload of a self-describing event
+    pub fn payload(&self) -> Option<&Payload> {
+        match self {
+            EventData::SelfDescribing { payload } => Some(&payload.0 .0 .0 .0),
+            _ => None,
+        }
+    }
+
+    /// The payload of a self-describing event, mutably
+    pub fn payload_mut(&mut self) -> Option<&mut Payload> {
+        match self {
+            EventData::SelfDescribing { payload } => Some(&mut payload.0 .0 .0 .0),
+            _ => None,
+        }
+    }
+}
+
+/// The parameters of a legacy ecommerce transaction event
+#[derive(Serialize, Clone, Debug)]
+pub struct TransactionParams<'a> {
+    /// The order ID of the transaction
+    #[serde(rename = "tr_id")]
+    pub order_id: Cow<'a, str>,
+
+    /// The affiliation (store, or partner) of the transaction
+    #[serde(rename = "tr_af")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub affiliation: Option<Cow<'a, str>>,
+
+    /// The total value of the transaction
+    #[serde(rename = "tr_tt")]
+    pub total: Stringify<f64>,
+
+    /// The tax included in the transaction's total
+    #[serde(rename = "tr_tx")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tax: Option<Stringify<f64>>,
+
+    /// The shipping cost included in the transaction's total
+    #[serde(rename = "tr_sh")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping: Option<Stringify<f64>>,
+
+    /// The delivery city
+    #[serde(rename = "tr_ci")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<Cow<'a, str>>,
+
+    /// The delivery state or province
+    #[serde(rename = "tr_st")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<Cow<'a, str>>,
+
+    /// The delivery country
+    #[serde(rename = "tr_co")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<Cow<'a, str>>,
+
+    /// The currency of the transaction, as an ISO 4217 code
+    #[serde(rename = "tr_cu")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Cow<'a, str>>,
+}
+
+/// The parameters of a legacy ecommerce transaction item event
+#[derive(Serialize, Clone, Debug)]
+pub struct TransactionItemParams<'a> {
+    /// The order ID of the transaction the item belongs to
+    #[serde(rename = "ti_id")]
+    pub order_id: Cow<'a, str>,
+
+    /// The SKU of the item
+    #[serde(rename = "ti_sk")]
+    pub sku: Cow<'a, str>,
+
+    /// The product name of the item
+    #[serde(rename = "ti_nm")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<Cow<'a, str>>,
+
+    /// The product category of the item
+    #[serde(rename = "ti_ca")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<Cow<'a, str>>,
+
+    /// The unit price of the item
+    #[serde(rename = "ti_pr")]
+    pub price: Stringify<f64>,
+
+    /// The quantity of the item
+    #[serde(rename = "ti_qu")]
+    pub quantity: Stringify<i64>,
+
+    /// The currency of the item, as an ISO 4217 code
+    #[serde(rename = "ti_cu")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Cow<'a, str>>,
+}
+
 /// The data type for a Snowplow event. Generally you won't need to create
 /// `SnowplowEvent` objects directly; you should prefer instead to create
 /// [`TrackedEvent`][crate::tracker::TrackedEvent] objects. See the
@@ -146,14 +344,15 @@ impl Serialize for SnowplowTimestamp {
 #[derive(Serialize, Clone, Debug)]
 pub struct SnowplowEvent<'a, Payload: HasSchema> {
     // ----- PAYLOAD ------
-    // TODO: replace this with an enum that handles the variations
-    /// The event type; currently we only support self-describing events
-    #[serde(rename = "e")]
-    pub event_type: EventType,
+    /// The event type, and the fields that go with it, like the payload of a
+    /// self-describing event
+    #[serde(flatten)]
+    pub data: EventData<'a, Payload>,
 
-    /// The user
-    #[serde(rename = "ue_pr")]
-    pub payload: JsonString<PayloadWrapper<Payload>>,
+    /// Context entities attached to this event, if any
+    #[serde(rename = "co")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contexts: Option<JsonString<Envelope<Contexts>>>,
 
     // ------ APPLICATION PARAMETERS ------
     /// The platform that this tracker is being used on
@@ -162,18 +361,70 @@ pub struct SnowplowEvent<'a, Payload: HasSchema> {
 
     /// An identifier describing this app
     #[serde(rename = "aid")]
-    pub app_id: &'a str,
+    pub app_id: Cow<'a, str>,
 
     /// The name of the tracker. This should generally always be the name &
     /// version of this Rust crate
     #[serde(rename = "tv")]
-    pub tracker_id: &'static str,
+    pub tracker_id: Cow<'a, str>,
 
     /// An identifier describing this specific tracker in the context of the
     /// application. If your application is using multiple trackers, this field
     /// distinguishes between them.
     #[serde(rename = "tna")]
-    pub namespace: &'a str,
+    pub namespace: Cow<'a, str>,
+
+    // ----- USER PARAMETERS ------
+    // These are populated from the tracker's Subject
+    /// A business-defined identifier for the user
+    #[serde(rename = "uid")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Cow<'a, str>>,
+
+    /// The IP address of the user's device
+    #[serde(rename = "ip")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<Cow<'a, str>>,
+
+    /// The user agent of the user's device or application
+    #[serde(rename = "ua")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub useragent: Option<Cow<'a, str>>,
+
+    /// The user's timezone
+    #[serde(rename = "tz")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Cow<'a, str>>,
+
+    /// The user's language
+    #[serde(rename = "lang")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<Cow<'a, str>>,
+
+    /// The resolution of the user's screen
+    #[serde(rename = "res")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screen_resolution: Option<Stringify<Resolution>>,
+
+    /// A tracker-generated identifier for the user's device
+    #[serde(rename = "duid")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain_user_id: Option<Cow<'a, str>>,
+
+    /// The collector-generated identifier for the user
+    #[serde(rename = "tnuid")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_user_id: Option<Cow<'a, str>>,
+
+    /// An identifier for the user's current session
+    #[serde(rename = "sid")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<Cow<'a, str>>,
+
+    /// The index of the user's current session
+    #[serde(rename = "vid")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_index: Option<Stringify<u32>>,
 
     // ----- GENERIC EVENT META ------
     /// The ID for this event. If omitted, one will be generated by the
@@ -193,8 +444,136 @@ pub struct SnowplowEvent<'a, Payload: HasSchema> {
     pub sent_timestamp: SnowplowTimestamp,
 }
 
+/// The raw form of a [`SnowplowEvent`], used to deserialize it. This is
+/// separate so that the base64 variants of fields (`ue_px` and `cx`) can be
+/// handled.
+#[derive(Deserialize)]
+struct RawSnowplowEvent {
+    e: EventType,
+    ue_pr: Option<String>,
+    ue_px: Option<String>,
+    co: Option<String>,
+    cx: Option<String>,
+    tr_id: Option<String>,
+    tr_af: Option<String>,
+    tr_tt: Option<Stringify<f64>>,
+    tr_tx: Option<Stringify<f64>>,
+    tr_sh: Option<Stringify<f64>>,
+    tr_ci: Option<String>,
+    tr_st: Option<String>,
+    tr_co: Option<String>,
+    tr_cu: Option<String>,
+    ti_id: Option<String>,
+    ti_sk: Option<String>,
+    ti_nm: Option<String>,
+    ti_ca: Option<String>,
+    ti_pr: Option<Stringify<f64>>,
+    ti_qu: Option<Stringify<i64>>,
+    ti_cu: Option<String>,
+    p: Platform,
+    aid: String,
+    tv: String,
+    tna: String,
+    uid: Option<String>,
+    ip: Option<String>,
+    ua: Option<String>,
+    tz: Option<String>,
+    lang: Option<String>,
+    res: Option<Stringify<Resolution>>,
+    duid: Option<String>,
+    tnuid: Option<String>,
+    sid: Option<String>,
+    vid: Option<Stringify<u32>>,
+    eid: Option<uuid::Uuid>,
+    dtm: SnowplowTimestamp,
+    stm: SnowplowTimestamp,
+}
+
+/// Deserialize a field that might be sent as JSON (`plain`), or as base64
+/// encoded JSON (`encoded`).
+fn decode_json_field<T: DeserializeOwned, E: de::Error>(
+    plain: Option<String>,
+    encoded: Option<String>,
+    plain_name: &str,
+    encoded_name: &str,
+) -> Result<Option<JsonString<T>>, E> {
+    match (plain, encoded) {
+        (Some(plain), _) => serde_json::from_str(&plain)
+            .map(|value| Some(JsonString(value)))
+            .map_err(|err| E::custom(format_args!("invalid {plain_name}: {err}"))),
+        (None, Some(encoded)) => decode_base64_json(&encoded)
+            .map(Some)
+            .map_err(|err| E::custom(format_args!("invalid {encoded_name}: {err}"))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Events are deserialized from the tracker protocol form, including the
+/// base64 encoded `ue_px` and `cx` fields. Deserialized events own all of
+/// their data.
+impl<'de, 'a, Payload> Deserialize<'de> for SnowplowEvent<'a, Payload>
+where
+    Payload: HasSchema + FromEnvelope,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawSnowplowEvent::deserialize(deserializer)?;
+
+        let data = match raw.e {
+            EventType::SelfDescribingEvent => EventData::SelfDescribing {
+                payload: decode_json_field(raw.ue_pr, raw.ue_px, "ue_pr", "ue_px")?
+                    .ok_or_else(|| de::Error::missing_field("ue_pr"))?,
+            },
+            EventType::Transaction => EventData::Transaction(TransactionParams {
+                order_id: Cow::Owned(raw.tr_id.ok_or_else(|| de::Error::missing_field("tr_id"))?),
+                affiliation: raw.tr_af.map(Cow::Owned),
+                total: raw.tr_tt.ok_or_else(|| de::Error::missing_field("tr_tt"))?,
+                tax: raw.tr_tx,
+                shipping: raw.tr_sh,
+                city: raw.tr_ci.map(Cow::Owned),
+                state: raw.tr_st.map(Cow::Owned),
+                country: raw.tr_co.map(Cow::Owned),
+                currency: raw.tr_cu.map(Cow::Owned),
+            }),
+            EventType::TransactionItem => EventData::TransactionItem(TransactionItemParams {
+                order_id: Cow::Owned(raw.ti_id.ok_or_else(|| de::Error::missing_field("ti_id"))?),
+                sku: Cow::Owned(raw.ti_sk.ok_or_else(|| de::Error::missing_field("ti_sk"))?),
+                name: raw.ti_nm.map(Cow::Owned),
+                category: raw.ti_ca.map(Cow::Owned),
+                price: raw.ti_pr.ok_or_else(|| de::Error::missing_field("ti_pr"))?,
+                quantity: raw.ti_qu.ok_or_else(|| de::Error::missing_field("ti_qu"))?,
+                currency: raw.ti_cu.map(Cow::Owned),
+            }),
+        };
+
+        Ok(Self {
+            data,
+            contexts: decode_json_field(raw.co, raw.cx, "co", "cx")?,
+            platform: raw.p,
+            app_id: Cow::Owned(raw.aid),
+            tracker_id: Cow::Owned(raw.tv),
+            namespace: Cow::Owned(raw.tna),
+            user_id: raw.uid.map(Cow::Owned),
+            ip_address: raw.ip.map(Cow::Owned),
+            useragent: raw.ua.map(Cow::Owned),
+            timezone: raw.tz.map(Cow::Owned),
+            language: raw.lang.map(Cow::Owned),
+            screen_resolution: raw.res,
+            domain_user_id: raw.duid.map(Cow::Owned),
+            network_user_id: raw.tnuid.map(Cow::Owned),
+            session_id: raw.sid.map(Cow::Owned),
+            session_index: raw.vid,
+            event_id: raw.eid,
+            created_timestamp: raw.dtm,
+            sent_timestamp: raw.stm,
+        })
+    }
+}
+
 /// An Iglu Schema version. Renders as `{major}-{minor}-{patch}`
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[allow(missing_docs)]
 pub struct SchemaVersion {
     pub major: u32,
@@ -226,14 +605,42 @@ impl Display for SchemaVersion {
     }
 }
 
+/// An error parsing a [`Schema`] or [`SchemaVersion`] from a string
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("Invalid Iglu schema: {0:?}")]
+pub struct SchemaParseError(String);
+
+impl FromStr for SchemaVersion {
+    type Err = SchemaParseError;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let error = || SchemaParseError(version.to_owned());
+
+        let mut parts = version.split('-').map(|part| {
+            if part.bytes().all(|b| b.is_ascii_digit()) {
+                part.parse().map_err(|_| error())
+            } else {
+                Err(error())
+            }
+        });
+
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(major), Some(minor), Some(patch), None) => {
+                Ok(SchemaVersion::new(major?, minor?, patch?))
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
 /// An Iglu Schema. Renders as `iglu:{vendor}/{name}/jsonschema/{version}`
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Schema {
     /// Typically a reverse domain name, like "com.agilebits.desktop"
-    pub vendor: &'static str,
+    pub vendor: Cow<'static, str>,
 
     /// The name of this specific schema
-    pub name: &'static str,
+    pub name: Cow<'static, str>,
 
     /// The version of this specific schema
     pub version: SchemaVersion,
@@ -242,26 +649,31 @@ pub struct Schema {
 impl Schema {
     /// Build a new schema. This will resemble
     /// "`iglu:{vendor}/{name}/jsonschema/{version}`". Schemas tend to be fixed
-    /// to a particular type, so all the string components are `&'static str`
+    /// to a particular type, so the string components are usually
+    /// `&'static str`, but owned strings are accepted too (for instance, for
+    /// schemas that were parsed at runtime).
     // TODO: macro version of this constructor so that the entire schema
     // can been build-time concatenated as a single string. This would provide
     // an opportunity for build time verification of the various components.
     #[inline]
     #[must_use]
-    pub fn new(vendor: &'static str, name: &'static str, version: SchemaVersion) -> Self {
+    pub fn new(
+        vendor: impl Into<Cow<'static, str>>,
+        name: impl Into<Cow<'static, str>>,
+        version: SchemaVersion,
+    ) -> Self {
         Self {
-            vendor,
-            name,
+            vendor: vendor.into(),
+            name: name.into(),
             version,
         }
     }
 
-    /// Build a new schema where the vendor is `com.snowplowanalytics.snowplow`.
-    /// For now this is internal-only; if library consumers want to use a
-    /// Snowplow first-party schema, we still ask them to be explicit and
-    /// supply the vendor themselves, for consistency.
+    /// Build a new schema where the vendor is `com.snowplowanalytics.snowplow`,
+    /// for Snowplow first-party events and entities. Most of these are
+    /// available as typed payloads in [`events`][crate::events].
     #[inline]
-    pub(crate) fn new_snowplow(name: &'static str, version: SchemaVersion) -> Self {
+    pub fn new_snowplow(name: &'static str, version: SchemaVersion) -> Self {
         Self::new("com.snowplowanalytics.snowplow", name, version)
     }
 }
@@ -272,17 +684,65 @@ impl Display for Schema {
             vendor,
             name,
             version,
-        } = *self;
+        } = self;
 
         write!(f, "iglu:{vendor}/{name}/jsonschema/{version}")
     }
 }
 
+impl FromStr for Schema {
+    type Err = SchemaParseError;
+
+    fn from_str(schema: &str) -> Result<Self, Self::Err> {
+        let error = || SchemaParseError(schema.to_owned());
+
+        let path = schema.strip_prefix("iglu:").ok_or_else(error)?;
+        let mut parts = path.split('/');
+
+        match (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) {
+            (Some(vendor), Some(name), Some("jsonschema"), Some(version), None)
+                if !vendor.is_empty() && !name.is_empty() =>
+            {
+                Ok(Schema::new(
+                    vendor.to_owned(),
+                    name.to_owned(),
+                    version.parse().map_err(|_| error())?,
+                ))
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+impl Serialize for Schema {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Stringify(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schema {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Stringify::deserialize(deserializer).map(|Stringify(schema)| schema)
+    }
+}
+
 /// Catch-all type for the snowplow data envelope, which combines a snowplow
 /// schema ID with some kind of payload. The payload includes the schema via
 /// the [`HasSchema`] trait. The [`Envelope`] will serialize as an object
 /// resembling `{"schema": "SCHEMA", "data": data}`.
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct Envelope<T: HasSchema>(
     /// The custom data for the event.
     ///
@@ -321,6 +781,56 @@ impl<T: HasSchema + Serialize> Serialize for Envelope<T> {
     }
 }
 
+/**
+Trait for types that can be deserialized out of an [`Envelope`], given the
+envelope's schema and data.
+
+This is implemented for every [`HasSchema`] type that implements
+[`Deserialize`], in which case the envelope's schema must have the same
+vendor and name as the deserialized value's own schema (the version isn't
+checked). [`SelfDescribingJson`] implements it by keeping whatever schema it
+was given.
+*/
+pub trait FromEnvelope: Sized {
+    /// Build a value out of the schema and data of an [`Envelope`]
+    fn from_envelope(schema: Schema, data: JsonValue) -> Result<Self, serde_json::Error>;
+}
+
+impl<T: HasSchema + DeserializeOwned> FromEnvelope for T {
+    fn from_envelope(schema: Schema, data: JsonValue) -> Result<Self, serde_json::Error> {
+        let value: T = serde_json::from_value(data)?;
+        let expected = value.schema();
+
+        if expected.vendor == schema.vendor && expected.name == schema.name {
+            Ok(value)
+        } else {
+            Err(de::Error::custom(format_args!(
+                "expected data with schema {expected}, but got {schema}"
+            )))
+        }
+    }
+}
+
+/// The raw form of an [`Envelope`], used to deserialize it
+#[derive(Deserialize)]
+struct RawEnvelope {
+    schema: Schema,
+    data: JsonValue,
+}
+
+impl<'de, T: HasSchema + FromEnvelope> Deserialize<'de> for Envelope<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let RawEnvelope { schema, data } = RawEnvelope::deserialize(deserializer)?;
+
+        T::from_envelope(schema, data)
+            .map(Envelope)
+            .map_err(de::Error::custom)
+    }
+}
+
 /**
 Snowplow imposes a *lot* of nesting on the way that event playloads are sent.
 A typical event payload resembles:
@@ -350,7 +860,7 @@ pub type PayloadWrapper<Payload> = Envelope<UnstructWrapper<Envelope<Payload>>>;
 /// `"iglu:com.snowplowanalytics.snowplow/unstruct_event/jsonschema/1-0-0"`
 /// schema via [`HasSchema`]. Generally you won't need to deal with this type
 /// directly.
-#[derive(Debug, Clone, Default, Copy, Serialize)]
+#[derive(Debug, Clone, Default, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct UnstructWrapper<Payload>(pub Payload);
 
@@ -367,3 +877,267 @@ impl<Payload: HasSchema> PayloadWrapper<Payload> {
         Envelope(UnstructWrapper(Envelope(payload)))
     }
 }
+
+/// A self-describing JSON value: an arbitrary JSON payload paired with the
+/// [`Schema`] that describes it. This is the dynamically typed counterpart to
+/// a custom [`HasSchema`] type, and is how the [`Tracker`][crate::Tracker]
+/// represents events and context entities internally once they've been
+/// submitted.
+///
+/// Like any other [`HasSchema`] type, this serializes as just its data; wrap
+/// it in an [`Envelope`] to get the full `{"schema", "data"}` object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfDescribingJson {
+    /// The schema describing `data`
+    pub schema: Schema,
+
+    /// The data itself
+    pub data: JsonValue,
+}
+
+impl SelfDescribingJson {
+    /// Create a new self-describing JSON value from a schema and some data
+    pub fn new(schema: Schema, data: JsonValue) -> Self {
+        Self { schema, data }
+    }
+
+    /// Convert some typed payload into a `SelfDescribingJson` by serializing
+    /// it to a [`serde_json::Value`].
+    pub fn from_payload<T: HasSchema + Serialize>(payload: &T) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            schema: payload.schema(),
+            data: serde_json::to_value(payload)?,
+        })
+    }
+}
+
+impl HasSchema for SelfDescribingJson {
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+}
+
+impl FromEnvelope for SelfDescribingJson {
+    fn from_envelope(schema: Schema, data: JsonValue) -> Result<Self, serde_json::Error> {
+        Ok(Self { schema, data })
+    }
+}
+
+impl Serialize for SelfDescribingJson {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.data.serialize(serializer)
+    }
+}
+
+/// The list of context entities attached to an event. Wrapped in an
+/// [`Envelope`], this supplies the
+/// `"iglu:com.snowplowanalytics.snowplow/contexts/jsonschema/1-0-1"` schema
+/// that Snowplow expects for the `co` field of an event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Contexts(pub Vec<Envelope<SelfDescribingJson>>);
+
+impl HasSchema for Contexts {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("contexts", SchemaVersion::new(1, 0, 1))
+    }
+}
+
+impl FromIterator<SelfDescribingJson> for Contexts {
+    fn from_iter<I: IntoIterator<Item = SelfDescribingJson>>(iter: I) -> Self {
+        Self(iter.into_iter().map(Envelope).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use std::time::{Duration, SystemTime};
+
+    use tracing::Level;
+
+    use super::{
+        Envelope, EventType, HasSchema, Schema, SchemaVersion, SelfDescribingJson, SnowplowEvent,
+        SnowplowTimestamp,
+    };
+    use crate::emitter::EventContainer;
+    use crate::testing::CapturedLogs;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Click {
+        target: String,
+    }
+
+    impl HasSchema for Click {
+        fn schema(&self) -> Schema {
+            Schema::new("com.example", "click", SchemaVersion::new(1, 0, 0))
+        }
+    }
+
+    #[test]
+    fn test_schema_parsing() {
+        let schema: Schema = "iglu:com.example/click/jsonschema/1-2-3".parse().unwrap();
+        assert_eq!(
+            schema,
+            Schema::new("com.example", "click", SchemaVersion::new(1, 2, 3))
+        );
+        assert_eq!(
+            schema.to_string(),
+            "iglu:com.example/click/jsonschema/1-2-3"
+        );
+
+        for invalid in [
+            "com.example/click/jsonschema/1-0-0",
+            "iglu:com.example/click/jsonschema/1-0",
+            "iglu:com.example/click/jsonschema/1-0-0-0",
+            "iglu:com.example/click/avro/1-0-0",
+            "iglu:com.example//jsonschema/1-0-0",
+            "iglu:com.example/click/jsonschema/1-+0-0",
+            "iglu:com.example/click/jsonschema/1-0-0/extra",
+        ] {
+            assert!(invalid.parse::<Schema>().is_err(), "{invalid} parsed");
+        }
+    }
+
+    #[test]
+    fn test_payload_data_round_trip() {
+        let body = json!({
+            "schema": "iglu:com.snowplowanalytics.snowplow/payload_data/jsonschema/1-0-4",
+            "data": [{
+                "e": "ue",
+                "ue_pr": "{\"schema\":\"iglu:com.snowplowanalytics.snowplow/unstruct_event/jsonschema/1-0-0\",\"data\":{\"schema\":\"iglu:com.example/click/jsonschema/1-0-0\",\"data\":{\"target\":\"button\"}}}",
+                "co": "{\"schema\":\"iglu:com.snowplowanalytics.snowplow/contexts/jsonschema/1-0-1\",\"data\":[{\"schema\":\"iglu:com.example/page/jsonschema/1-0-0\",\"data\":{\"id\":5}}]}",
+                "p": "pc",
+                "aid": "app",
+                "tv": "rust-1.0.0",
+                "tna": "ns",
+                "uid": "user",
+                "res": "1920x1080",
+                "vid": "3",
+                "eid": "8c2a1b52-a2ff-4f2d-9c4d-6f5c8e3b1a7e",
+                "dtm": "1666000000000",
+                "stm": "1666000000500",
+            }],
+        });
+
+        let container: EventContainer<'static, SelfDescribingJson> =
+            serde_json::from_value(body.clone()).unwrap();
+
+        let event = &container.0[0];
+        assert_eq!(
+            *event.data.payload().unwrap(),
+            SelfDescribingJson::new(
+                Schema::new("com.example", "click", SchemaVersion::new(1, 0, 0)),
+                json!({"target": "button"}),
+            )
+        );
+        assert_eq!(event.user_id.as_deref(), Some("user"));
+        assert_eq!(event.contexts.as_ref().unwrap().0 .0 .0.len(), 1);
+
+        // Reserializing gives back exactly the original
+        assert_eq!(serde_json::to_value(&container).unwrap(), body);
+    }
+
+    #[test]
+    fn test_base64_fields() {
+        // ue_px and cx are base64 encodings of the same JSON as ue_pr and co
+        let event: SnowplowEvent<'static, Click> = serde_json::from_value(json!({
+            "e": "ue",
+            "ue_px": "eyJzY2hlbWEiOiJpZ2x1OmNvbS5zbm93cGxvd2FuYWx5dGljcy5zbm93cGxvdy91bnN0cnVjdF9ldmVudC9qc29uc2NoZW1hLzEtMC0wIiwiZGF0YSI6eyJzY2hlbWEiOiJpZ2x1OmNvbS5leGFtcGxlL2NsaWNrL2pzb25zY2hlbWEvMS0wLTAiLCJkYXRhIjp7InRhcmdldCI6ImJ1dHRvbiJ9fX0",
+            "cx": "eyJzY2hlbWEiOiJpZ2x1OmNvbS5zbm93cGxvd2FuYWx5dGljcy5zbm93cGxvdy9jb250ZXh0cy9qc29uc2NoZW1hLzEtMC0xIiwiZGF0YSI6W119",
+            "p": "web",
+            "aid": "app",
+            "tv": "js-3.0.0",
+            "tna": "sp",
+            "dtm": "1666000000000",
+            "stm": "1666000000500",
+        }))
+        .unwrap();
+
+        assert_eq!(
+            event.data.payload(),
+            Some(&Click {
+                target: "button".to_owned()
+            })
+        );
+        assert_eq!(event.contexts.unwrap().0 .0 .0, []);
+    }
+
+    #[test]
+    fn test_transaction_round_trip() {
+        let body = json!({
+            "e": "tr",
+            "tr_id": "order-1",
+            "tr_tt": "25.5",
+            "tr_cu": "EUR",
+            "p": "srv",
+            "aid": "app",
+            "tv": "rust-0.1.0",
+            "tna": "ns",
+            "dtm": "1666000000000",
+            "stm": "1666000000500",
+        });
+        let event: SnowplowEvent<'static, Click> = serde_json::from_value(body.clone()).unwrap();
+
+        assert_eq!(event.data.event_type(), EventType::Transaction);
+        assert!(event.data.payload().is_none());
+        assert_eq!(serde_json::to_value(&event).unwrap(), body);
+
+        // Transaction items need a SKU
+        let result = serde_json::from_value::<SnowplowEvent<'static, Click>>(json!({
+            "e": "ti",
+            "ti_id": "order-1",
+            "ti_pr": "9.25",
+            "ti_qu": "2",
+            "p": "srv",
+            "aid": "app",
+            "tv": "rust-0.1.0",
+            "tna": "ns",
+            "dtm": "1666000000000",
+            "stm": "1666000000500",
+        }));
+        assert!(result.unwrap_err().to_string().contains("ti_sk"));
+    }
+
+    #[test]
+    fn test_typed_schema_mismatch() {
+        let result = serde_json::from_value::<Envelope<Click>>(json!({
+            "schema": "iglu:com.example/tap/jsonschema/1-0-0",
+            "data": {"target": "button"},
+        }));
+
+        assert!(result.is_err());
+
+        let result = serde_json::from_value::<Envelope<Click>>(json!({
+            "schema": "iglu:com.example/click/jsonschema/1-1-0",
+            "data": {"target": "button"},
+        }));
+
+        assert_eq!(
+            result.unwrap(),
+            Envelope(Click {
+                target: "button".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_pre_epoch_timestamp() {
+        let logs = CapturedLogs::start();
+        let timestamp = SnowplowTimestamp::from(SystemTime::UNIX_EPOCH - Duration::from_secs(1));
+
+        assert_eq!(serde_json::to_value(timestamp).unwrap(), json!("0"));
+        assert_eq!(
+            logs.messages(),
+            [(
+                Level::WARN,
+                "timestamp is before the unix epoch; sending it as 0".to_owned()
+            )]
+        );
+    }
+}
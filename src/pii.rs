@@ -0,0 +1,376 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+PII pseudonymization. A [`PiiPolicy`] describes which values in an event are
+personally identifiable, and how they should be hashed before they leave the
+device. Install one on a [`Tracker`][crate::Tracker] with
+[`set_pii_policy`][crate::Tracker::set_pii_policy].
+
+```
+use serde_json::json;
+use snowplow_tracker::pii::{PiiHasher, PiiPolicy, SubjectField};
+use snowplow_tracker::{Schema, SchemaVersion, SelfDescribingJson};
+
+let policy = PiiPolicy::new(PiiHasher::hmac_sha256("secret key"))
+    .with_path(
+        Schema::new("com.example", "signup", SchemaVersion::new(1, 0, 0)),
+        "/user/email",
+    )
+    .with_subject_field(SubjectField::UserId);
+
+let mut event = SelfDescribingJson::new(
+    Schema::new("com.example", "signup", SchemaVersion::new(1, 0, 0)),
+    json!({"user": {"email": "someone@example.com"}}),
+);
+policy.apply(&mut event);
+assert_ne!(event.data["user"]["email"], "someone@example.com");
+```
+*/
+
+use std::fmt::Write as _;
+
+use hmac::{Hmac, Mac as _};
+use serde_json::Value as JsonValue;
+use sha2::{Digest as _, Sha256};
+
+use crate::payload::{HasSchema, Schema, SelfDescribingJson};
+use crate::subject::Subject;
+
+/// The hashing scheme used by a [`PiiPolicy`]. Hashes are rendered as
+/// lowercase hex strings.
+#[derive(Clone)]
+pub enum PiiHasher {
+    /// SHA-256 of the salt followed by the value
+    SaltedSha256 {
+        /// The salt, prepended to every value before hashing
+        salt: Vec<u8>,
+    },
+
+    /// HMAC-SHA256 of the value, keyed with a secret key
+    HmacSha256 {
+        /// The HMAC key
+        key: Vec<u8>,
+    },
+}
+
+impl PiiHasher {
+    /// Create a salted SHA-256 hasher
+    pub fn salted_sha256(salt: impl Into<Vec<u8>>) -> Self {
+        Self::SaltedSha256 { salt: salt.into() }
+    }
+
+    /// Create an HMAC-SHA256 hasher
+    pub fn hmac_sha256(key: impl Into<Vec<u8>>) -> Self {
+        Self::HmacSha256 { key: key.into() }
+    }
+
+    /// Hash a value, returning the hash as a lowercase hex string
+    pub fn hash(&self, value: &str) -> String {
+        let digest = match self {
+            PiiHasher::SaltedSha256 { salt } => {
+                let mut hasher = Sha256::new();
+                hasher.update(salt);
+                hasher.update(value);
+                hasher.finalize()
+            }
+            PiiHasher::HmacSha256 { key } => {
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take a key of any size");
+                mac.update(value.as_bytes());
+                mac.finalize().into_bytes()
+            }
+        };
+
+        digest
+            .iter()
+            .fold(String::with_capacity(64), |mut hex, byte| {
+                write!(&mut hex, "{byte:02x}").expect("write! to a string is infallible");
+                hex
+            })
+    }
+}
+
+// Hand-written so that salts and keys don't end up in logs
+impl std::fmt::Debug for PiiHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PiiHasher::SaltedSha256 { .. } => f.write_str("SaltedSha256 { .. }"),
+            PiiHasher::HmacSha256 { .. } => f.write_str("HmacSha256 { .. }"),
+        }
+    }
+}
+
+/// A field of the [`Subject`] that can be pseudonymized by a [`PiiPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubjectField {
+    /// [`Subject::user_id`] (`uid`)
+    UserId,
+
+    /// [`Subject::ip_address`] (`ip`)
+    IpAddress,
+
+    /// [`Subject::useragent`] (`ua`)
+    Useragent,
+
+    /// [`Subject::domain_user_id`] (`duid`)
+    DomainUserId,
+
+    /// [`Subject::network_user_id`] (`tnuid`)
+    NetworkUserId,
+
+    /// [`Subject::session_id`] (`sid`)
+    SessionId,
+}
+
+impl SubjectField {
+    fn get_mut(self, subject: &mut Subject) -> &mut Option<String> {
+        match self {
+            SubjectField::UserId => &mut subject.user_id,
+            SubjectField::IpAddress => &mut subject.ip_address,
+            SubjectField::Useragent => &mut subject.useragent,
+            SubjectField::DomainUserId => &mut subject.domain_user_id,
+            SubjectField::NetworkUserId => &mut subject.network_user_id,
+            SubjectField::SessionId => &mut subject.session_id,
+        }
+    }
+}
+
+/// A JSON pointer into the data of a particular schema
+#[derive(Debug, Clone)]
+struct PiiPath {
+    vendor: String,
+    name: String,
+    pointer: String,
+}
+
+/// A policy describing which values in an event should be pseudonymized
+/// before the event is sent.
+///
+/// Values in self-describing JSON (event payloads and context entities) are
+/// selected with [JSON pointers](https://www.rfc-editor.org/rfc/rfc6901)
+/// per schema; schemas are matched on vendor and name, so a path applies to
+/// all versions of a schema. Values in the [`Subject`] are selected with
+/// [`SubjectField`]. Selected strings are replaced with their hash; other
+/// JSON values (numbers, objects, etc) are hashed in their JSON form. Nulls
+/// and missing values are left alone.
+#[derive(Debug, Clone)]
+pub struct PiiPolicy {
+    hasher: PiiHasher,
+    paths: Vec<PiiPath>,
+    subject_fields: Vec<SubjectField>,
+}
+
+impl PiiPolicy {
+    /// Create a new, empty policy that will use the given hasher
+    pub fn new(hasher: PiiHasher) -> Self {
+        Self {
+            hasher,
+            paths: Vec::new(),
+            subject_fields: Vec::new(),
+        }
+    }
+
+    /// Pseudonymize the value at `pointer` in any self-describing JSON with
+    /// the given schema's vendor and name.
+    #[must_use]
+    pub fn with_path(mut self, schema: Schema, pointer: impl Into<String>) -> Self {
+        self.paths.push(PiiPath {
+            vendor: schema.vendor.into_owned(),
+            name: schema.name.into_owned(),
+            pointer: pointer.into(),
+        });
+        self
+    }
+
+    /// Pseudonymize a field of the [`Subject`]
+    #[must_use]
+    pub fn with_subject_field(mut self, field: SubjectField) -> Self {
+        self.subject_fields.push(field);
+        self
+    }
+
+    /// The hasher used by this policy
+    pub fn hasher(&self) -> &PiiHasher {
+        &self.hasher
+    }
+
+    /// Pseudonymize all of the configured paths in a piece of
+    /// self-describing JSON
+    pub fn apply(&self, json: &mut SelfDescribingJson) {
+        let schema = json.schema();
+
+        self.paths
+            .iter()
+            .filter(|path| path.vendor == schema.vendor && path.name == schema.name)
+            .for_each(|path| {
+                if let Some(value) = json.data.pointer_mut(&path.pointer) {
+                    self.hash_value(value)
+                }
+            })
+    }
+
+    /// Pseudonymize all of the configured fields of a subject
+    pub fn apply_to_subject(&self, subject: &mut Subject) {
+        for &field in &self.subject_fields {
+            if let Some(value) = field.get_mut(subject) {
+                *value = self.hasher.hash(value);
+            }
+        }
+    }
+
+    fn hash_value(&self, value: &mut JsonValue) {
+        let hashed = match &*value {
+            JsonValue::Null => return,
+            JsonValue::String(s) => self.hasher.hash(s),
+            other => self.hasher.hash(&other.to_string()),
+        };
+
+        *value = JsonValue::String(hashed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{PiiHasher, PiiPolicy, SubjectField};
+    use crate::testing::CaptureServer;
+    use crate::{
+        Platform, Schema, SchemaVersion, SelfDescribingJson, Subject, TrackedEvent, Tracker,
+    };
+
+    fn signup_schema() -> Schema {
+        Schema::new("com.example", "signup", SchemaVersion::new(1, 0, 0))
+    }
+
+    #[test]
+    fn test_hashes() {
+        assert_eq!(
+            PiiHasher::salted_sha256("").hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        );
+        assert_eq!(
+            PiiHasher::salted_sha256("sa").hash("lt"),
+            PiiHasher::salted_sha256("").hash("salt"),
+        );
+
+        // RFC 4231, test case 2
+        assert_eq!(
+            PiiHasher::hmac_sha256("Jefe").hash("what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+        );
+    }
+
+    #[test]
+    fn test_apply_paths() {
+        let hasher = PiiHasher::salted_sha256("pepper");
+        let policy = PiiPolicy::new(hasher.clone())
+            .with_path(signup_schema(), "/email")
+            .with_path(signup_schema(), "/ids/0")
+            .with_path(signup_schema(), "/missing")
+            .with_path(signup_schema(), "/nothing");
+
+        let mut json = SelfDescribingJson::new(
+            signup_schema(),
+            json!({"email": "me@example.com", "ids": [12, 13], "nothing": null, "plan": "pro"}),
+        );
+        policy.apply(&mut json);
+
+        assert_eq!(
+            json.data,
+            json!({
+                "email": hasher.hash("me@example.com"),
+                "ids": [hasher.hash("12"), 13],
+                "nothing": null,
+                "plan": "pro",
+            })
+        );
+
+        // Other schemas are unaffected, but other versions of this one are
+        let mut other = SelfDescribingJson::new(
+            Schema::new("com.example", "login", SchemaVersion::new(1, 0, 0)),
+            json!({"email": "me@example.com"}),
+        );
+        policy.apply(&mut other);
+        assert_eq!(other.data, json!({"email": "me@example.com"}));
+
+        let mut newer = SelfDescribingJson::new(
+            Schema::new("com.example", "signup", SchemaVersion::new(2, 0, 0)),
+            json!({"email": "me@example.com"}),
+        );
+        policy.apply(&mut newer);
+        assert_eq!(newer.data, json!({"email": hasher.hash("me@example.com")}));
+    }
+
+    #[tokio::test]
+    async fn test_raw_values_never_sent() {
+        let server = CaptureServer::start(200).await;
+
+        let mut tracker = Tracker::build(
+            "ns",
+            "app".to_owned(),
+            Platform::Desktop,
+            server.url(),
+            reqwest::Client::new(),
+        );
+        tracker.set_pii_policy(
+            PiiPolicy::new(PiiHasher::hmac_sha256("key"))
+                .with_path(signup_schema(), "/user/email")
+                .with_path(
+                    Schema::new("com.example", "account", SchemaVersion::new(1, 0, 0)),
+                    "/account_id",
+                )
+                .with_subject_field(SubjectField::UserId)
+                .with_subject_field(SubjectField::IpAddress),
+        );
+        tracker.set_subject(Subject {
+            user_id: Some("raw-user-id".to_owned()),
+            ip_address: Some("203.0.113.42".to_owned()),
+            ..Subject::default()
+        });
+
+        let event = TrackedEvent::new(SelfDescribingJson::new(
+            signup_schema(),
+            json!({"user": {"email": "raw-email@example.com"}}),
+        ))
+        .with_context(SelfDescribingJson::new(
+            Schema::new("com.example", "account", SchemaVersion::new(1, 0, 0)),
+            json!({"account_id": "raw-account-id"}),
+        ));
+
+        tracker.track(event).await.expect("failed to track event");
+
+        let bodies = server.bodies();
+        assert_eq!(bodies.len(), 1);
+        let body = &bodies[0];
+
+        for raw in [
+            "raw-user-id",
+            "203.0.113.42",
+            "raw-email@example.com",
+            "raw-account-id",
+        ] {
+            assert!(!body.contains(raw), "{raw:?} was found in {body}");
+        }
+
+        let hasher = PiiHasher::hmac_sha256("key");
+        for hashed in [
+            "raw-user-id",
+            "203.0.113.42",
+            "raw-email@example.com",
+            "raw-account-id",
+        ] {
+            let hashed = hasher.hash(hashed);
+            assert!(body.contains(&hashed), "{hashed:?} was not found in {body}");
+        }
+    }
+}
@@ -0,0 +1,553 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Platform context entities, describing the environment the app is running in,
+similar to the ones the mobile trackers collect automatically. These are
+opt-in: pick which ones to attach to every event with
+[`Tracker::set_platform_contexts`][crate::Tracker::set_platform_contexts] (or
+[`TrackerBuilder::platform_context`][crate::TrackerBuilder::platform_context]).
+
+- [`PlatformContext::Desktop`] attaches a [`DesktopContext`], with the
+  `desktop_context` schema: the OS type and version, whether it's 64-bit,
+  and the number of processors, plus the device manufacturer and model on
+  Linux.
+- [`PlatformContext::System`] attaches a [`SystemEntity`]: the processor
+  architecture and, on Linux, the kernel release.
+- [`PlatformContext::Application`] attaches an [`ApplicationEntity`], with the
+  `application` schema: the app's version and build, usually from its
+  compile-time environment with [`application_entity!`][crate::application_entity].
+- [`PlatformContext::Process`] attaches a [`ProcessEntity`]: the host name,
+  process ID and executable name.
+
+# Schemas
+
+Only the `desktop_context` and `application` schemas are published on Iglu
+Central, under `com.snowplowanalytics.snowplow`, and they don't cover
+everything:
+
+- `desktop_context` has no fields for the architecture or the kernel release,
+  only whether the OS is 64-bit. That's why those are in the separate
+  [`SystemEntity`].
+- `application` only has the version and build, not the app's name. The
+  name is the tracker's app ID, which is sent with every event anyway.
+
+The `system` and `process` entities have no published schemas at all, so
+they're attached with schemas under a vendor of your choosing, which you have
+to publish in your own Iglu registry; see [`SystemEntity`] and
+[`ProcessEntity`] for the schemas to publish.
+
+The entities are collected once, when they're set on the tracker, since none
+of them change while the app is running.
+
+```no_run
+use snowplow_tracker::platform::PlatformContext;
+use snowplow_tracker::{application_entity, Tracker};
+
+# fn example(mut tracker: Tracker) {
+tracker.set_platform_contexts([
+    PlatformContext::Desktop,
+    PlatformContext::System {
+        vendor: "com.example".to_owned(),
+    },
+    PlatformContext::Application(application_entity!()),
+    PlatformContext::Process {
+        vendor: "com.example".to_owned(),
+    },
+]);
+# }
+```
+*/
+
+use std::borrow::Cow;
+
+use serde::Serialize;
+
+use crate::payload::{HasSchema, Schema, SchemaVersion, SelfDescribingJson};
+
+/// The operating system and device the app is running on, attached as a
+/// `desktop_context` entity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DesktopContext {
+    /// The type of the OS, like `"linux"` or `"macos"`; see
+    /// [`std::env::consts::OS`]
+    pub os_type: String,
+
+    /// The version of the OS. On Linux, this is the `VERSION_ID` from
+    /// `/etc/os-release`, like `"22.04"`, or the kernel release if there
+    /// isn't one. It's empty if the version can't be found.
+    pub os_version: String,
+
+    /// The service pack of the OS, on Windows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_service_pack: Option<String>,
+
+    /// Whether the OS is 64-bit. The schema has no field for the
+    /// architecture itself; that's in the [`SystemEntity`].
+    #[serde(rename = "osIs64Bit", skip_serializing_if = "Option::is_none")]
+    pub os_is_64_bit: Option<bool>,
+
+    /// The manufacturer of the device, where it can be found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_manufacturer: Option<String>,
+
+    /// The model of the device, where it can be found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_model: Option<String>,
+
+    /// The number of processors available to the app
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_processor_count: Option<u32>,
+}
+
+impl DesktopContext {
+    /// Collect the details of the current OS and device
+    pub fn current() -> Self {
+        Self {
+            os_type: std::env::consts::OS.to_owned(),
+            os_version: os_version().unwrap_or_default(),
+            os_service_pack: None,
+            os_is_64_bit: Some(cfg!(target_pointer_width = "64")),
+            device_manufacturer: device_manufacturer(),
+            device_model: device_model(),
+            device_processor_count: std::thread::available_parallelism()
+                .ok()
+                .and_then(|count| u32::try_from(count.get()).ok()),
+        }
+    }
+}
+
+impl HasSchema for DesktopContext {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("desktop_context", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// Read a file from `/proc`, `/sys` or `/etc`, trimming whitespace. Missing
+/// or unreadable files are treated as unknown, rather than as an error.
+#[cfg(target_os = "linux")]
+fn read_trimmed(path: &str) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let contents = contents.trim();
+    (!contents.is_empty()).then(|| contents.to_owned())
+}
+
+#[cfg(target_os = "linux")]
+fn os_version() -> Option<String> {
+    std::fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|os_release| os_release_version(&os_release))
+        .or_else(kernel_release)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn os_version() -> Option<String> {
+    None
+}
+
+/// Get the `VERSION_ID` from the contents of an `os-release` file
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn os_release_version(os_release: &str) -> Option<String> {
+    os_release
+        .lines()
+        .find_map(|line| line.strip_prefix("VERSION_ID="))
+        .map(|version| version.trim().trim_matches('"').to_owned())
+        .filter(|version| !version.is_empty())
+}
+
+#[cfg(target_os = "linux")]
+fn kernel_release() -> Option<String> {
+    read_trimmed("/proc/sys/kernel/osrelease")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel_release() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn device_manufacturer() -> Option<String> {
+    read_trimmed("/sys/class/dmi/id/sys_vendor")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn device_manufacturer() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn device_model() -> Option<String> {
+    read_trimmed("/sys/class/dmi/id/product_name")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn device_model() -> Option<String> {
+    None
+}
+
+/**
+The processor architecture and kernel of the host, attached as a `system`
+entity alongside the [`DesktopContext`], which has no fields for them.
+
+There's no published Iglu schema for this entity, so it's attached with the
+`iglu:<vendor>/system/jsonschema/1-0-0` schema, for the vendor given to
+[`PlatformContext::System`]. Publish a schema like this one in your own Iglu
+registry under that vendor, or events carrying the entity will fail
+validation:
+
+```json
+{
+  "$schema": "http://iglucentral.com/schemas/com.snowplowanalytics.self-desc/schema/jsonschema/1-0-0#",
+  "self": {"vendor": "com.example", "name": "system", "format": "jsonschema", "version": "1-0-0"},
+  "type": "object",
+  "properties": {
+    "architecture": {"type": "string", "maxLength": 255},
+    "kernelRelease": {"type": "string", "maxLength": 255}
+  },
+  "required": ["architecture"],
+  "additionalProperties": false
+}
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemEntity {
+    /// The processor architecture, like `"x86_64"` or `"aarch64"`; see
+    /// [`std::env::consts::ARCH`]
+    pub architecture: String,
+
+    /// The release of the kernel, like `"6.5.0-14-generic"`. This is only
+    /// found on Linux.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernel_release: Option<String>,
+}
+
+impl SystemEntity {
+    /// Collect the details of the current host
+    pub fn current() -> Self {
+        Self {
+            architecture: std::env::consts::ARCH.to_owned(),
+            kernel_release: kernel_release(),
+        }
+    }
+
+    /// The schema of the entity, under the given vendor
+    pub fn schema(vendor: impl Into<Cow<'static, str>>) -> Schema {
+        Schema::new(vendor, "system", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/// The app itself, attached as an `application` entity. The published schema
+/// has no field for the app's name; that's the tracker's app ID.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApplicationEntity {
+    /// The version of the app
+    pub version: String,
+
+    /// The build of the app, like a build number or commit hash
+    pub build: String,
+}
+
+impl ApplicationEntity {
+    /// Create a new application entity. Usually this is created from the
+    /// app's compile-time environment with
+    /// [`application_entity!`][crate::application_entity] instead.
+    pub fn new(version: impl Into<String>, build: impl Into<String>) -> Self {
+        Self {
+            version: version.into(),
+            build: build.into(),
+        }
+    }
+}
+
+impl HasSchema for ApplicationEntity {
+    fn schema(&self) -> Schema {
+        Schema::new_snowplow("application", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+/**
+Create an [`ApplicationEntity`][crate::platform::ApplicationEntity] for the
+calling crate, from its compile-time environment: the version comes from its
+`Cargo.toml`, and the build comes from the `SNOWPLOW_APP_BUILD` environment
+variable, if it was set during compilation (it's empty otherwise).
+
+```
+use snowplow_tracker::application_entity;
+
+let app = application_entity!();
+assert_eq!(app.version, env!("CARGO_PKG_VERSION"));
+```
+*/
+#[macro_export]
+macro_rules! application_entity {
+    () => {
+        $crate::platform::ApplicationEntity {
+            version: ::std::env!("CARGO_PKG_VERSION").to_owned(),
+            build: match ::std::option_env!("SNOWPLOW_APP_BUILD") {
+                ::std::option::Option::Some(build) => build,
+                ::std::option::Option::None => "",
+            }
+            .to_owned(),
+        }
+    };
+}
+
+/**
+The host and process the app is running in, attached as a `process` entity.
+
+There's no published Iglu schema for this entity, so it's attached with the
+`iglu:<vendor>/process/jsonschema/1-0-0` schema, for the vendor given to
+[`PlatformContext::Process`]. Publish a schema like this one in your own Iglu
+registry under that vendor, or events carrying the entity will fail
+validation:
+
+```json
+{
+  "$schema": "http://iglucentral.com/schemas/com.snowplowanalytics.self-desc/schema/jsonschema/1-0-0#",
+  "self": {"vendor": "com.example", "name": "process", "format": "jsonschema", "version": "1-0-0"},
+  "type": "object",
+  "properties": {
+    "hostname": {"type": "string", "maxLength": 255},
+    "pid": {"type": "integer", "minimum": 0, "maximum": 4294967295},
+    "executable": {"type": "string", "maxLength": 255}
+  },
+  "required": ["pid"],
+  "additionalProperties": false
+}
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessEntity {
+    /// The name of the host, where it can be found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+
+    /// The ID of the process
+    pub pid: u32,
+
+    /// The file name of the process's executable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executable: Option<String>,
+}
+
+impl ProcessEntity {
+    /// Collect the details of the current process
+    pub fn current() -> Self {
+        let executable = std::env::current_exe().ok().and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        });
+
+        Self {
+            hostname: hostname(),
+            pid: std::process::id(),
+            executable,
+        }
+    }
+
+    /// The schema of the entity, under the given vendor
+    pub fn schema(vendor: impl Into<Cow<'static, str>>) -> Schema {
+        Schema::new(vendor, "process", SchemaVersion::new(1, 0, 0))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn hostname() -> Option<String> {
+    read_trimmed("/proc/sys/kernel/hostname")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn hostname() -> Option<String> {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .into_iter()
+        .find_map(|var| std::env::var(var).ok())
+}
+
+/// A platform context entity to attach to every event; see the
+/// [module docs][self].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlatformContext {
+    /// Attach a [`DesktopContext`] for the current OS and device
+    Desktop,
+
+    /// Attach a [`SystemEntity`] for the current host
+    System {
+        /// The vendor of the `system` schema, which has to be published in
+        /// your own Iglu registry; see [`SystemEntity`]
+        vendor: String,
+    },
+
+    /// Attach the given [`ApplicationEntity`]
+    Application(ApplicationEntity),
+
+    /// Attach a [`ProcessEntity`] for the current process
+    Process {
+        /// The vendor of the `process` schema, which has to be published in
+        /// your own Iglu registry; see [`ProcessEntity`]
+        vendor: String,
+    },
+}
+
+impl PlatformContext {
+    /// Collect the entity
+    pub fn collect(&self) -> SelfDescribingJson {
+        match self {
+            PlatformContext::Desktop => entity(&DesktopContext::current()),
+            PlatformContext::System { vendor } => SelfDescribingJson::new(
+                SystemEntity::schema(vendor.clone()),
+                serde_json::to_value(SystemEntity::current())
+                    .expect("platform entities always serialize successfully"),
+            ),
+            PlatformContext::Application(app) => entity(app),
+            PlatformContext::Process { vendor } => SelfDescribingJson::new(
+                ProcessEntity::schema(vendor.clone()),
+                serde_json::to_value(ProcessEntity::current())
+                    .expect("platform entities always serialize successfully"),
+            ),
+        }
+    }
+}
+
+/// Convert a platform entity to self-describing JSON
+fn entity(entity: &(impl HasSchema + Serialize)) -> SelfDescribingJson {
+    SelfDescribingJson::from_payload(entity)
+        .expect("platform entities always serialize successfully")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+
+    use super::{os_release_version, ApplicationEntity, PlatformContext};
+    use crate::testing::CaptureServer;
+    use crate::{Schema, SchemaVersion, SelfDescribingJson, TrackedEvent, Tracker};
+
+    #[test]
+    fn test_os_release_version() {
+        let os_release = "NAME=\"Ubuntu\"\nVERSION_ID=\"22.04\"\nID=ubuntu\n";
+        assert_eq!(os_release_version(os_release).as_deref(), Some("22.04"));
+        assert_eq!(os_release_version("ID=arch\n"), None);
+    }
+
+    #[test]
+    fn test_application_entity_macro() {
+        let app = crate::application_entity!();
+        assert_eq!(app.version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn test_platform_contexts_are_attached() {
+        let server = CaptureServer::start(200).await;
+        let tracker = Tracker::builder()
+            .collector(server.url().as_str())
+            .app_id("app")
+            .platform_context(PlatformContext::Desktop)
+            .platform_context(PlatformContext::System {
+                vendor: "com.example".to_owned(),
+            })
+            .platform_context(PlatformContext::Application(ApplicationEntity::new(
+                "1.2.3", "abc123",
+            )))
+            .platform_context(PlatformContext::Process {
+                vendor: "com.example".to_owned(),
+            })
+            .build()
+            .unwrap();
+
+        tracker
+            .track(TrackedEvent::new(SelfDescribingJson::new(
+                Schema::new("com.example", "test", SchemaVersion::new(1, 0, 0)),
+                json!({}),
+            )))
+            .await
+            .unwrap();
+
+        let body: JsonValue = serde_json::from_str(&server.bodies()[0]).unwrap();
+        let contexts: JsonValue =
+            serde_json::from_str(body["data"][0]["co"].as_str().unwrap()).unwrap();
+        let contexts = contexts["data"].as_array().unwrap();
+        assert_eq!(contexts.len(), 4);
+
+        let processors = std::thread::available_parallelism().unwrap().get();
+        let mut desktop = json!({
+            "osType": std::env::consts::OS,
+            "osVersion": "",
+            "osIs64Bit": cfg!(target_pointer_width = "64"),
+            "deviceProcessorCount": processors,
+        });
+        let mut system = json!({"architecture": std::env::consts::ARCH});
+        let mut process = json!({"pid": std::process::id()});
+
+        let executable = std::env::current_exe().unwrap();
+        process["executable"] = json!(executable.file_name().unwrap().to_str().unwrap());
+
+        #[cfg(target_os = "linux")]
+        {
+            let read = |path| {
+                std::fs::read_to_string(path)
+                    .ok()
+                    .map(|contents| contents.trim().to_owned())
+                    .filter(|contents| !contents.is_empty())
+            };
+
+            let kernel_release = read("/proc/sys/kernel/osrelease").unwrap();
+            desktop["osVersion"] = json!(read("/etc/os-release")
+                .and_then(|os_release| os_release_version(&os_release))
+                .unwrap_or_else(|| kernel_release.clone()));
+            system["kernelRelease"] = json!(kernel_release);
+
+            if let Some(manufacturer) = read("/sys/class/dmi/id/sys_vendor") {
+                desktop["deviceManufacturer"] = json!(manufacturer);
+            }
+            if let Some(model) = read("/sys/class/dmi/id/product_name") {
+                desktop["deviceModel"] = json!(model);
+            }
+            if let Some(hostname) = read("/proc/sys/kernel/hostname") {
+                process["hostname"] = json!(hostname);
+            }
+        }
+
+        assert_eq!(
+            contexts[0],
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/desktop_context/jsonschema/1-0-0",
+                "data": desktop,
+            })
+        );
+
+        assert_eq!(
+            contexts[1],
+            json!({
+                "schema": "iglu:com.example/system/jsonschema/1-0-0",
+                "data": system,
+            })
+        );
+
+        assert_eq!(
+            contexts[2],
+            json!({
+                "schema": "iglu:com.snowplowanalytics.snowplow/application/jsonschema/1-0-0",
+                "data": {"version": "1.2.3", "build": "abc123"},
+            })
+        );
+
+        assert_eq!(
+            contexts[3],
+            json!({
+                "schema": "iglu:com.example/process/jsonschema/1-0-0",
+                "data": process,
+            })
+        );
+    }
+}
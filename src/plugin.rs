@@ -0,0 +1,112 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Tracker plugins. A [`TrackerPlugin`] is registered on a
+[`Tracker`][crate::Tracker] and gets to observe (and in some cases modify)
+every event as it passes through the tracker. This is the extension point for
+things like enrichment, filtering, scrubbing, logging and metrics.
+*/
+
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::emitter::SendError;
+use crate::payload::{SelfDescribingJson, SnowplowEvent};
+use crate::tracker::TrackedEvent;
+
+/// What a [`TrackerPlugin`] wants to happen to an event passed to
+/// [`before_track`][TrackerPlugin::before_track].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PluginAction {
+    /// Continue processing the event normally
+    #[default]
+    Keep,
+
+    /// Silently drop the event. It won't be seen by any later plugins and
+    /// won't be sent to the collector.
+    Drop,
+}
+
+/// The outcome of sending a single batch of events to the collector, as seen
+/// by [`TrackerPlugin::after_send`].
+#[derive(Debug, Clone, Copy)]
+pub struct SendOutcome<'a> {
+    /// The IDs of the events in this batch, in the order they were sent
+    pub event_ids: &'a [Option<Uuid>],
+
+    /// The result of sending the batch
+    pub result: Result<(), &'a SendError>,
+}
+
+/**
+A hook into the [`Tracker`][crate::Tracker] event pipeline. All of the methods
+have no-op default implementations, so a plugin only needs to implement the
+hooks it cares about.
+
+Plugins are run in the order they were registered. For each call to
+[`track_batch`][crate::Tracker::track_batch]:
+
+1. Every event is passed to [`before_track`][Self::before_track], where it can
+   be modified or dropped.
+2. The surviving events are assembled into full [`SnowplowEvent`]s, each of
+   which is passed to [`after_build`][Self::after_build].
+3. The batch is sent to the collector, and the outcome is passed to
+   [`after_send`][Self::after_send].
+*/
+pub trait TrackerPlugin: Send + Sync {
+    /// Inspect, modify, or drop an event before it's built into a
+    /// [`SnowplowEvent`]. By this point the event's payload has already been
+    /// serialized into a [`SelfDescribingJson`].
+    fn before_track(&self, event: &mut TrackedEvent<SelfDescribingJson>) -> PluginAction {
+        let _ = event;
+        PluginAction::Keep
+    }
+
+    /// Inspect a fully built event, just before it's sent.
+    fn after_build(&self, event: &SnowplowEvent<'_, SelfDescribingJson>) {
+        let _ = event;
+    }
+
+    /// Observe the outcome of sending a batch of events.
+    fn after_send(&self, outcome: &SendOutcome<'_>) {
+        let _ = outcome;
+    }
+}
+
+impl<T: TrackerPlugin + ?Sized> TrackerPlugin for Arc<T> {
+    fn before_track(&self, event: &mut TrackedEvent<SelfDescribingJson>) -> PluginAction {
+        T::before_track(self, event)
+    }
+
+    fn after_build(&self, event: &SnowplowEvent<'_, SelfDescribingJson>) {
+        T::after_build(self, event)
+    }
+
+    fn after_send(&self, outcome: &SendOutcome<'_>) {
+        T::after_send(self, outcome)
+    }
+}
+
+impl<T: TrackerPlugin + ?Sized> TrackerPlugin for Box<T> {
+    fn before_track(&self, event: &mut TrackedEvent<SelfDescribingJson>) -> PluginAction {
+        T::before_track(self, event)
+    }
+
+    fn after_build(&self, event: &SnowplowEvent<'_, SelfDescribingJson>) {
+        T::after_build(self, event)
+    }
+
+    fn after_send(&self, outcome: &SendOutcome<'_>) {
+        T::after_send(self, outcome)
+    }
+}
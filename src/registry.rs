@@ -0,0 +1,176 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+A process-wide registry of [`Tracker`]s, keyed by namespace. This lets
+different components of an application each use their own tracker, without
+passing trackers around: register them once during startup, then fetch them
+from anywhere with [`get`]. Trackers that should share one emitter can be
+created with [`Tracker::shared_emitter`].
+
+```
+use snowplow_tracker::Tracker;
+
+# fn example() -> Result<(), snowplow_tracker::builder::BuildError> {
+let billing = Tracker::builder()
+    .collector("collector.example.com")
+    .namespace("billing")
+    .app_id("my-app")
+    .build()?;
+
+let search = Tracker::builder()
+    .shared_emitter(billing.shared_emitter())
+    .namespace("search")
+    .app_id("my-app")
+    .build()?;
+
+snowplow_tracker::register(billing);
+snowplow_tracker::register(search);
+
+// Later, anywhere in the application
+let billing = snowplow_tracker::get("billing").expect("billing tracker is registered");
+# Ok(())
+# }
+```
+*/
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::tracker::Tracker;
+
+#[derive(Default)]
+struct Registry {
+    trackers: HashMap<String, Arc<Tracker>>,
+    default: Option<String>,
+}
+
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+fn read() -> RwLockReadGuard<'static, Registry> {
+    registry()
+        .read()
+        .unwrap_or_else(|poison| poison.into_inner())
+}
+
+fn write() -> RwLockWriteGuard<'static, Registry> {
+    registry()
+        .write()
+        .unwrap_or_else(|poison| poison.into_inner())
+}
+
+/// Register a tracker under its namespace, replacing any tracker already
+/// registered with the same namespace. If there's no default tracker yet,
+/// this one becomes the default. Returns the registered tracker.
+pub fn register(tracker: impl Into<Arc<Tracker>>) -> Arc<Tracker> {
+    let tracker = tracker.into();
+    let namespace = tracker.namespace().to_owned();
+
+    let mut registry = write();
+    registry.default.get_or_insert_with(|| namespace.clone());
+    registry.trackers.insert(namespace, tracker.clone());
+
+    tracker
+}
+
+/// Get the tracker registered with the given namespace
+pub fn get(namespace: &str) -> Option<Arc<Tracker>> {
+    read().trackers.get(namespace).cloned()
+}
+
+/// Remove the tracker registered with the given namespace, returning it. If
+/// it was the default tracker, there's no default tracker afterwards.
+pub fn unregister(namespace: &str) -> Option<Arc<Tracker>> {
+    let mut registry = write();
+    if registry.default.as_deref() == Some(namespace) {
+        registry.default = None;
+    }
+
+    registry.trackers.remove(namespace)
+}
+
+/// Make the tracker registered with the given namespace the default one.
+/// Returns false (and leaves the default unchanged) if there's no such
+/// tracker.
+pub fn set_default(namespace: &str) -> bool {
+    let mut registry = write();
+    if !registry.trackers.contains_key(namespace) {
+        return false;
+    }
+
+    registry.default = Some(namespace.to_owned());
+    true
+}
+
+/// Get the default tracker; see [`register`] and [`set_default`].
+pub fn default_tracker() -> Option<Arc<Tracker>> {
+    let registry = read();
+    registry
+        .default
+        .as_ref()
+        .and_then(|namespace| registry.trackers.get(namespace))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{default_tracker, get, register, set_default, unregister};
+    use crate::Tracker;
+
+    fn tracker(namespace: &'static str) -> Tracker {
+        Tracker::builder()
+            .collector("collector.example.com")
+            .namespace(namespace)
+            .app_id("app")
+            .build()
+            .unwrap()
+    }
+
+    // The registry is global, so everything is tested together, to avoid
+    // other tests changing the default tracker concurrently.
+    #[test]
+    fn test_registry() {
+        let billing = tracker("registry-billing");
+        let search = Tracker::builder()
+            .shared_emitter(billing.shared_emitter())
+            .namespace("registry-search")
+            .app_id("app")
+            .build()
+            .unwrap();
+
+        let billing = register(billing);
+        let search = register(search);
+
+        assert!(Arc::ptr_eq(&get("registry-billing").unwrap(), &billing));
+        assert!(Arc::ptr_eq(
+            &get("registry-search").unwrap().shared_emitter(),
+            &billing.shared_emitter()
+        ));
+        assert!(get("registry-missing").is_none());
+
+        assert!(set_default("registry-search"));
+        assert!(!set_default("registry-missing"));
+        assert!(Arc::ptr_eq(&default_tracker().unwrap(), &search));
+
+        // Replacing a tracker keeps it as the default
+        let replacement = register(tracker("registry-search"));
+        assert!(Arc::ptr_eq(&default_tracker().unwrap(), &replacement));
+
+        assert!(unregister("registry-search").is_some());
+        assert!(get("registry-search").is_none());
+        assert!(default_tracker().is_none());
+    }
+}
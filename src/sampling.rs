@@ -0,0 +1,395 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Event sampling and rate limiting. A [`Sampler`] is consulted by the
+[`Tracker`][crate::Tracker] for every event, and decides (per schema) whether
+the event is kept or dropped. It keeps count of how many events it has kept
+and dropped, so that downstream analysis can be reweighted.
+*/
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::Rng as _;
+use sha2::{Digest as _, Sha256};
+use thiserror::Error;
+
+use crate::payload::Schema;
+
+/// How a [`Sampler`] decides which events of a particular schema to keep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplingStrategy {
+    /// Keep each event independently with the given probability, between
+    /// 0 and 1.
+    Probability(f64),
+
+    /// Keep events as long as they don't exceed a rate limit. This is a token
+    /// bucket: it holds up to `burst` tokens, refilled at `per_second` tokens
+    /// per second, and each kept event consumes one token.
+    RateLimit {
+        /// The sustained number of events per second to keep
+        per_second: f64,
+
+        /// The number of events that can be kept in a single burst
+        burst: u32,
+    },
+
+    /// Keep all events from a fixed fraction (between 0 and 1) of users.
+    /// Whether a user is kept is decided deterministically from their
+    /// [`user_id`][crate::Subject::user_id], so all of a user's events are
+    /// kept or dropped together, across restarts and across devices. Events
+    /// without a user ID are sampled as with
+    /// [`Probability`][Self::Probability].
+    ByUser(f64),
+}
+
+impl SamplingStrategy {
+    /// Convenience for [`RateLimit`][Self::RateLimit] with a time period
+    /// other than one second; for instance,
+    /// `SamplingStrategy::rate_limit(10, Duration::from_secs(60))` keeps 10
+    /// events per minute, in bursts of up to 10. Fails if `period` is zero.
+    pub fn rate_limit(events: u32, period: Duration) -> Result<Self, SamplingError> {
+        if period.is_zero() {
+            return Err(SamplingError::ZeroPeriod);
+        }
+
+        Ok(Self::RateLimit {
+            per_second: events as f64 / period.as_secs_f64(),
+            burst: events,
+        })
+    }
+
+    /// Check that this strategy's probability is between 0 and 1, or that
+    /// its rate is finite and not negative
+    pub fn validate(&self) -> Result<(), SamplingError> {
+        match *self {
+            SamplingStrategy::Probability(probability) | SamplingStrategy::ByUser(probability) => {
+                if (0.0..=1.0).contains(&probability) {
+                    Ok(())
+                } else {
+                    Err(SamplingError::Probability(probability))
+                }
+            }
+            SamplingStrategy::RateLimit { per_second, .. } => {
+                if per_second.is_finite() && per_second >= 0.0 {
+                    Ok(())
+                } else {
+                    Err(SamplingError::Rate(per_second))
+                }
+            }
+        }
+    }
+}
+
+/// An invalid [`SamplingStrategy`]
+#[derive(Debug, Clone, Copy, Error, PartialEq)]
+pub enum SamplingError {
+    /// A probability wasn't between 0 and 1
+    #[error("Sampling probability {0} isn't between 0 and 1")]
+    Probability(f64),
+
+    /// A rate limit wasn't a finite, non-negative number of events per second
+    #[error("Rate limit of {0} events per second isn't finite and non-negative")]
+    Rate(f64),
+
+    /// A rate limit was given over a period of zero
+    #[error("Rate limit period must be nonzero")]
+    ZeroPeriod,
+}
+
+/// Counts of events seen by a [`Sampler`] for a particular schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamplingStats {
+    /// The vendor of the schema
+    pub vendor: String,
+
+    /// The name of the schema
+    pub name: String,
+
+    /// The number of events that were kept
+    pub kept: u64,
+
+    /// The number of events that were dropped
+    pub dropped: u64,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[derive(Debug)]
+struct SamplingRule {
+    vendor: String,
+    name: String,
+    strategy: SamplingStrategy,
+    bucket: Mutex<TokenBucket>,
+    kept: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl SamplingRule {
+    fn should_keep(&self, user_id: Option<&str>) -> bool {
+        match self.strategy {
+            SamplingStrategy::Probability(probability) => random_keep(probability),
+            SamplingStrategy::ByUser(probability) => match user_id {
+                Some(user_id) => user_keep(user_id, probability),
+                None => random_keep(probability),
+            },
+            SamplingStrategy::RateLimit { per_second, burst } => {
+                let mut bucket = self
+                    .bucket
+                    .lock()
+                    .unwrap_or_else(|poison| poison.into_inner());
+
+                let now = Instant::now();
+                let elapsed = now.saturating_duration_since(bucket.last_refill);
+                bucket.tokens =
+                    (bucket.tokens + elapsed.as_secs_f64() * per_second).min(burst as f64);
+                bucket.last_refill = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+fn random_keep(probability: f64) -> bool {
+    rand::thread_rng().gen::<f64>() < probability
+}
+
+/// Deterministically map a user ID to a number in `[0, 1)` and compare it to
+/// the probability. This uses SHA-256 rather than std's hasher because it
+/// needs to be stable across processes and compiler versions.
+fn user_keep(user_id: &str, probability: f64) -> bool {
+    let digest = Sha256::digest(user_id.as_bytes());
+    let bytes: [u8; 8] = digest[..8].try_into().expect("SHA-256 has 32 bytes");
+    let position = (u64::from_be_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64;
+
+    position < probability
+}
+
+/// Decides which events to keep, per schema. Schemas are matched on vendor
+/// and name, so a rule applies to all versions of a schema. Events with a
+/// schema that doesn't have a rule are always kept (and aren't counted).
+#[derive(Debug, Default)]
+pub struct Sampler {
+    rules: Vec<SamplingRule>,
+}
+
+impl Sampler {
+    /// Create a new sampler with no rules, which keeps everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sample events with the given schema's vendor and name using some
+    /// strategy. If a schema is given more than one rule, only the first one
+    /// is used. Fails if the strategy isn't [valid][SamplingStrategy::validate].
+    pub fn with_rule(
+        mut self,
+        schema: Schema,
+        strategy: SamplingStrategy,
+    ) -> Result<Self, SamplingError> {
+        strategy.validate()?;
+
+        let initial_tokens = match strategy {
+            SamplingStrategy::RateLimit { burst, .. } => burst as f64,
+            _ => 0.0,
+        };
+
+        self.rules.push(SamplingRule {
+            vendor: schema.vendor.into_owned(),
+            name: schema.name.into_owned(),
+            strategy,
+            bucket: Mutex::new(TokenBucket {
+                tokens: initial_tokens,
+                last_refill: Instant::now(),
+            }),
+            kept: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+        Ok(self)
+    }
+
+    /// Decide whether to keep an event with the given schema, on behalf of
+    /// the given user. This updates the kept & dropped counts.
+    pub fn should_keep(&self, schema: &Schema, user_id: Option<&str>) -> bool {
+        let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| rule.vendor == schema.vendor && rule.name == schema.name)
+        else {
+            return true;
+        };
+
+        let keep = rule.should_keep(user_id);
+        let counter = if keep { &rule.kept } else { &rule.dropped };
+        counter.fetch_add(1, Ordering::Relaxed);
+        keep
+    }
+
+    /// Get the kept & dropped counts for every schema with a rule
+    pub fn stats(&self) -> Vec<SamplingStats> {
+        self.rules
+            .iter()
+            .map(|rule| SamplingStats {
+                vendor: rule.vendor.clone(),
+                name: rule.name.clone(),
+                kept: rule.kept.load(Ordering::Relaxed),
+                dropped: rule.dropped.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// The total number of events dropped by this sampler
+    pub fn dropped(&self) -> u64 {
+        self.rules
+            .iter()
+            .map(|rule| rule.dropped.load(Ordering::Relaxed))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Sampler, SamplingError, SamplingStats, SamplingStrategy};
+    use crate::{Schema, SchemaVersion};
+
+    fn tick() -> Schema {
+        Schema::new("com.example", "tick", SchemaVersion::new(1, 0, 0))
+    }
+
+    #[test]
+    fn test_probability() {
+        let sampler = Sampler::new()
+            .with_rule(tick(), SamplingStrategy::Probability(0.0))
+            .unwrap()
+            .with_rule(
+                Schema::new("com.example", "tock", SchemaVersion::new(1, 0, 0)),
+                SamplingStrategy::Probability(1.0),
+            )
+            .unwrap();
+
+        for _ in 0..100 {
+            assert!(!sampler.should_keep(&tick(), None));
+            assert!(sampler.should_keep(
+                &Schema::new("com.example", "tock", SchemaVersion::new(2, 0, 0)),
+                None
+            ));
+            assert!(sampler.should_keep(
+                &Schema::new("com.example", "other", SchemaVersion::new(1, 0, 0)),
+                None
+            ));
+        }
+
+        assert_eq!(sampler.dropped(), 100);
+        assert_eq!(
+            sampler.stats(),
+            [
+                SamplingStats {
+                    vendor: "com.example".to_owned(),
+                    name: "tick".to_owned(),
+                    kept: 0,
+                    dropped: 100,
+                },
+                SamplingStats {
+                    vendor: "com.example".to_owned(),
+                    name: "tock".to_owned(),
+                    kept: 100,
+                    dropped: 0,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let sampler = Sampler::new()
+            .with_rule(
+                tick(),
+                SamplingStrategy::rate_limit(5, Duration::from_secs(3600)).unwrap(),
+            )
+            .unwrap();
+
+        let kept = (0..20)
+            .filter(|_| sampler.should_keep(&tick(), None))
+            .count();
+
+        assert_eq!(kept, 5);
+        assert_eq!(sampler.dropped(), 15);
+    }
+
+    #[test]
+    fn test_invalid_strategies() {
+        assert_eq!(
+            SamplingStrategy::rate_limit(0, Duration::ZERO),
+            Err(SamplingError::ZeroPeriod)
+        );
+
+        for strategy in [
+            SamplingStrategy::Probability(1.5),
+            SamplingStrategy::ByUser(-0.1),
+            SamplingStrategy::Probability(f64::NAN),
+            SamplingStrategy::RateLimit {
+                per_second: f64::INFINITY,
+                burst: 1,
+            },
+            SamplingStrategy::RateLimit {
+                per_second: -1.0,
+                burst: 1,
+            },
+        ] {
+            assert!(
+                Sampler::new().with_rule(tick(), strategy).is_err(),
+                "{strategy:?} was accepted"
+            );
+        }
+
+        assert!(SamplingStrategy::rate_limit(0, Duration::from_secs(1))
+            .unwrap()
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_by_user_is_deterministic() {
+        let sampler = Sampler::new()
+            .with_rule(tick(), SamplingStrategy::ByUser(0.5))
+            .unwrap();
+
+        let users: Vec<String> = (0..200).map(|i| format!("user-{i}")).collect();
+        let first: Vec<bool> = users
+            .iter()
+            .map(|user| sampler.should_keep(&tick(), Some(user)))
+            .collect();
+        let second: Vec<bool> = users
+            .iter()
+            .map(|user| sampler.should_keep(&tick(), Some(user)))
+            .collect();
+
+        assert_eq!(first, second);
+
+        // Roughly half of the users should be kept
+        let kept = first.iter().filter(|&&keep| keep).count();
+        assert!((50..150).contains(&kept), "kept {kept} users out of 200");
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+Configuration-driven setup for a [`Tracker`].
+[`TrackerSettings`] can be deserialized from any serde format (for instance,
+a section of your application's TOML config file), or read from `SNOWPLOW_`
+environment variables.
+
+```no_run
+use snowplow_tracker::{Tracker, TrackerSettings};
+
+# fn example() -> Result<(), Box<dyn std::error::Error>> {
+let settings: TrackerSettings = serde_json::from_str(
+    r#"{
+        "collector_url": "https://collector.example.com/com.snowplowanalytics.snowplow/tp2",
+        "namespace": "ns",
+        "app_id": "my-app",
+        "platform": "srv",
+        "max_attempts": 3,
+        "compression": "gzip"
+    }"#,
+)?;
+let tracker = Tracker::from_settings(settings);
+
+// Or, equivalently, with SNOWPLOW_COLLECTOR_URL etc
+let tracker = Tracker::from_env()?;
+# Ok(())
+# }
+```
+*/
+
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+use reqwest::Url;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::emitter::{Compression, Emitter, RetryPolicy};
+use crate::payload::Platform;
+use crate::tracker::{Tracker, TrackerConfig};
+
+/// The prefix of the environment variables read by
+/// [`TrackerSettings::from_env`]
+pub const ENV_PREFIX: &str = "SNOWPLOW_";
+
+/// Everything needed to create a [`Tracker`] and its [`Emitter`]. Only the
+/// collector URL, namespace and app ID are required; everything else has a
+/// default.
+///
+/// When read from the environment, each field is read from the variable with
+/// the same name in upper case, prefixed with `SNOWPLOW_`; for instance,
+/// `SNOWPLOW_COLLECTOR_URL` or `SNOWPLOW_MAX_BATCH_SIZE`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrackerSettings {
+    /// The full collector URL, including the path (usually
+    /// `/com.snowplowanalytics.snowplow/tp2`)
+    pub collector_url: Url,
+
+    /// The tracker namespace
+    pub namespace: String,
+
+    /// The application ID
+    pub app_id: String,
+
+    /// The platform, like `"pc"` or `"srv"`. Defaults to `"app"`.
+    #[serde(default)]
+    pub platform: Platform,
+
+    /// The maximum number of events sent in a single request. By default,
+    /// there's no limit.
+    #[serde(default)]
+    pub max_batch_size: Option<usize>,
+
+    /// The maximum number of attempts for each request. Defaults to 1, which
+    /// means requests aren't retried.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+
+    /// The delay before the first retry, in milliseconds. Defaults to 500.
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    /// The longest delay between retries, in milliseconds. Defaults to
+    /// 30000.
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+
+    /// How request bodies are compressed, either `"none"` or `"gzip"`.
+    /// Defaults to `"none"`.
+    #[serde(default)]
+    pub compression: Compression,
+}
+
+fn default_max_attempts() -> u32 {
+    1
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+/// An error reading [`TrackerSettings`] from the environment
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum SettingsError {
+    /// A required environment variable wasn't set
+    #[error("Missing environment variable {0}")]
+    Missing(String),
+
+    /// An environment variable was set, but its value was invalid
+    #[error("Invalid value for environment variable {name}: {reason}")]
+    Invalid {
+        /// The name of the variable
+        name: String,
+
+        /// What was wrong with it
+        reason: String,
+    },
+}
+
+impl TrackerSettings {
+    /// Read settings from `SNOWPLOW_` environment variables. See
+    /// [`TrackerSettings`] for details.
+    pub fn from_env() -> Result<Self, SettingsError> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Read settings from some source of variables, like the environment
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Result<Self, SettingsError> {
+        let var = |field: &str| {
+            let name = format!("{ENV_PREFIX}{}", field.to_ascii_uppercase());
+            let value = get(&name).filter(|value| !value.is_empty());
+            (name, value)
+        };
+
+        Ok(Self {
+            collector_url: required_var(var("collector_url"))?,
+            namespace: required_var(var("namespace"))?,
+            app_id: required_var(var("app_id"))?,
+            platform: parse_var(var("platform"))?.unwrap_or_default(),
+            max_batch_size: parse_var(var("max_batch_size"))?,
+            max_attempts: parse_var(var("max_attempts"))?.unwrap_or_else(default_max_attempts),
+            initial_backoff_ms: parse_var(var("initial_backoff_ms"))?
+                .unwrap_or_else(default_initial_backoff_ms),
+            max_backoff_ms: parse_var(var("max_backoff_ms"))?
+                .unwrap_or_else(default_max_backoff_ms),
+            compression: parse_var(var("compression"))?.unwrap_or_default(),
+        })
+    }
+
+    /// The [`RetryPolicy`] described by these settings
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts,
+            initial_backoff: Duration::from_millis(self.initial_backoff_ms),
+            max_backoff: Duration::from_millis(self.max_backoff_ms),
+        }
+    }
+
+    /// Create an [`Emitter`] with these settings, using the given client
+    pub fn emitter(&self, client: reqwest::Client) -> Emitter {
+        let emitter = Emitter::new(self.collector_url.clone(), client)
+            .with_retry_policy(self.retry_policy())
+            .with_compression(self.compression);
+
+        match self.max_batch_size {
+            Some(max_batch_size) => emitter.with_max_batch_size(max_batch_size),
+            None => emitter,
+        }
+    }
+
+    /// The [`TrackerConfig`] described by these settings
+    pub fn tracker_config(&self) -> TrackerConfig {
+        TrackerConfig {
+            namespace: self.namespace.clone().into(),
+            platform: self.platform,
+            app_id: self.app_id.clone(),
+        }
+    }
+}
+
+/// Parse an environment variable, given its name and (if it's set) its value
+fn parse_var<T>((name, value): (String, Option<String>)) -> Result<Option<T>, SettingsError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .map(|value| value.parse::<T>())
+        .transpose()
+        .map_err(|err| SettingsError::Invalid {
+            name,
+            reason: err.to_string(),
+        })
+}
+
+/// Parse an environment variable that must be set
+fn required_var<T>((name, value): (String, Option<String>)) -> Result<T, SettingsError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match value {
+        None => Err(SettingsError::Missing(name)),
+        Some(value) => parse_var((name, Some(value))).map(|value| value.expect("value is set")),
+    }
+}
+
+impl Tracker {
+    /// Create a new tracker from some [`TrackerSettings`]
+    pub fn from_settings(settings: TrackerSettings) -> Self {
+        Self::new(
+            settings.emitter(reqwest::Client::new()),
+            settings.tracker_config(),
+        )
+    }
+
+    /// Create a new tracker from `SNOWPLOW_` environment variables. See
+    /// [`TrackerSettings`] for details.
+    pub fn from_env() -> Result<Self, SettingsError> {
+        TrackerSettings::from_env().map(Self::from_settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::{SettingsError, TrackerSettings};
+    use crate::emitter::{Compression, RetryPolicy};
+    use crate::Platform;
+
+    fn from_vars(vars: &[(&str, &str)]) -> Result<TrackerSettings, SettingsError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect();
+
+        TrackerSettings::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let settings: TrackerSettings = serde_json::from_value(json!({
+            "collector_url": "http://localhost:9090/com.snowplowanalytics.snowplow/tp2",
+            "namespace": "ns",
+            "app_id": "app",
+            "platform": "srv",
+            "max_batch_size": 10,
+            "max_attempts": 3,
+            "compression": "gzip",
+        }))
+        .unwrap();
+
+        assert_eq!(settings.platform, Platform::ServerSide);
+        assert_eq!(settings.max_batch_size, Some(10));
+        assert_eq!(settings.compression, Compression::Gzip);
+        assert_eq!(
+            settings.retry_policy(),
+            RetryPolicy {
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(500),
+                max_backoff: Duration::from_secs(30),
+            }
+        );
+        assert_eq!(settings.tracker_config().namespace, "ns");
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let settings = from_vars(&[
+            (
+                "SNOWPLOW_COLLECTOR_URL",
+                "http://localhost:9090/com.snowplowanalytics.snowplow/tp2",
+            ),
+            ("SNOWPLOW_NAMESPACE", "ns"),
+            ("SNOWPLOW_APP_ID", "app"),
+            ("SNOWPLOW_MAX_ATTEMPTS", "5"),
+            ("SNOWPLOW_PLATFORM", ""),
+        ])
+        .unwrap();
+
+        assert_eq!(settings.app_id, "app");
+        assert_eq!(settings.platform, Platform::App);
+        assert_eq!(settings.max_attempts, 5);
+        assert_eq!(settings.max_batch_size, None);
+
+        assert_eq!(
+            from_vars(&[("SNOWPLOW_NAMESPACE", "ns")]),
+            Err(SettingsError::Missing("SNOWPLOW_COLLECTOR_URL".to_owned()))
+        );
+
+        let error = from_vars(&[
+            (
+                "SNOWPLOW_COLLECTOR_URL",
+                "http://localhost:9090/com.snowplowanalytics.snowplow/tp2",
+            ),
+            ("SNOWPLOW_NAMESPACE", "ns"),
+            ("SNOWPLOW_APP_ID", "app"),
+            ("SNOWPLOW_COMPRESSION", "zstd"),
+        ])
+        .unwrap_err();
+        assert!(
+            matches!(&error, SettingsError::Invalid { name, .. } if name == "SNOWPLOW_COMPRESSION")
+        );
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+/*!
+The [`Subject`] of tracked events: the user, device and session that the
+events are about. A [`Tracker`][crate::Tracker] attaches its current subject
+to every event it sends.
+*/
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// A screen or viewport resolution. Renders as `{width}x{height}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    /// Create a new resolution
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+}
+
+impl Display for Resolution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self { width, height } = *self;
+        write!(f, "{width}x{height}")
+    }
+}
+
+/// An error parsing a [`Resolution`] from a string
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("Invalid resolution {0:?}; expected {{width}}x{{height}}")]
+pub struct ResolutionParseError(String);
+
+impl FromStr for Resolution {
+    type Err = ResolutionParseError;
+
+    fn from_str(resolution: &str) -> Result<Self, Self::Err> {
+        let error = || ResolutionParseError(resolution.to_owned());
+        let (width, height) = resolution.split_once('x').ok_or_else(error)?;
+
+        Ok(Self {
+            width: width.parse().map_err(|_| error())?,
+            height: height.parse().map_err(|_| error())?,
+        })
+    }
+}
+
+/// Information about the user and device that events are being tracked
+/// for. All of the fields are optional; only the ones that are set will be
+/// attached to events.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Subject {
+    /// A business-defined user identifier, like a username or account ID.
+    /// Sent as `uid`.
+    pub user_id: Option<String>,
+
+    /// The IP address of the user's device. Sent as `ip`.
+    pub ip_address: Option<String>,
+
+    /// The user agent of the user's device or application. Sent as `ua`.
+    pub useragent: Option<String>,
+
+    /// The user's timezone, like `"Europe/London"`. Sent as `tz`.
+    pub timezone: Option<String>,
+
+    /// The user's language, like `"en-GB"`. Sent as `lang`.
+    pub language: Option<String>,
+
+    /// The resolution of the user's screen. Sent as `res`.
+    pub screen_resolution: Option<Resolution>,
+
+    /// A tracker-generated identifier for the device, persistent across
+    /// sessions. Sent as `duid`.
+    pub domain_user_id: Option<String>,
+
+    /// The identifier for this user set by the collector in a cookie. Sent as
+    /// `tnuid`.
+    pub network_user_id: Option<String>,
+
+    /// An identifier for the user's current session. Sent as `sid`.
+    pub session_id: Option<String>,
+
+    /// The number of sessions this user has had, including the current one.
+    /// Sent as `vid`.
+    pub session_index: Option<u32>,
+}
+
+impl Subject {
+    /// Remove everything from this subject that identifies the user: the
+    /// user ID, domain & network user IDs, session ID and IP address. This is
+    /// what the tracker sends when the
+    /// [`ConsentLevel`][crate::consent::ConsentLevel] is `Anonymous`.
+    pub fn anonymize(&mut self) {
+        self.user_id = None;
+        self.ip_address = None;
+        self.domain_user_id = None;
+        self.network_user_id = None;
+        self.session_id = None;
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+//! Test helpers shared between the unit tests of different modules.
+
+use std::sync::{Arc, Mutex};
+
+use reqwest::Url;
+use serde_json::Value as JsonValue;
+use tokio::io::{AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::field::{Field, Visit};
+use tracing::subscriber::DefaultGuard;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt as _};
+
+use crate::{Tracker, TrackerBuilder};
+
+/// A request received by a [`CaptureServer`]
+#[derive(Debug, Clone)]
+pub(crate) struct CapturedRequest {
+    /// The request headers, with lowercased names
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// The raw request body, for bodies that aren't UTF-8
+    pub raw_body: Vec<u8>,
+}
+
+impl CapturedRequest {
+    /// Get the value of a header, by its lowercase name
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An event received by a [`CaptureServer`], with its self-describing event
+/// and context entities decoded
+#[derive(Debug, Clone)]
+pub(crate) struct CapturedEvent {
+    /// The raw tracker protocol parameters of the event
+    pub params: JsonValue,
+    /// The self-describing event (the `data` of `ue_pr`), for `ue` events
+    pub payload: Option<JsonValue>,
+    /// The context entities attached to the event (the `data` of `co`)
+    pub contexts: Vec<JsonValue>,
+}
+
+impl CapturedEvent {
+    fn decode(params: JsonValue) -> Self {
+        let decode = |field: &str| {
+            params[field].as_str().map(|json| {
+                let envelope: JsonValue = serde_json::from_str(json).expect("invalid JSON field");
+                envelope["data"].clone()
+            })
+        };
+
+        let payload = decode("ue_pr");
+        let contexts = match decode("co") {
+            Some(JsonValue::Array(contexts)) => contexts,
+            _ => Vec::new(),
+        };
+
+        Self {
+            params,
+            payload,
+            contexts,
+        }
+    }
+
+    /// The name of the self-describing event's schema, like `"play_event"`
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.payload.as_ref()?["schema"].as_str()?.split('/').nth(1)
+    }
+}
+
+/// A minimal HTTP server that records every request it receives and responds
+/// to all of them with a fixed status code.
+///
+/// Tests that only need a working collector use [`Micro`][crate::micro::Micro]
+/// instead. This is for the tests that Micro can't serve: it always accepts
+/// events, and only reports the events it parsed, not the raw requests that
+/// carried them.
+pub(crate) struct CaptureServer {
+    url: Url,
+    requests: Arc<Mutex<Vec<CapturedRequest>>>,
+}
+
+impl CaptureServer {
+    /// Start a server on an ephemeral localhost port that responds to every
+    /// request with `status`.
+    pub(crate) async fn start(status: u16) -> Self {
+        Self::start_with_headers(status, &[]).await
+    }
+
+    /// Start a server that responds to every request with `status` and the
+    /// given extra headers.
+    pub(crate) async fn start_with_headers(status: u16, headers: &[(&str, &str)]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind test server");
+        let address = listener.local_addr().expect("no local address");
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let headers: Arc<str> = headers
+            .iter()
+            .map(|(name, value)| format!("{name}: {value}\r\n"))
+            .collect::<String>()
+            .into();
+
+        tokio::spawn({
+            let requests = requests.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve_connection(
+                        stream,
+                        status,
+                        headers.clone(),
+                        requests.clone(),
+                    ));
+                }
+            }
+        });
+
+        Self {
+            url: format!("http://{address}/com.snowplowanalytics.snowplow/tp2")
+                .parse()
+                .expect("valid URL"),
+            requests,
+        }
+    }
+
+    /// The full collector URL of this server
+    pub(crate) fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    /// All of the requests received so far
+    pub(crate) fn requests(&self) -> Vec<CapturedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Wait until at least `count` requests have been received. This yields
+    /// rather than sleeping, so that it works with a paused clock without
+    /// letting the clock auto-advance.
+    pub(crate) async fn wait_for_requests(&self, count: usize) {
+        while self.requests.lock().unwrap().len() < count {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// All of the request bodies received so far
+    pub(crate) fn bodies(&self) -> Vec<String> {
+        self.requests()
+            .into_iter()
+            .map(|request| request.body)
+            .collect()
+    }
+
+    /// All of the events received so far, from every request, in order
+    pub(crate) fn events(&self) -> Vec<CapturedEvent> {
+        self.bodies()
+            .iter()
+            .flat_map(|body| {
+                let mut body: JsonValue = serde_json::from_str(body).expect("invalid request body");
+                match body["data"].take() {
+                    JsonValue::Array(events) => events,
+                    _ => panic!("request body has no events: {body}"),
+                }
+            })
+            .map(CapturedEvent::decode)
+            .collect()
+    }
+
+    /// A tracker builder that sends events to this server, with an app ID
+    /// of `"app"`
+    pub(crate) fn tracker_builder(&self) -> TrackerBuilder {
+        Tracker::builder()
+            .collector(self.url.as_str())
+            .app_id("app")
+    }
+
+    /// A tracker that sends events to this server; see
+    /// [`tracker_builder`][Self::tracker_builder]
+    pub(crate) fn tracker(&self) -> Tracker {
+        self.tracker_builder()
+            .build()
+            .expect("the test tracker is valid")
+    }
+}
+
+async fn serve_connection(
+    stream: TcpStream,
+    status: u16,
+    response_headers: Arc<str>,
+    requests: Arc<Mutex<Vec<CapturedRequest>>>,
+) {
+    let mut stream = BufReader::new(stream);
+
+    loop {
+        let mut content_length = 0;
+        let mut headers = Vec::new();
+        let mut line = String::new();
+
+        // Request line
+        match stream.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+
+        // Headers
+        loop {
+            line.clear();
+            match stream.read_line(&mut line).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                let name = name.trim().to_ascii_lowercase();
+                let value = value.trim().to_owned();
+
+                if name == "content-length" {
+                    content_length = value.parse().unwrap_or(0);
+                }
+
+                headers.push((name, value));
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        if stream.read_exact(&mut body).await.is_err() {
+            return;
+        }
+        requests.lock().unwrap().push(CapturedRequest {
+            headers,
+            body: String::from_utf8_lossy(&body).into_owned(),
+            raw_body: body,
+        });
+
+        let response =
+            format!("HTTP/1.1 {status} Test\r\n{response_headers}content-length: 0\r\n\r\n");
+        if stream
+            .get_mut()
+            .write_all(response.as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Captures the messages of all of the `tracing` events emitted on the
+/// current thread, for as long as it's alive.
+pub(crate) struct CapturedLogs {
+    logs: Arc<Mutex<Vec<(Level, String)>>>,
+    _guard: DefaultGuard,
+}
+
+impl CapturedLogs {
+    pub(crate) fn start() -> Self {
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(CaptureLayer(logs.clone()));
+
+        Self {
+            logs,
+            _guard: tracing::subscriber::set_default(subscriber),
+        }
+    }
+
+    /// All of the messages logged so far, with their levels
+    pub(crate) fn messages(&self) -> Vec<(Level, String)> {
+        self.logs.lock().unwrap().clone()
+    }
+}
+
+struct CaptureLayer(Arc<Mutex<Vec<(Level, String)>>>);
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        struct MessageVisitor(String);
+
+        impl Visit for MessageVisitor {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    self.0 = format!("{value:?}");
+                }
+            }
+        }
+
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+        self.0
+            .lock()
+            .unwrap()
+            .push((*event.metadata().level(), visitor.0));
+    }
+}
@@ -16,14 +16,33 @@ The types in this module are your main entry point to this library.
 
 use reqwest::Url;
 use serde::Serialize;
+use std::borrow::Cow;
 use std::fmt::Debug;
+use std::fs::OpenOptions;
+use std::io::{self, Write as _};
+use std::path::Path;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use thiserror::Error;
 use uuid::Uuid;
 
 use crate::{
-    emitter::Emitter,
-    payload::{EventType, HasSchema, PayloadWrapper, Platform, SnowplowEvent, SnowplowTimestamp},
-    util::JsonString,
+    consent::{ConsentDocument, ConsentGranted, ConsentLevel, ConsentWithdrawn},
+    deep_link::{is_screen_view, DeepLink, DeepLinkReceived},
+    ecommerce::{EcommerceAction, Transaction},
+    emitter::{Emitter, SendError, SendOptions},
+    lifecycle::{ApplicationInstall, InstallError, LifecycleState},
+    payload::{
+        Contexts, Envelope, EventData, HasSchema, Platform, SelfDescribingJson, SnowplowEvent,
+        SnowplowTimestamp, TransactionItemParams, TransactionParams,
+    },
+    pii::PiiPolicy,
+    platform::PlatformContext,
+    plugin::{PluginAction, SendOutcome, TrackerPlugin},
+    sampling::Sampler,
+    subject::Subject,
+    util::{JsonString, Stringify},
 };
 
 /// An error encountered when submitting an event for tracking. Generally
@@ -34,7 +53,38 @@ pub enum TrackError {
     /// or there was a TCP error. This variant does *not* include HTTP error
     /// codes.
     #[error("Unexpected error during HTTP request (not an error code)")]
-    HttpConnection(#[from] reqwest::Error),
+    HttpConnection(#[source] reqwest::Error),
+
+    /// The collector responded with an HTTP error code
+    #[error("Collector responded with an HTTP error code")]
+    HttpStatus(#[source] reqwest::Error),
+
+    /// The emitter's [circuit breaker][crate::circuit_breaker] is open,
+    /// because the collector has been failing, so the event wasn't sent
+    #[error("The collector is unavailable; the circuit breaker is open")]
+    CollectorUnavailable,
+
+    /// An event payload couldn't be serialized to JSON
+    #[error("Failed to serialize event payload")]
+    Serialization(#[from] serde_json::Error),
+}
+
+impl From<reqwest::Error> for TrackError {
+    fn from(err: reqwest::Error) -> Self {
+        match err.status() {
+            Some(_) => TrackError::HttpStatus(err),
+            None => TrackError::HttpConnection(err),
+        }
+    }
+}
+
+impl From<SendError> for TrackError {
+    fn from(err: SendError) -> Self {
+        match err {
+            SendError::Http(err) => err.into(),
+            SendError::CircuitOpen => TrackError::CollectorUnavailable,
+        }
+    }
 }
 
 /// The tracker ID, corresponding to the `tv` field of a snowplow event.
@@ -50,7 +100,7 @@ const TRACKER_ID: &str = concat!("rust-fork-", env!("CARGO_PKG_VERSION"));
 #[derive(Debug, Clone)]
 pub struct TrackerConfig {
     /// The namespace this tracker will use
-    pub namespace: &'static str,
+    pub namespace: Cow<'static, str>,
 
     /// The platform we're operating on. If unsure, App is a good default.
     pub platform: Platform,
@@ -65,10 +115,30 @@ pub struct TrackerConfig {
 /// out of the [`TrackedEvent`] objects you pass into it. It takes care of
 /// stuff like event type, timestamps, app & tracker IDs, etc.
 pub struct Tracker {
-    /// Emitter used to send events to the Collector
-    emitter: Emitter,
+    /// Emitter used to send events to the Collector, which may be shared with
+    /// other trackers
+    emitter: Arc<Emitter>,
     /// Additional tracker config
     config: TrackerConfig,
+    /// Plugins that are run on every event, in order
+    plugins: Vec<Box<dyn TrackerPlugin>>,
+    /// The user & device that events are being tracked for. This can change
+    /// while the tracker is in use (for instance, when a user logs in)
+    subject: RwLock<Subject>,
+    /// Policy for pseudonymizing PII before it's sent
+    pii_policy: Option<PiiPolicy>,
+    /// The current [`ConsentLevel`], stored as a `u8`
+    consent: AtomicU8,
+    /// Sampler deciding which events are kept
+    sampler: Option<Sampler>,
+    /// Context entities attached to every event
+    global_contexts: Vec<SelfDescribingJson>,
+    /// Platform entities attached to every event, after the global contexts
+    platform_contexts: Vec<SelfDescribingJson>,
+    /// Foreground & background state, for lifecycle events
+    lifecycle: Mutex<LifecycleState>,
+    /// The latest deep link, waiting to be attached to the next screen view
+    deep_link: Mutex<Option<DeepLink>>,
 }
 
 impl Tracker {
@@ -79,7 +149,7 @@ impl Tracker {
     /// snowplow trackers, we as you to include the full path, in case you want
     /// to change it for your specific collector configuration.
     pub fn build(
-        namespace: &'static str,
+        namespace: impl Into<Cow<'static, str>>,
         app_id: String,
         platform: Platform,
         url: Url,
@@ -88,16 +158,278 @@ impl Tracker {
         Self::new(
             Emitter::new(url, client),
             TrackerConfig {
-                namespace,
+                namespace: namespace.into(),
                 platform,
                 app_id,
             },
         )
     }
 
-    /// Create a new tracker
-    pub fn new(emitter: Emitter, config: TrackerConfig) -> Tracker {
-        Tracker { emitter, config }
+    /// Create a new tracker. The emitter can be given either by value, or as
+    /// an `Arc<Emitter>` to share it between several trackers (for instance,
+    /// trackers with different namespaces in the same application).
+    pub fn new(emitter: impl Into<Arc<Emitter>>, config: TrackerConfig) -> Tracker {
+        Tracker {
+            emitter: emitter.into(),
+            config,
+            plugins: Vec::new(),
+            subject: RwLock::new(Subject::default()),
+            pii_policy: None,
+            consent: AtomicU8::new(ConsentLevel::Full as u8),
+            sampler: None,
+            global_contexts: Vec::new(),
+            platform_contexts: Vec::new(),
+            lifecycle: Mutex::new(LifecycleState::default()),
+            deep_link: Mutex::new(None),
+        }
+    }
+
+    /// Get the [`Emitter`] used by this tracker; for instance, to
+    /// [`subscribe`][Emitter::subscribe] to event outcomes.
+    pub fn emitter(&self) -> &Emitter {
+        &self.emitter
+    }
+
+    /// Get a shared handle to the [`Emitter`] used by this tracker, to
+    /// create other trackers that send events through it.
+    pub fn shared_emitter(&self) -> Arc<Emitter> {
+        self.emitter.clone()
+    }
+
+    /// Get the namespace of this tracker
+    pub fn namespace(&self) -> &str {
+        &self.config.namespace
+    }
+
+    /// Get a copy of the tracker's current [`Subject`]
+    pub fn subject(&self) -> Subject {
+        self.subject
+            .read()
+            .unwrap_or_else(|poison| poison.into_inner())
+            .clone()
+    }
+
+    /// Replace the tracker's [`Subject`]. The new subject will be attached to
+    /// all events tracked from now on.
+    pub fn set_subject(&self, subject: Subject) {
+        *self
+            .subject
+            .write()
+            .unwrap_or_else(|poison| poison.into_inner()) = subject;
+    }
+
+    /// Get the tracker's current [`ConsentLevel`]
+    pub fn consent(&self) -> ConsentLevel {
+        ConsentLevel::from_u8(self.consent.load(Ordering::Acquire))
+    }
+
+    /// Change the tracker's [`ConsentLevel`]. This takes effect for all
+    /// events tracked from now on. Note that this doesn't itself track
+    /// anything; see [`grant_consent`][Self::grant_consent] and
+    /// [`withdraw_consent`][Self::withdraw_consent] for that.
+    pub fn set_consent(&self, level: ConsentLevel) {
+        self.consent.store(level as u8, Ordering::Release)
+    }
+
+    /// Change the tracker's [`ConsentLevel`], then track a `consent_granted`
+    /// event with the given documents attached.
+    pub async fn grant_consent(
+        &self,
+        level: ConsentLevel,
+        event: ConsentGranted,
+        documents: &[ConsentDocument],
+    ) -> Result<(), TrackError> {
+        self.set_consent(level);
+
+        let event = documents
+            .iter()
+            .fold(TrackedEvent::new(event), |event, document| {
+                event.with_context(document.into())
+            });
+
+        self.track(event).await
+    }
+
+    /// Track a `consent_withdrawn` event with the given documents attached,
+    /// then change the tracker's [`ConsentLevel`]. The event is tracked under
+    /// the previous consent level, so that the withdrawal itself is recorded
+    /// even if the new level is [`ConsentLevel::None`]. The consent level is
+    /// changed even if tracking the event fails.
+    pub async fn withdraw_consent(
+        &self,
+        level: ConsentLevel,
+        event: ConsentWithdrawn,
+        documents: &[ConsentDocument],
+    ) -> Result<(), TrackError> {
+        let event = documents
+            .iter()
+            .fold(TrackedEvent::new(event), |event, document| {
+                event.with_context(document.into())
+            });
+
+        let result = self.track(event).await;
+        self.set_consent(level);
+        result
+    }
+
+    /// Install a [`Sampler`], which will be consulted for every event
+    /// before it's sent.
+    pub fn set_sampler(&mut self, sampler: Sampler) {
+        self.sampler = Some(sampler)
+    }
+
+    /// Get the tracker's [`Sampler`], if any; for instance, to see how many
+    /// events it has dropped.
+    pub fn sampler(&self) -> Option<&Sampler> {
+        self.sampler.as_ref()
+    }
+
+    /// Install a [`PiiPolicy`], which will be used to pseudonymize the
+    /// payload, contexts and subject of every event before it's sent.
+    pub fn set_pii_policy(&mut self, policy: PiiPolicy) {
+        self.pii_policy = Some(policy)
+    }
+
+    /// Register a [`TrackerPlugin`] with this tracker. Plugins are run in the
+    /// order they're registered.
+    pub fn add_plugin(&mut self, plugin: impl TrackerPlugin + 'static) {
+        self.add_boxed_plugin(Box::new(plugin))
+    }
+
+    /// Register an already-boxed [`TrackerPlugin`], without boxing it again
+    pub(crate) fn add_boxed_plugin(&mut self, plugin: Box<dyn TrackerPlugin>) {
+        self.plugins.push(plugin)
+    }
+
+    /// Add a context entity that will be attached to every event tracked
+    /// from now on, after the event's own contexts.
+    pub fn add_global_context(&mut self, context: SelfDescribingJson) {
+        self.global_contexts.push(context)
+    }
+
+    /// Set which [`PlatformContext`] entities are attached to every event
+    /// tracked from now on, after the global contexts. This replaces any
+    /// platform contexts that were set before. The entities are collected
+    /// immediately.
+    pub fn set_platform_contexts(&mut self, contexts: impl IntoIterator<Item = PlatformContext>) {
+        self.platform_contexts = contexts
+            .into_iter()
+            .map(|context| context.collect())
+            .collect();
+    }
+
+    /// The global and platform contexts, which are attached to every event
+    fn tracker_contexts(&self) -> impl Iterator<Item = &SelfDescribingJson> {
+        self.global_contexts.iter().chain(&self.platform_contexts)
+    }
+
+    /// Start a new session when the app comes back to the
+    /// [foreground][Self::app_foreground] after spending at least `timeout`
+    /// in the [background][Self::app_background]. A new session gets a new
+    /// random session ID, and the next session index, on the tracker's
+    /// [`Subject`].
+    pub fn set_background_session_timeout(&mut self, timeout: Duration) {
+        self.lifecycle
+            .get_mut()
+            .unwrap_or_else(|poison| poison.into_inner())
+            .set_session_timeout(timeout)
+    }
+
+    /// Track an `application_install` event, unless one was already tracked.
+    /// A marker file is created at `state_path` to remember that the install
+    /// was tracked, so it should be somewhere that persists across restarts
+    /// of the app, in a directory that already exists.
+    ///
+    /// Returns whether the event was tracked. If tracking it fails, or the
+    /// event isn't sent (because there's no consent to track it, or it was
+    /// dropped by the sampler or a plugin), the marker is removed again, so
+    /// that it's tried again next time.
+    pub async fn track_install_once(
+        &self,
+        state_path: impl AsRef<Path>,
+    ) -> Result<bool, InstallError> {
+        let path = state_path.as_ref();
+        let marker_error = |source| InstallError::Marker {
+            path: path.to_owned(),
+            source,
+        };
+
+        // Creating the marker first means that concurrent calls can't both
+        // track the install
+        let mut marker = match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(marker) => marker,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+            Err(err) => return Err(marker_error(err)),
+        };
+
+        let id = Uuid::new_v4();
+        let event = TrackedEvent {
+            id: Some(id),
+            ..TrackedEvent::new(ApplicationInstall {})
+        };
+
+        let result = match self.track_and_count([event]).await {
+            Ok(0) => {
+                tracing::debug!("install event was dropped; it will be tracked next time");
+                Ok(false)
+            }
+            Ok(_) => {
+                return writeln!(marker, "{id}")
+                    .map(|()| true)
+                    .map_err(marker_error)
+            }
+            Err(err) => Err(err.into()),
+        };
+
+        drop(marker);
+        if let Err(err) = std::fs::remove_file(path) {
+            tracing::warn!(error = %err, "failed to remove install marker");
+        }
+        result
+    }
+
+    /// Track an `application_foreground` event. If the app was in the
+    /// background for longer than the
+    /// [background session timeout][Self::set_background_session_timeout],
+    /// a new session is started first.
+    pub async fn app_foreground(&self) -> Result<(), TrackError> {
+        let event = {
+            let mut subject = self
+                .subject
+                .write()
+                .unwrap_or_else(|poison| poison.into_inner());
+            self.lifecycle
+                .lock()
+                .unwrap_or_else(|poison| poison.into_inner())
+                .foreground(&mut subject)
+        };
+
+        self.track(TrackedEvent::new(event)).await
+    }
+
+    /// Track an `application_background` event
+    pub async fn app_background(&self) -> Result<(), TrackError> {
+        let event = self
+            .lifecycle
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner())
+            .background();
+
+        self.track(TrackedEvent::new(event)).await
+    }
+
+    /// Track a `deep_link_received` event for a [`DeepLink`] the app was
+    /// opened from. The deep link is also attached as a context entity to the
+    /// next screen view tracked, replacing any earlier deep link that hasn't
+    /// been attached yet.
+    pub async fn deep_link_received(&self, deep_link: DeepLink) -> Result<(), TrackError> {
+        *self
+            .deep_link
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner()) = Some(deep_link.clone());
+
+        self.track(TrackedEvent::new(DeepLinkReceived(deep_link)))
+            .await
     }
 
     /// Tracks a Snowplow event and send it to the Snowplow collector.
@@ -113,27 +445,313 @@ impl Tracker {
         &self,
         events: impl IntoIterator<Item = TrackedEvent<Payload>>,
     ) -> Result<(), TrackError> {
-        let now = SnowplowTimestamp::now();
+        self.track_and_count(events).await.map(|_sent| ())
+    }
+
+    /// Track a batch of events, returning the number of events that were
+    /// sent; that is, the ones that weren't dropped for lack of consent, by
+    /// the sampler, or by a plugin.
+    #[tracing::instrument(
+        name = "track_batch",
+        level = "debug",
+        skip_all,
+        fields(namespace = %self.config.namespace)
+    )]
+    async fn track_and_count<Payload: HasSchema + Serialize>(
+        &self,
+        events: impl IntoIterator<Item = TrackedEvent<Payload>>,
+    ) -> Result<usize, TrackError> {
+        let events: Vec<_> = events.into_iter().collect();
+        let Some(batch) = self.begin_batch(events.len()) else {
+            return Ok(0);
+        };
+
+        let mut prepared = Vec::new();
+        let mut dropped = 0;
+        // The pending deep link is attached to the first screen view that's
+        // kept, but only taken once the whole batch has been prepared
+        let mut attached_deep_link = None;
+        for event in events {
+            if let Some(sampler) = &self.sampler {
+                let schema = event.payload.schema();
+                if !sampler.should_keep(&schema, batch.subject.user_id.as_deref()) {
+                    tracing::trace!(%schema, "event dropped by sampler");
+                    dropped += 1;
+                    continue;
+                }
+            }
+
+            let mut event = event.into_self_describing().map_err(|err| {
+                tracing::warn!(error = %err, "failed to serialize event payload");
+                err
+            })?;
+
+            let deep_link = match attached_deep_link {
+                None if is_screen_view(&event.payload.schema) => self
+                    .deep_link
+                    .lock()
+                    .unwrap_or_else(|poison| poison.into_inner())
+                    .clone(),
+                _ => None,
+            };
+            if let Some(deep_link) = &deep_link {
+                event
+                    .contexts
+                    .push(SelfDescribingJson::from_payload(deep_link)?);
+            }
+
+            event.contexts.extend(self.tracker_contexts().cloned());
+
+            if self
+                .plugins
+                .iter()
+                .all(|plugin| plugin.before_track(&mut event) == PluginAction::Keep)
+            {
+                if deep_link.is_some() {
+                    attached_deep_link = deep_link;
+                }
+                prepared.push(PreparedEvent {
+                    data: EventData::self_describing(event.payload),
+                    id: event.id,
+                    timestamp: event.timestamp,
+                    contexts: event.contexts,
+                });
+            } else {
+                tracing::trace!(schema = %event.payload.schema, "event dropped by plugin");
+                dropped += 1;
+            }
+        }
+
+        if let Some(deep_link) = attached_deep_link {
+            let mut pending = self
+                .deep_link
+                .lock()
+                .unwrap_or_else(|poison| poison.into_inner());
+
+            // Unless a newer deep link was received in the meantime
+            if pending.as_ref() == Some(&deep_link) {
+                *pending = None;
+            }
+        }
+
+        tracing::debug!(events = prepared.len(), dropped, "prepared events");
+        self.emitter.record_dropped(dropped);
+        self.send_prepared(batch, prepared).await
+    }
+
+    /// Track a legacy ecommerce [`Transaction`]. This sends a transaction
+    /// event, followed by an event for each of its items, in a single batch.
+    ///
+    /// These events aren't self-describing, so they aren't sampled or seen by
+    /// [`TrackerPlugin::before_track`], but they're otherwise handled like any
+    /// other event; for instance, global contexts are attached to each of
+    /// them.
+    pub async fn track_transaction(&self, transaction: &Transaction) -> Result<(), TrackError> {
+        let Some(batch) = self.begin_batch(1 + transaction.items.len()) else {
+            return Ok(());
+        };
+
+        let order_id = Cow::Borrowed(transaction.order_id.as_str());
+        let currency = transaction.currency.as_deref().map(Cow::Borrowed);
+
+        let transaction_event = EventData::Transaction(TransactionParams {
+            order_id: order_id.clone(),
+            affiliation: transaction.affiliation.as_deref().map(Cow::Borrowed),
+            total: Stringify(transaction.total),
+            tax: transaction.tax.map(Stringify),
+            shipping: transaction.shipping.map(Stringify),
+            city: transaction.city.as_deref().map(Cow::Borrowed),
+            state: transaction.state.as_deref().map(Cow::Borrowed),
+            country: transaction.country.as_deref().map(Cow::Borrowed),
+            currency: currency.clone(),
+        });
+
+        let item_events = transaction.items.iter().map(|item| {
+            EventData::TransactionItem(TransactionItemParams {
+                order_id: order_id.clone(),
+                sku: Cow::Borrowed(&item.sku),
+                name: item.name.as_deref().map(Cow::Borrowed),
+                category: item.category.as_deref().map(Cow::Borrowed),
+                price: Stringify(item.price),
+                quantity: Stringify(item.quantity),
+                currency: currency.clone(),
+            })
+        });
+
+        let prepared = std::iter::once(transaction_event)
+            .chain(item_events)
+            .map(|data| PreparedEvent {
+                data,
+                id: None,
+                timestamp: None,
+                contexts: self.tracker_contexts().cloned().collect(),
+            })
+            .collect();
+
+        self.send_prepared(batch, prepared).await.map(|_sent| ())
+    }
+
+    /// Track an [`EcommerceAction`], with its entities attached as context
+    /// entities
+    pub async fn track_ecommerce_action(&self, action: EcommerceAction) -> Result<(), TrackError> {
+        self.track(action.into_tracked_event()).await
+    }
+
+    /// Start tracking a batch of `count` events: check the consent level,
+    /// and get the subject the events are tracked for. If there's no consent
+    /// to track anything, the events are counted as dropped, and `None` is
+    /// returned.
+    fn begin_batch(&self, count: usize) -> Option<Batch> {
+        let consent = self.consent();
+        if consent == ConsentLevel::None {
+            tracing::debug!("consent level is None; not tracking anything");
+            self.emitter.record_dropped(count);
+            return None;
+        }
+
+        Some(Batch {
+            consent,
+            subject: self.event_subject(consent),
+            now: SnowplowTimestamp::now(),
+        })
+    }
+
+    /// Get the subject to attach to events tracked under the given consent
+    /// level. The emitter's network user ID is used if the subject doesn't
+    /// have one of its own.
+    fn event_subject(&self, consent: ConsentLevel) -> Subject {
+        let mut subject = self.subject();
+        if subject.network_user_id.is_none() {
+            subject.network_user_id = self.emitter.network_user_id();
+        }
+
+        if consent == ConsentLevel::Anonymous {
+            subject.anonymize();
+        }
+        subject
+    }
+
+    /// Finish tracking a batch of events: pseudonymize any PII, build the
+    /// full events, and send them. Returns the number of events sent.
+    async fn send_prepared(
+        &self,
+        batch: Batch,
+        mut events: Vec<PreparedEvent<'_>>,
+    ) -> Result<usize, TrackError> {
+        if events.is_empty() {
+            return Ok(0);
+        }
 
-        let events = events.into_iter().map(|event| SnowplowEvent {
-            event_type: EventType::SelfDescribingEvent,
-            payload: JsonString(PayloadWrapper::new(event.payload)),
+        let Batch {
+            consent,
+            mut subject,
+            now,
+        } = batch;
+
+        if let Some(policy) = &self.pii_policy {
+            policy.apply_to_subject(&mut subject);
+
+            events
+                .iter_mut()
+                .flat_map(|event| {
+                    event
+                        .data
+                        .payload_mut()
+                        .into_iter()
+                        .chain(&mut event.contexts)
+                })
+                .for_each(|json| policy.apply(json));
+        }
+
+        let events: Vec<_> = events
+            .into_iter()
+            .map(|event| self.build_event(&subject, event, now))
+            .collect();
+
+        let sent = events.len();
+        self.send_built(events, consent).await.map(|()| sent)
+    }
+
+    /// Build a full event, with the tracker's config and the given subject
+    fn build_event<'a>(
+        &'a self,
+        subject: &'a Subject,
+        event: PreparedEvent<'a>,
+        now: SnowplowTimestamp,
+    ) -> SnowplowEvent<'a, SelfDescribingJson> {
+        let contexts = event.contexts;
+
+        SnowplowEvent {
+            data: event.data,
+            contexts: (!contexts.is_empty())
+                .then(|| JsonString(Envelope(contexts.into_iter().collect::<Contexts>()))),
             platform: self.config.platform,
-            app_id: &self.config.app_id,
-            tracker_id: TRACKER_ID,
-            namespace: self.config.namespace,
-            event_id: event.id,
+            app_id: Cow::Borrowed(&self.config.app_id),
+            tracker_id: Cow::Borrowed(TRACKER_ID),
+            namespace: Cow::Borrowed(&self.config.namespace),
+            user_id: subject.user_id.as_deref().map(Cow::Borrowed),
+            ip_address: subject.ip_address.as_deref().map(Cow::Borrowed),
+            useragent: subject.useragent.as_deref().map(Cow::Borrowed),
+            timezone: subject.timezone.as_deref().map(Cow::Borrowed),
+            language: subject.language.as_deref().map(Cow::Borrowed),
+            screen_resolution: subject.screen_resolution.map(Stringify),
+            domain_user_id: subject.domain_user_id.as_deref().map(Cow::Borrowed),
+            network_user_id: subject.network_user_id.as_deref().map(Cow::Borrowed),
+            session_id: subject.session_id.as_deref().map(Cow::Borrowed),
+            session_index: subject.session_index.map(Stringify),
+            event_id: Some(event.id.unwrap_or_else(Uuid::new_v4)),
             created_timestamp: event.timestamp.unwrap_or(now),
             sent_timestamp: now,
-        });
+        }
+    }
 
-        self.emitter
-            .track_events(events)
-            .await
-            .map_err(TrackError::HttpConnection)
+    /// Run the plugins on some built events, and send them
+    async fn send_built(
+        &self,
+        events: Vec<SnowplowEvent<'_, SelfDescribingJson>>,
+        consent: ConsentLevel,
+    ) -> Result<(), TrackError> {
+        for event in &events {
+            for plugin in &self.plugins {
+                plugin.after_build(event);
+            }
+        }
+
+        let event_ids: Vec<_> = events.iter().map(|event| event.event_id).collect();
+        let options = SendOptions {
+            anonymous: consent == ConsentLevel::Anonymous,
+        };
+        let result = self.emitter.send_events(events, options).await;
+
+        let outcome = SendOutcome {
+            event_ids: &event_ids,
+            result: result.as_ref().map(|_| ()),
+        };
+        for plugin in &self.plugins {
+            plugin.after_send(&outcome);
+        }
+
+        result.map_err(TrackError::from)
     }
 }
 
+/// The consent level, subject and time that a batch of events is tracked
+/// under; see [`Tracker::begin_batch`]
+struct Batch {
+    consent: ConsentLevel,
+    subject: Subject,
+    now: SnowplowTimestamp,
+}
+
+/// An event that's made it through the tracker's pipeline, and is ready to
+/// be built and sent; see [`Tracker::send_prepared`]
+struct PreparedEvent<'a> {
+    data: EventData<'a, SelfDescribingJson>,
+    id: Option<Uuid>,
+    timestamp: Option<SnowplowTimestamp>,
+    contexts: Vec<SelfDescribingJson>,
+}
+
 /// An event to be sent to the tracker. Mostly this is a vehicle for your
 /// Unstructured payload, but also allows you to include your own fields for
 /// the top-level snowplow event
@@ -148,9 +766,10 @@ pub struct TrackedEvent<T: HasSchema + Serialize> {
     /// schema ID corresponding to its layout.
     pub payload: T,
 
-    /// The event Uuid. If omitted, one will be generated by the snowplow
-    /// collector. You only need to populate this if your batching scheme might
-    /// retry sending events and risk duplication
+    /// The event Uuid. If omitted, a random one will be generated by the
+    /// tracker. You only need to populate this if your batching scheme might
+    /// retry sending events and risk duplication, or if you want to correlate
+    /// the event with its [`EventOutcome`][crate::emitter::EventOutcome]
     pub id: Option<Uuid>,
 
     /// The moment when this event occurred. If omitted, we will use the moment
@@ -158,7 +777,10 @@ pub struct TrackedEvent<T: HasSchema + Serialize> {
     /// your batching scheme imposes delay between when the event occurs and
     /// when it's tracked.
     pub timestamp: Option<SnowplowTimestamp>,
-    // TODO: Contexts
+
+    /// Context entities to attach to this event. Each entity is a
+    /// self-describing JSON object with its own schema.
+    pub contexts: Vec<SelfDescribingJson>,
 }
 
 impl<T: HasSchema + Serialize> TrackedEvent<T> {
@@ -168,6 +790,151 @@ impl<T: HasSchema + Serialize> TrackedEvent<T> {
             payload,
             id: None,
             timestamp: None,
+            contexts: Vec::new(),
         }
     }
+
+    /// Attach a context entity to this event
+    #[must_use]
+    pub fn with_context(mut self, context: SelfDescribingJson) -> Self {
+        self.contexts.push(context);
+        self
+    }
+
+    /// Convert this event's payload into a [`SelfDescribingJson`]. This is
+    /// the form in which events are seen by [`TrackerPlugin`]s.
+    pub fn into_self_describing(
+        self,
+    ) -> Result<TrackedEvent<SelfDescribingJson>, serde_json::Error> {
+        Ok(TrackedEvent {
+            payload: SelfDescribingJson::from_payload(&self.payload)?,
+            id: self.id,
+            timestamp: self.timestamp,
+            contexts: self.contexts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use crate::payload::{SelfDescribingJson, SnowplowEvent};
+    use crate::plugin::{PluginAction, SendOutcome, TrackerPlugin};
+    use crate::{Platform, Schema, SchemaVersion, TrackedEvent, Tracker};
+
+    fn test_event(name: &str) -> TrackedEvent<SelfDescribingJson> {
+        TrackedEvent::new(SelfDescribingJson::new(
+            Schema::new("com.example", "test", SchemaVersion::new(1, 0, 0)),
+            json!({ "name": name }),
+        ))
+    }
+
+    /// A tracker pointed at a port where nothing is listening, so that any
+    /// attempt to actually send events fails.
+    fn unreachable_tracker() -> Tracker {
+        Tracker::build(
+            "ns",
+            "app".to_owned(),
+            Platform::Desktop,
+            "http://127.0.0.1:1/com.snowplowanalytics.snowplow/tp2"
+                .parse()
+                .unwrap(),
+            reqwest::Client::new(),
+        )
+    }
+
+    #[derive(Default)]
+    struct DropEverything {
+        seen: AtomicUsize,
+        built: AtomicUsize,
+        sent: AtomicUsize,
+    }
+
+    impl TrackerPlugin for DropEverything {
+        fn before_track(&self, _event: &mut TrackedEvent<SelfDescribingJson>) -> PluginAction {
+            self.seen.fetch_add(1, Ordering::SeqCst);
+            PluginAction::Drop
+        }
+
+        fn after_build(&self, _event: &SnowplowEvent<'_, SelfDescribingJson>) {
+            self.built.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn after_send(&self, _outcome: &SendOutcome<'_>) {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropped_events_are_not_sent() {
+        let plugin = Arc::new(DropEverything::default());
+        let mut tracker = unreachable_tracker();
+        tracker.add_plugin(plugin.clone());
+
+        tracker
+            .track_batch([test_event("a"), test_event("b")])
+            .await
+            .expect("dropped events shouldn't hit the network");
+
+        assert_eq!(plugin.seen.load(Ordering::SeqCst), 2);
+        assert_eq!(plugin.built.load(Ordering::SeqCst), 0);
+        assert_eq!(plugin.sent.load(Ordering::SeqCst), 0);
+    }
+
+    struct Enrich;
+
+    impl TrackerPlugin for Enrich {
+        fn before_track(&self, event: &mut TrackedEvent<SelfDescribingJson>) -> PluginAction {
+            event.contexts.push(SelfDescribingJson::new(
+                Schema::new("com.example", "enriched", SchemaVersion::new(1, 0, 0)),
+                json!({ "ok": true }),
+            ));
+            PluginAction::Keep
+        }
+
+        fn after_build(&self, event: &SnowplowEvent<'_, SelfDescribingJson>) {
+            let built = serde_json::to_value(event).unwrap();
+            assert_eq!(
+                built["co"],
+                "{\"schema\":\"iglu:com.snowplowanalytics.snowplow/contexts/jsonschema/1-0-1\",\
+                 \"data\":[{\"schema\":\"iglu:com.example/enriched/jsonschema/1-0-0\",\
+                 \"data\":{\"ok\":true}}]}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugins_see_failed_sends() {
+        let plugin = Arc::new(DropEverything::default());
+        let mut tracker = unreachable_tracker();
+        tracker.add_plugin(Enrich);
+
+        struct CountFailures(Arc<DropEverything>);
+
+        impl TrackerPlugin for CountFailures {
+            fn after_build(&self, _event: &SnowplowEvent<'_, SelfDescribingJson>) {
+                self.0.built.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn after_send(&self, outcome: &SendOutcome<'_>) {
+                assert_eq!(outcome.event_ids.len(), 1);
+                assert!(outcome.result.is_err());
+                self.0.sent.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        tracker.add_plugin(CountFailures(plugin.clone()));
+
+        tracker
+            .track(test_event("a"))
+            .await
+            .expect_err("nothing is listening on the collector port");
+
+        assert_eq!(plugin.built.load(Ordering::SeqCst), 1);
+        assert_eq!(plugin.sent.load(Ordering::SeqCst), 1);
+    }
 }
@@ -6,13 +6,20 @@ event containers.
 
 use std::cell::Cell;
 use std::fmt::{Display, Write as _};
+use std::str::FromStr;
 
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine as _;
 use lazy_format::lazy_format;
+use serde::de::{self, DeserializeOwned};
 use serde::ser;
 use serde_json::to_string;
+use thiserror::Error;
 
 thread_local! {
-    static STRINGIFY_BUFFER: Cell<String> = Cell::new(String::new());
+    static STRINGIFY_BUFFER: Cell<String> = const { Cell::new(String::new()) };
 }
 
 /// Adapter type that serializes something by converting it into a string and
@@ -44,6 +51,24 @@ impl<T: Display> ser::Serialize for Stringify<T> {
     }
 }
 
+impl<'de, T> de::Deserialize<'de> for Stringify<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let string = String::deserialize(deserializer)?;
+
+        string
+            .parse()
+            .map(Stringify)
+            .map_err(|err| de::Error::custom(lazy_format!("Error parsing {string:?}: {err}")))
+    }
+}
+
 /// Adapter type that serializes something by first converting it to a JSON string
 /// and serializing that as a string. For some reason.
 #[derive(Debug, Clone, Copy, Default)]
@@ -61,3 +86,62 @@ impl<T: ser::Serialize> ser::Serialize for JsonString<T> {
         serializer.serialize_str(&jsonified)
     }
 }
+
+impl<'de, T: DeserializeOwned> de::Deserialize<'de> for JsonString<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let string = String::deserialize(deserializer)?;
+
+        serde_json::from_str(&string)
+            .map(JsonString)
+            .map_err(|json_err| {
+                de::Error::custom(lazy_format!(
+                    "Error deserializing from JSON string: {json_err}"
+                ))
+            })
+    }
+}
+
+/// Base64 as used by trackers for `ue_px` and `cx`: URL-safe, with or without
+/// padding
+const URL_SAFE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Some trackers use the standard base64 alphabet instead
+const STANDARD: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Decode base64 in any of the forms that trackers use for the base64
+/// variants of JSON fields, like `ue_px`.
+pub fn decode_base64(encoded: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    URL_SAFE
+        .decode(encoded)
+        .or_else(|_| STANDARD.decode(encoded))
+}
+
+/// An error decoding base64 encoded JSON; see [`decode_base64_json`].
+#[derive(Debug, Error)]
+pub enum Base64JsonError {
+    /// The value wasn't valid base64
+    #[error("Invalid base64")]
+    Base64(#[from] base64::DecodeError),
+
+    /// The decoded value couldn't be deserialized from JSON
+    #[error("Error deserializing from base64 JSON")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Decode a [`JsonString`] that was base64 encoded before being serialized,
+/// as is done with the `ue_px` and `cx` fields of an event.
+pub fn decode_base64_json<T: DeserializeOwned>(
+    encoded: &str,
+) -> Result<JsonString<T>, Base64JsonError> {
+    let decoded = decode_base64(encoded)?;
+    Ok(JsonString(serde_json::from_slice(&decoded)?))
+}
@@ -0,0 +1,93 @@
+// Copyright (c) 2022 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0,
+// and you may not use this file except in compliance with the Apache License Version 2.0.
+// You may obtain a copy of the Apache License Version 2.0 at http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the Apache License Version 2.0 is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the Apache License Version 2.0 for the specific language governing permissions and limitations there under.
+
+//! The panic hook is process-wide, so it's tested in its own binary, where
+//! no other tests can panic while it's installed. The tests here take turns
+//! installing it.
+
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use snowplow_tracker::lifecycle::install_panic_hook;
+use snowplow_tracker::micro::Micro;
+use snowplow_tracker::Tracker;
+
+static PANIC_HOOK: Mutex<()> = Mutex::new(());
+
+#[test]
+fn test_panic_hook() {
+    let _hook = PANIC_HOOK.lock().unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let micro = {
+        let _guard = runtime.enter();
+        Micro::start(([127, 0, 0, 1], 0).into()).unwrap()
+    };
+    let tracker = Tracker::builder()
+        .collector(micro.collector_url().as_str())
+        .app_id("app")
+        .build()
+        .unwrap();
+
+    install_panic_hook(Arc::new(tracker), Duration::from_secs(5));
+    let result = std::thread::Builder::new()
+        .name("doomed".to_owned())
+        .spawn(|| panic!("the disk is full"))
+        .unwrap()
+        .join();
+    assert!(result.is_err());
+
+    let events = micro.good();
+    assert_eq!(events.len(), 1);
+    let error = events[0]
+        .event
+        .unstruct_event
+        .as_ref()
+        .expect("the panic wasn't tracked");
+
+    assert_eq!(
+        error.0.schema.to_string(),
+        "iglu:com.snowplowanalytics.snowplow/application_error/jsonschema/1-0-2"
+    );
+    let data = &error.0.data;
+    assert_eq!(data["message"], "the disk is full");
+    assert_eq!(data["isFatal"], true);
+    assert_eq!(data["exceptionName"], "panic");
+    assert_eq!(data["className"], file!());
+    assert_eq!(data["threadName"], "doomed");
+
+    drop(std::panic::take_hook());
+}
+
+#[test]
+fn test_panic_hook_timeout() {
+    let _hook = PANIC_HOOK.lock().unwrap();
+
+    // A collector that accepts connections, but never responds
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let tracker = Tracker::builder()
+        .collector(format!("http://{}", listener.local_addr().unwrap()))
+        .app_id("app")
+        .build()
+        .unwrap();
+
+    install_panic_hook(Arc::new(tracker), Duration::from_millis(200));
+    let start = Instant::now();
+    let result = std::thread::spawn(|| panic!("the collector is down")).join();
+    assert!(result.is_err());
+    assert!(
+        start.elapsed() < Duration::from_secs(2),
+        "{:?}",
+        start.elapsed()
+    );
+
+    drop(std::panic::take_hook());
+}